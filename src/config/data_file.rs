@@ -0,0 +1,41 @@
+use crate::types::DataType;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum DataFileInterpolation {
+    Step,
+    #[default]
+    Linear,
+}
+
+impl DataFileInterpolation {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Step => "Step",
+            Self::Linear => "Linear",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Step" => Some(Self::Step),
+            "Linear" => Some(Self::Linear),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum DataFileClock {
+    #[default]
+    Time,
+    Beat,
+}
+
+// Source columns are concatenated in order, array cells are flattened into the value
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DataFileColumn {
+    pub name: String,
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub data_type: Option<DataType>,
+}
@@ -0,0 +1,53 @@
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum GeneratorPattern {
+    SolidColor {
+        color: [f32; 3],
+    },
+    LinearGradient {
+        from: [f32; 3],
+        to: [f32; 3],
+        angle: f32,
+    },
+    RadialGradient {
+        inner: [f32; 3],
+        outer: [f32; 3],
+    },
+    Checkerboard {
+        cell_size: usize,
+        even: [f32; 3],
+        odd: [f32; 3],
+    },
+    TestCard,
+    ValueNoise {
+        scale: f32,
+        seed: u32,
+        speed: f32,
+    },
+    PerlinNoise {
+        scale: f32,
+        seed: u32,
+        speed: f32,
+        octaves: usize,
+    },
+}
+
+impl GeneratorPattern {
+    pub fn is_animated(&self) -> bool {
+        match *self {
+            Self::ValueNoise { speed, .. } | Self::PerlinNoise { speed, .. } => speed != 0.0,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum GeneratorOverlay {
+    #[default]
+    None,
+    FrameCounter {
+        fps: f32,
+    },
+    Timecode {
+        fps: f32,
+    },
+}
@@ -1,4 +1,39 @@
-use crate::types::Speed;
+use std::collections::HashMap;
+
+use crate::types::{DataHolder, DataType, PlaybackConfig, Speed};
+
+use super::data_file::{DataFileClock, DataFileColumn, DataFileInterpolation};
+use super::generator::{GeneratorOverlay, GeneratorPattern};
+use super::midi::MidiMapping;
+use super::text::TextStyle;
+
+fn default_preload() -> usize {
+    8
+}
+
+fn default_time_column() -> String {
+    "time".to_string()
+}
+
+fn default_rate() -> f64 {
+    1.0
+}
+
+fn default_live_reload() -> bool {
+    true
+}
+
+fn default_fft_size() -> usize {
+    2048
+}
+
+fn default_band_count() -> usize {
+    16
+}
+
+fn default_osc_address() -> String {
+    "127.0.0.1".to_string()
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type")]
@@ -8,12 +43,35 @@ pub enum InputConfig {
         width: usize,
         height: usize,
         speed: Speed,
+        #[serde(default)]
+        playback: PlaybackConfig,
     },
     Picture {
         path: String,
         width: usize,
         height: usize,
     },
+    ImageSequence {
+        path: String,
+        width: usize,
+        height: usize,
+        speed: Speed,
+        #[serde(default)]
+        playback: PlaybackConfig,
+        #[serde(default = "default_preload")]
+        preload: usize,
+    },
+    Audio {
+        path: String,
+        #[serde(default = "default_fft_size")]
+        fft_size: usize,
+        #[serde(default = "default_band_count")]
+        band_count: usize,
+        #[serde(default)]
+        bpm: Option<f64>,
+        #[serde(default)]
+        looping: bool,
+    },
     Cam {
         path: String,
         width: usize,
@@ -21,10 +79,92 @@ pub enum InputConfig {
     },
     Midi {
         name: String,
+        #[serde(default)]
+        mappings: Vec<MidiMapping>,
+    },
+    Osc {
+        #[serde(default = "default_osc_address")]
+        address: String,
+        port: u16,
+        #[serde(default)]
+        prefix: String,
+        #[serde(default)]
+        uniforms: HashMap<String, DataType>,
+    },
+    Generator {
+        width: usize,
+        height: usize,
+        pattern: GeneratorPattern,
+        #[serde(default)]
+        overlay: GeneratorOverlay,
+    },
+    Text {
+        width: usize,
+        height: usize,
+        #[serde(default)]
+        text: String,
+        style: TextStyle,
+    },
+    Pointer {
+        width: usize,
+        height: usize,
+    },
+    Keyboard {},
+    Ipc {
+        path: String,
+    },
+    Script {
+        path: String,
+        #[serde(default = "default_live_reload")]
+        live_reload: bool,
+        #[serde(default)]
+        properties: HashMap<String, DataHolder>,
+        #[serde(default)]
+        uniforms: HashMap<String, DataType>,
+    },
+    DataFile {
+        path: String,
+        #[serde(default = "default_time_column")]
+        time_column: String,
+        #[serde(default)]
+        clock: DataFileClock,
+        #[serde(default)]
+        interpolation: DataFileInterpolation,
+        #[serde(default = "default_rate")]
+        rate: f64,
+        #[serde(default)]
+        columns: Vec<DataFileColumn>,
+        #[serde(default)]
+        playback: PlaybackConfig,
+    },
+    Custom {
+        kind: String,
+        #[serde(default)]
+        params: HashMap<String, DataHolder>,
     },
 }
 
 impl InputConfig {
+    pub fn get_type_tag(&self) -> &str {
+        match self {
+            InputConfig::Video { .. } => "Video",
+            InputConfig::Picture { .. } => "Picture",
+            InputConfig::ImageSequence { .. } => "ImageSequence",
+            InputConfig::Audio { .. } => "Audio",
+            InputConfig::Cam { .. } => "Cam",
+            InputConfig::Midi { .. } => "Midi",
+            InputConfig::Osc { .. } => "Osc",
+            InputConfig::Generator { .. } => "Generator",
+            InputConfig::Text { .. } => "Text",
+            InputConfig::Pointer { .. } => "Pointer",
+            InputConfig::Keyboard { .. } => "Keyboard",
+            InputConfig::Ipc { .. } => "Ipc",
+            InputConfig::Script { .. } => "Script",
+            InputConfig::DataFile { .. } => "DataFile",
+            InputConfig::Custom { kind, .. } => kind,
+        }
+    }
+
     pub fn is_video(&self) -> bool {
         matches!(self, InputConfig::Video { .. })
     }
@@ -33,6 +173,14 @@ impl InputConfig {
         matches!(self, InputConfig::Picture { .. })
     }
 
+    pub fn is_image_sequence(&self) -> bool {
+        matches!(self, InputConfig::ImageSequence { .. })
+    }
+
+    pub fn is_audio(&self) -> bool {
+        matches!(self, InputConfig::Audio { .. })
+    }
+
     pub fn is_cam(&self) -> bool {
         matches!(self, InputConfig::Cam { .. })
     }
@@ -40,4 +188,40 @@ impl InputConfig {
     pub fn is_midi(&self) -> bool {
         matches!(self, InputConfig::Midi { .. })
     }
+
+    pub fn is_osc(&self) -> bool {
+        matches!(self, InputConfig::Osc { .. })
+    }
+
+    pub fn is_generator(&self) -> bool {
+        matches!(self, InputConfig::Generator { .. })
+    }
+
+    pub fn is_text(&self) -> bool {
+        matches!(self, InputConfig::Text { .. })
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        matches!(self, InputConfig::Pointer { .. })
+    }
+
+    pub fn is_keyboard(&self) -> bool {
+        matches!(self, InputConfig::Keyboard { .. })
+    }
+
+    pub fn is_ipc(&self) -> bool {
+        matches!(self, InputConfig::Ipc { .. })
+    }
+
+    pub fn is_script(&self) -> bool {
+        matches!(self, InputConfig::Script { .. })
+    }
+
+    pub fn is_data_file(&self) -> bool {
+        matches!(self, InputConfig::DataFile { .. })
+    }
+
+    pub fn is_custom(&self) -> bool {
+        matches!(self, InputConfig::Custom { .. })
+    }
 }
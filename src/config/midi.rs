@@ -0,0 +1,347 @@
+use crate::config::project::ProjectConfig;
+use crate::protocol::midi::MidiMessage;
+use crate::types::DataHolder;
+
+pub const NRPN_PARAMETER_MSB: u8 = 99;
+pub const NRPN_PARAMETER_LSB: u8 = 98;
+pub const RPN_PARAMETER_MSB: u8 = 101;
+pub const RPN_PARAMETER_LSB: u8 = 100;
+pub const DATA_ENTRY_MSB: u8 = 6;
+pub const DATA_ENTRY_LSB: u8 = 38;
+
+fn default_range() -> (f32, f32) {
+    (0.0, 1.0)
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum MidiSource {
+    ControlChange { controller: u8 },
+    ControlChange14 { controller: u8 },
+    Note { note: u8 },
+    PitchBend,
+    Nrpn { parameter: u16 },
+}
+
+impl MidiSource {
+    pub fn get_resolution(&self) -> u16 {
+        match self {
+            Self::ControlChange { .. } | Self::Note { .. } => 127,
+            Self::ControlChange14 { .. } | Self::PitchBend | Self::Nrpn { .. } => 16383,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum MidiTarget {
+    ProjectVariable { name: String },
+    StageVariable { stage: String, name: String },
+}
+
+impl MidiTarget {
+    pub fn get_uniform_name(&self) -> String {
+        match self {
+            Self::ProjectVariable { name } => name.clone(),
+            Self::StageVariable { stage, name } => format!("{}_{}", stage, name),
+        }
+    }
+
+    pub fn get_value<'a>(&self, project: &'a ProjectConfig) -> Option<&'a DataHolder> {
+        match self {
+            Self::ProjectVariable { name } => project.variables.get(name).map(|(value, _)| value),
+            Self::StageVariable { stage, name } => std::iter::once(&project.final_stage)
+                .chain(project.render_chain.iter())
+                .find(|stage_config| &stage_config.name == stage)
+                .and_then(|stage_config| stage_config.variables.get(name))
+                .map(|(value, _, _)| value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub enum MidiCurve {
+    #[default]
+    Linear,
+    Exponential(f32),
+    Logarithmic(f32),
+    SCurve,
+}
+
+impl MidiCurve {
+    pub fn apply(&self, value: f32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+        match *self {
+            Self::Linear => value,
+            Self::Exponential(exponent) => value.powf(exponent.max(f32::EPSILON)),
+            Self::Logarithmic(base) if base > 1.0 => (1.0 + value * (base - 1.0)).ln() / base.ln(),
+            Self::Logarithmic(_) => value,
+            Self::SCurve => value * value * (3.0 - 2.0 * value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub enum MidiTakeover {
+    #[default]
+    Jump,
+    Pickup,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct MidiMapping {
+    pub source: MidiSource,
+    #[serde(default)]
+    pub channel: Option<u8>,
+    pub target: MidiTarget,
+    #[serde(default = "default_range")]
+    pub range: (f32, f32),
+    #[serde(default)]
+    pub curve: MidiCurve,
+    #[serde(default)]
+    pub takeover: MidiTakeover,
+}
+
+impl MidiMapping {
+    pub fn new(source: MidiSource, channel: Option<u8>, target: MidiTarget) -> Self {
+        Self {
+            source,
+            channel,
+            target,
+            range: default_range(),
+            curve: MidiCurve::default(),
+            takeover: MidiTakeover::default(),
+        }
+    }
+
+    pub fn matches(&self, source: MidiSource, channel: u8) -> bool {
+        self.source == source && self.channel.map_or(true, |filter| filter == channel)
+    }
+
+    pub fn map_value(&self, raw_value: u16) -> f32 {
+        let normalized = raw_value.min(self.source.get_resolution()) as f32
+            / self.source.get_resolution() as f32;
+        let (min, max) = self.range;
+
+        min + self.curve.apply(normalized) * (max - min)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct MidiLearn {
+    pub target: MidiTarget,
+    pub range: (f32, f32),
+    pub curve: MidiCurve,
+    pub takeover: MidiTakeover,
+    pub omni: bool,
+    held_controller: Option<(u8, u8)>,
+    nrpn_parameter: Option<(u8, u8, Option<u8>)>,
+}
+
+impl MidiLearn {
+    pub fn new(target: MidiTarget) -> Self {
+        Self {
+            target,
+            range: default_range(),
+            curve: MidiCurve::default(),
+            takeover: MidiTakeover::default(),
+            omni: false,
+            held_controller: None,
+            nrpn_parameter: None,
+        }
+    }
+
+    fn build(&self, source: MidiSource, channel: u8) -> MidiMapping {
+        MidiMapping {
+            source,
+            channel: if self.omni { None } else { Some(channel) },
+            target: self.target.clone(),
+            range: self.range,
+            curve: self.curve,
+            takeover: self.takeover,
+        }
+    }
+
+    pub fn capture(&mut self, message: &MidiMessage) -> Option<MidiMapping> {
+        let held_controller = self.held_controller.take();
+
+        match *message {
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => match controller {
+                NRPN_PARAMETER_MSB => {
+                    self.nrpn_parameter = Some((channel, value, None));
+                    None
+                }
+                NRPN_PARAMETER_LSB => {
+                    if let Some((nrpn_channel, msb, _)) = self.nrpn_parameter {
+                        if nrpn_channel == channel {
+                            self.nrpn_parameter = Some((channel, msb, Some(value)));
+                        }
+                    }
+                    None
+                }
+                RPN_PARAMETER_MSB | RPN_PARAMETER_LSB => {
+                    self.nrpn_parameter = None;
+                    None
+                }
+                DATA_ENTRY_MSB | DATA_ENTRY_LSB => match self.nrpn_parameter {
+                    Some((nrpn_channel, msb, Some(lsb))) if nrpn_channel == channel => {
+                        let parameter = (msb as u16) << 7 | lsb as u16;
+                        Some(self.build(MidiSource::Nrpn { parameter }, channel))
+                    }
+                    _ => self.capture_controller(held_controller, channel, controller),
+                },
+                _ => self.capture_controller(held_controller, channel, controller),
+            },
+            _ if held_controller.is_some() => self.finish_controller(held_controller),
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } if velocity > 0 => Some(self.build(MidiSource::Note { note }, channel)),
+            MidiMessage::PitchBend { channel, .. } => {
+                Some(self.build(MidiSource::PitchBend, channel))
+            }
+            _ => None,
+        }
+    }
+
+    // Controllers 0-31 may be the MSB of a 14-bit pair, so they are held until the next message
+    fn capture_controller(
+        &mut self,
+        held_controller: Option<(u8, u8)>,
+        channel: u8,
+        controller: u8,
+    ) -> Option<MidiMapping> {
+        match held_controller {
+            Some((held_channel, held_controller))
+                if held_channel == channel && held_controller + 32 == controller =>
+            {
+                Some(self.build(
+                    MidiSource::ControlChange14 {
+                        controller: held_controller,
+                    },
+                    channel,
+                ))
+            }
+            Some(_) => self.finish_controller(held_controller),
+            None if controller < 32 => {
+                self.held_controller = Some((channel, controller));
+                None
+            }
+            None => Some(self.build(MidiSource::ControlChange { controller }, channel)),
+        }
+    }
+
+    fn finish_controller(&self, held_controller: Option<(u8, u8)>) -> Option<MidiMapping> {
+        held_controller.map(|(channel, controller)| {
+            self.build(MidiSource::ControlChange { controller }, channel)
+        })
+    }
+
+    pub fn finish(&mut self) -> Option<MidiMapping> {
+        let held_controller = self.held_controller.take();
+        self.finish_controller(held_controller)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control_change(channel: u8, controller: u8) -> MidiMessage {
+        MidiMessage::ControlChange {
+            channel,
+            controller,
+            value: 64,
+        }
+    }
+
+    fn get_learn() -> MidiLearn {
+        MidiLearn::new(MidiTarget::StageVariable {
+            stage: "blur".to_string(),
+            name: "radius".to_string(),
+        })
+    }
+
+    #[test]
+    fn learn_captures_plain_controllers_and_notes() {
+        let mut learn = get_learn();
+        let mapping = learn.capture(&control_change(2, 74)).unwrap();
+        assert_eq!(mapping.source, MidiSource::ControlChange { controller: 74 });
+        assert_eq!(mapping.channel, Some(2));
+        assert_eq!(mapping.target.get_uniform_name(), "blur_radius");
+
+        learn.omni = true;
+        let note_on = MidiMessage::NoteOn {
+            channel: 3,
+            note: 60,
+            velocity: 90,
+        };
+        let mapping = learn.capture(&note_on).unwrap();
+        assert_eq!(mapping.source, MidiSource::Note { note: 60 });
+        assert_eq!(mapping.channel, None);
+    }
+
+    #[test]
+    fn learn_holds_low_controllers_for_a_14_bit_pair() {
+        let mut learn = get_learn();
+        assert_eq!(learn.capture(&control_change(0, 7)), None);
+        assert_eq!(
+            learn.capture(&control_change(0, 39)).unwrap().source,
+            MidiSource::ControlChange14 { controller: 7 }
+        );
+
+        // Anything else than the matching LSB releases the held controller as a plain one
+        assert_eq!(learn.capture(&control_change(0, 7)), None);
+        assert_eq!(
+            learn.capture(&control_change(1, 39)).unwrap().source,
+            MidiSource::ControlChange { controller: 7 }
+        );
+
+        assert_eq!(learn.capture(&control_change(0, 1)), None);
+        assert_eq!(
+            learn.finish().unwrap().source,
+            MidiSource::ControlChange { controller: 1 }
+        );
+        assert_eq!(learn.finish(), None);
+    }
+
+    #[test]
+    fn learn_captures_nrpn_parameters() {
+        let mut learn = get_learn();
+        assert_eq!(learn.capture(&control_change(0, NRPN_PARAMETER_MSB)), None);
+        assert_eq!(learn.capture(&control_change(0, NRPN_PARAMETER_LSB)), None);
+        assert_eq!(
+            learn
+                .capture(&control_change(0, DATA_ENTRY_MSB))
+                .unwrap()
+                .source,
+            MidiSource::Nrpn {
+                parameter: (64 << 7) | 64
+            }
+        );
+    }
+
+    #[test]
+    fn mappings_apply_range_and_curve() {
+        let mut mapping = MidiMapping::new(
+            MidiSource::ControlChange { controller: 1 },
+            Some(0),
+            MidiTarget::ProjectVariable {
+                name: "level".to_string(),
+            },
+        );
+        mapping.range = (10.0, 20.0);
+        assert_eq!(mapping.map_value(0), 10.0);
+        assert_eq!(mapping.map_value(127), 20.0);
+        assert_eq!(mapping.map_value(1000), 20.0);
+
+        mapping.curve = MidiCurve::Exponential(2.0);
+        assert!((mapping.map_value(64) - (10.0 + 10.0 * (64.0f32 / 127.0).powi(2))).abs() < 1e-5);
+
+        assert!(mapping.matches(MidiSource::ControlChange { controller: 1 }, 0));
+        assert!(!mapping.matches(MidiSource::ControlChange { controller: 1 }, 1));
+    }
+}
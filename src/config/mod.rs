@@ -1,5 +1,9 @@
+pub mod data_file;
 pub mod filter;
+pub mod generator;
 pub mod input;
+pub mod midi;
 pub mod project;
 pub mod rendering;
 pub mod server;
+pub mod text;
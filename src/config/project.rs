@@ -1,9 +1,12 @@
 use std::collections::hash_map::HashMap;
+use std::collections::HashSet;
 use std::path::PathBuf;
 
+use anyhow::{bail, Result};
+
 use crate::config::server::ServerConfig;
 
-use crate::types::{Automation, DataHolder};
+use crate::types::{Automation, DataHolder, InputReference, InputSource, PROJECT_OUTPUT_NAME};
 
 use super::input::InputConfig;
 use super::rendering::RenderStageConfig;
@@ -32,3 +35,298 @@ pub struct ProjectConfig {
     pub render_chain: Vec<RenderStageConfig>,
     pub final_stage: RenderStageConfig,
 }
+
+impl ProjectConfig {
+    pub fn get_stage_index(&self, stage: &str) -> Option<usize> {
+        if self.final_stage.name == stage {
+            return Some(self.render_chain.len());
+        }
+
+        self.render_chain
+            .iter()
+            .position(|stage_config| stage_config.name == stage)
+    }
+
+    // Stages render in chain order and the final stage last, reading a stage that has not
+    // rendered yet in the current frame is only allowed through an explicit feedback source
+    pub fn resolve_input(
+        &self,
+        stage_index: usize,
+        reference: &InputReference,
+    ) -> Result<InputSource> {
+        let source = match reference {
+            InputReference::Source(source) => source.clone(),
+            InputReference::Named(name) => {
+                let is_input = self.inputs.contains_key(name);
+                let is_stage = self.get_stage_index(name).is_some();
+                match (is_input, is_stage) {
+                    (true, true) => bail!(
+                        "{} is both an input and a render stage, use an explicit input source",
+                        name
+                    ),
+                    (true, false) => InputSource::Input(name.clone()),
+                    (false, true) => InputSource::Stage(name.clone()),
+                    (false, false) => bail!("Unknown input or render stage: {}", name),
+                }
+            }
+        };
+
+        match &source {
+            InputSource::Input(name) => {
+                if !self.inputs.contains_key(name) {
+                    bail!("Unknown input: {}", name);
+                }
+            }
+            InputSource::Stage(name) => match self.get_stage_index(name) {
+                Some(index) if index < stage_index => (),
+                Some(_) => bail!(
+                    "Render stage {} is not rendered yet, use PreviousStage to read its last frame",
+                    name
+                ),
+                None => bail!("Unknown render stage: {}", name),
+            },
+            InputSource::PreviousStage(name) => {
+                if self.get_stage_index(name).is_none() {
+                    bail!("Unknown render stage: {}", name);
+                }
+            }
+            InputSource::ProjectOutput => (),
+        }
+
+        Ok(source)
+    }
+
+    pub fn resolve_stage_inputs(&self, stage: &str) -> Result<HashMap<String, InputSource>> {
+        match self.get_stage_index(stage) {
+            Some(stage_index) => self.resolve_stage_inputs_at(stage_index),
+            None => bail!("Unknown render stage: {}", stage),
+        }
+    }
+
+    fn resolve_stage_inputs_at(&self, stage_index: usize) -> Result<HashMap<String, InputSource>> {
+        let stage_config = self
+            .render_chain
+            .get(stage_index)
+            .unwrap_or(&self.final_stage);
+
+        let mut sources = HashMap::new();
+        for (name, sampler) in stage_config.inputs.iter() {
+            let source = self
+                .resolve_input(stage_index, sampler.get_reference())
+                .map_err(|e| {
+                    e.context(format!(
+                        "Invalid input {} of stage {}",
+                        name, stage_config.name
+                    ))
+                })?;
+            sources.insert(name.clone(), source);
+        }
+
+        Ok(sources)
+    }
+
+    pub fn validate_inputs(&self) -> Result<()> {
+        let mut stage_names = HashSet::new();
+        for stage_config in self.render_chain.iter().chain(Some(&self.final_stage)) {
+            if !stage_names.insert(stage_config.name.as_str()) {
+                bail!("Duplicate render stage name: {}", stage_config.name);
+            }
+        }
+
+        // A plain "ProjectOutput" reference always parses as the feedback source
+        if stage_names.contains(PROJECT_OUTPUT_NAME)
+            || self.inputs.contains_key(PROJECT_OUTPUT_NAME)
+        {
+            bail!("{} is a reserved input name", PROJECT_OUTPUT_NAME);
+        }
+
+        for stage_index in 0..=self.render_chain.len() {
+            self.resolve_stage_inputs_at(stage_index)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::filter::FilterMode;
+    use crate::types::{BufferPrecision, InputSampler};
+
+    fn create_stage(name: &str, inputs: &[(&str, InputReference)]) -> RenderStageConfig {
+        RenderStageConfig {
+            name: name.to_string(),
+            filter: "copy".to_string(),
+            filter_mode_params: FilterMode::Rectangle(0.0, 0.0, 1.0, 1.0),
+            inputs: inputs
+                .iter()
+                .map(|(name, reference)| {
+                    (name.to_string(), InputSampler::Linear(reference.clone()))
+                })
+                .collect(),
+            variables: HashMap::new(),
+            precision: BufferPrecision::U8,
+        }
+    }
+
+    fn create_project(
+        render_chain: Vec<RenderStageConfig>,
+        final_stage: RenderStageConfig,
+    ) -> ProjectConfig {
+        let mut inputs = HashMap::new();
+        inputs.insert("keys".to_string(), InputConfig::Keyboard {});
+
+        ProjectConfig {
+            bpm: 120.0,
+            view: ViewConfig {
+                width: 640,
+                height: 480,
+                fullscreen: false,
+                target_fps: 60.0,
+                dynamic: false,
+                vsync: true,
+                screenshot_path: PathBuf::new(),
+                screenshot: false,
+                screenshot_frame_count: 0,
+                locked_speed: false,
+            },
+            server: ServerConfig {
+                ip: "127.0.0.1".to_string(),
+                port: 3000,
+                enable: false,
+            },
+            variables: HashMap::new(),
+            inputs,
+            render_chain,
+            final_stage,
+        }
+    }
+
+    fn named(name: &str) -> InputReference {
+        InputReference::Named(name.to_string())
+    }
+
+    #[test]
+    fn sources_resolve_in_render_order() {
+        let project = create_project(
+            vec![
+                create_stage("first", &[("keys", named("keys"))]),
+                create_stage(
+                    "second",
+                    &[
+                        ("first", named("first")),
+                        (
+                            "own",
+                            InputSource::PreviousStage("second".to_string()).into(),
+                        ),
+                        ("output", InputSource::ProjectOutput.into()),
+                    ],
+                ),
+            ],
+            create_stage(
+                "final",
+                &[("second", InputSource::Stage("second".to_string()).into())],
+            ),
+        );
+        project.validate_inputs().unwrap();
+
+        let sources = project.resolve_stage_inputs("second").unwrap();
+        assert_eq!(sources["first"], InputSource::Stage("first".to_string()));
+        assert_eq!(
+            sources["own"],
+            InputSource::PreviousStage("second".to_string())
+        );
+        assert_eq!(sources["output"], InputSource::ProjectOutput);
+        assert_eq!(
+            project.resolve_stage_inputs("first").unwrap()["keys"],
+            InputSource::Input("keys".to_string())
+        );
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        let project = create_project(
+            vec![create_stage("first", &[]), create_stage("first", &[])],
+            create_stage("final", &[]),
+        );
+        assert!(project.validate_inputs().is_err());
+
+        let project = create_project(
+            vec![create_stage("keys", &[])],
+            create_stage("final", &[("keys", named("keys"))]),
+        );
+        assert!(project.validate_inputs().is_err());
+
+        // Explicit sources settle which of the two is meant
+        let project = create_project(
+            vec![create_stage("keys", &[])],
+            create_stage(
+                "final",
+                &[("keys", InputSource::Input("keys".to_string()).into())],
+            ),
+        );
+        project.validate_inputs().unwrap();
+
+        let mut project = create_project(vec![], create_stage("final", &[]));
+        project
+            .inputs
+            .insert(PROJECT_OUTPUT_NAME.to_string(), InputConfig::Keyboard {});
+        assert!(project.validate_inputs().is_err());
+    }
+
+    #[test]
+    fn unknown_sources_are_rejected() {
+        for reference in [
+            named("missing"),
+            InputSource::Input("first".to_string()).into(),
+            InputSource::Stage("keys".to_string()).into(),
+            InputSource::PreviousStage("missing".to_string()).into(),
+        ] {
+            let project = create_project(
+                vec![create_stage("first", &[])],
+                create_stage("final", &[("input", reference.clone())]),
+            );
+            assert!(project.validate_inputs().is_err(), "{:?}", reference);
+        }
+
+        let project = create_project(vec![], create_stage("final", &[]));
+        assert!(project.resolve_stage_inputs("missing").is_err());
+    }
+
+    #[test]
+    fn stages_that_have_not_rendered_are_rejected() {
+        for stage in ["first", "second"] {
+            let project = create_project(
+                vec![
+                    create_stage("first", &[]),
+                    create_stage(
+                        "second",
+                        &[("input", InputSource::Stage(stage.to_string()).into())],
+                    ),
+                ],
+                create_stage("final", &[]),
+            );
+            assert_eq!(project.validate_inputs().is_err(), stage == "second");
+        }
+
+        let project = create_project(
+            vec![create_stage("first", &[("final", named("final"))])],
+            create_stage("final", &[]),
+        );
+        assert!(project.validate_inputs().is_err());
+    }
+
+    #[test]
+    fn plain_project_output_parses_as_the_feedback_source() {
+        let sampler: InputSampler =
+            serde_json::from_str(r#"{"Nearest": "ProjectOutput"}"#).unwrap();
+        assert_eq!(
+            sampler.get_reference(),
+            &InputReference::Source(InputSource::ProjectOutput)
+        );
+
+        let sampler: InputSampler = serde_json::from_str(r#"{"Nearest": "first"}"#).unwrap();
+        assert_eq!(sampler.get_reference(), &named("first"));
+    }
+}
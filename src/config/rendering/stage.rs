@@ -5,13 +5,15 @@ use crate::{
     types::{Automation, BufferPrecision, DataHolder, InputSampler},
 };
 
+pub type StageVariable = (DataHolder, Automation, Option<(String, DataHolder)>);
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct RenderStageConfig {
     pub name: String,
     pub filter: String,
     pub filter_mode_params: FilterMode,
     pub inputs: HashMap<String, InputSampler>,
-    pub variables: HashMap<String, (DataHolder, Automation, Option<(String, DataHolder)>)>,
+    pub variables: HashMap<String, StageVariable>,
     #[serde(default)]
     pub precision: BufferPrecision,
 }
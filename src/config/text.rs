@@ -0,0 +1,85 @@
+fn default_text_color() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_line_spacing() -> f32 {
+    1.2
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum TextAlignment {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+impl TextAlignment {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Left => "Left",
+            Self::Center => "Center",
+            Self::Right => "Right",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Left" => Some(Self::Left),
+            "Center" => Some(Self::Center),
+            "Right" => Some(Self::Right),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum TextVerticalAlignment {
+    Top,
+    #[default]
+    Middle,
+    Bottom,
+}
+
+impl TextVerticalAlignment {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Top => "Top",
+            Self::Middle => "Middle",
+            Self::Bottom => "Bottom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Top" => Some(Self::Top),
+            "Middle" => Some(Self::Middle),
+            "Bottom" => Some(Self::Bottom),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TextOutline {
+    pub width: f32,
+    pub color: [f32; 3],
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TextStyle {
+    pub font: String,
+    pub size: f32,
+    #[serde(default)]
+    pub alignment: TextAlignment,
+    #[serde(default)]
+    pub vertical_alignment: TextVerticalAlignment,
+    #[serde(default = "default_text_color")]
+    pub color: [f32; 3],
+    #[serde(default)]
+    pub background: [f32; 3],
+    #[serde(default)]
+    pub outline: Option<TextOutline>,
+    #[serde(default = "default_line_spacing")]
+    pub line_spacing: f32,
+}
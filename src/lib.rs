@@ -7,7 +7,10 @@ use std::path::{Path, PathBuf};
 use directories_next::ProjectDirs;
 
 pub mod config;
+pub mod protocol;
+pub mod providers;
 pub mod shader;
+pub mod timing;
 pub mod types;
 
 pub fn get_data_path() -> PathBuf {
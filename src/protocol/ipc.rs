@@ -0,0 +1,419 @@
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+
+use crate::types::DataHolder;
+
+pub const IPC_MAGIC: &[u8; 4] = b"WVRI";
+pub const IPC_VERSION: u8 = 1;
+pub const IPC_HEADER_SIZE: usize = 28;
+pub const IPC_MAX_PAYLOAD_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum IpcFormat {
+    Rgb8,
+    Rgba8,
+    Gray8,
+    Uniforms,
+}
+
+impl IpcFormat {
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Self::Rgb8 => 0,
+            Self::Rgba8 => 1,
+            Self::Gray8 => 2,
+            Self::Uniforms => 3,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Rgb8),
+            1 => Some(Self::Rgba8),
+            2 => Some(Self::Gray8),
+            3 => Some(Self::Uniforms),
+            _ => None,
+        }
+    }
+
+    pub fn get_channel_count(&self) -> Option<usize> {
+        match self {
+            Self::Rgb8 => Some(3),
+            Self::Rgba8 => Some(4),
+            Self::Gray8 => Some(1),
+            Self::Uniforms => None,
+        }
+    }
+}
+
+pub fn get_ipc_timestamp() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+// Little endian layout: magic, version, format, two reserved bytes, width, height,
+// timestamp in seconds as f64 and payload size
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct IpcHeader {
+    pub format: IpcFormat,
+    pub width: u32,
+    pub height: u32,
+    pub timestamp: f64,
+    pub payload_size: u32,
+}
+
+impl IpcHeader {
+    pub fn encode(&self) -> [u8; IPC_HEADER_SIZE] {
+        let mut header = [0; IPC_HEADER_SIZE];
+        header[0..4].copy_from_slice(IPC_MAGIC);
+        header[4] = IPC_VERSION;
+        header[5] = self.format.to_byte();
+        header[8..12].copy_from_slice(&self.width.to_le_bytes());
+        header[12..16].copy_from_slice(&self.height.to_le_bytes());
+        header[16..24].copy_from_slice(&self.timestamp.to_le_bytes());
+        header[24..28].copy_from_slice(&self.payload_size.to_le_bytes());
+
+        header
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < IPC_HEADER_SIZE {
+            bail!("Truncated IPC header");
+        }
+
+        if &data[0..4] != IPC_MAGIC {
+            bail!("Invalid IPC magic: {:?}", &data[0..4]);
+        }
+
+        if data[4] != IPC_VERSION {
+            bail!("Unsupported IPC version: {}", data[4]);
+        }
+
+        let format = match IpcFormat::from_byte(data[5]) {
+            Some(format) => format,
+            None => bail!("Unsupported IPC format: {}", data[5]),
+        };
+
+        let header = Self {
+            format,
+            width: u32::from_le_bytes(data[8..12].try_into()?),
+            height: u32::from_le_bytes(data[12..16].try_into()?),
+            timestamp: f64::from_le_bytes(data[16..24].try_into()?),
+            payload_size: u32::from_le_bytes(data[24..28].try_into()?),
+        };
+
+        if header.payload_size as usize > IPC_MAX_PAYLOAD_SIZE {
+            bail!("IPC payload too large: {} bytes", header.payload_size);
+        }
+
+        if let Some(channel_count) = format.get_channel_count() {
+            let expected_size = header.width as usize * header.height as usize * channel_count;
+            if header.payload_size as usize != expected_size {
+                bail!(
+                    "IPC frame payload size {} does not match {}x{} {:?}",
+                    header.payload_size,
+                    header.width,
+                    header.height,
+                    format
+                );
+            }
+        }
+
+        Ok(header)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum IpcMessage {
+    Frame {
+        timestamp: f64,
+        width: u32,
+        height: u32,
+        format: IpcFormat,
+        data: Vec<u8>,
+    },
+    Uniforms {
+        timestamp: f64,
+        values: BTreeMap<String, DataHolder>,
+    },
+}
+
+impl IpcMessage {
+    pub fn get_timestamp(&self) -> f64 {
+        match self {
+            Self::Frame { timestamp, .. } | Self::Uniforms { timestamp, .. } => *timestamp,
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let (header, payload) = match self {
+            Self::Frame {
+                timestamp,
+                width,
+                height,
+                format,
+                data,
+            } => {
+                if *format == IpcFormat::Uniforms {
+                    bail!("IPC frames need a pixel format");
+                }
+
+                (
+                    IpcHeader {
+                        format: *format,
+                        width: *width,
+                        height: *height,
+                        timestamp: *timestamp,
+                        payload_size: data.len() as u32,
+                    },
+                    data.clone(),
+                )
+            }
+            Self::Uniforms { timestamp, values } => {
+                // Uniform values are JSON encoded so that any language can produce them
+                let payload =
+                    serde_json::to_vec(values).context("Failed to encode IPC uniforms")?;
+
+                (
+                    IpcHeader {
+                        format: IpcFormat::Uniforms,
+                        width: 0,
+                        height: 0,
+                        timestamp: *timestamp,
+                        payload_size: payload.len() as u32,
+                    },
+                    payload,
+                )
+            }
+        };
+
+        if payload.len() > IPC_MAX_PAYLOAD_SIZE {
+            bail!("IPC payload too large: {} bytes", payload.len());
+        }
+
+        let mut buffer = header.encode().to_vec();
+        buffer.extend_from_slice(&payload);
+        // Validates frame dimensions against the payload
+        IpcHeader::decode(&buffer)?;
+
+        Ok(buffer)
+    }
+
+    pub fn decode(header: &IpcHeader, payload: &[u8]) -> Result<Self> {
+        if payload.len() != header.payload_size as usize {
+            bail!(
+                "IPC payload size {} does not match header size {}",
+                payload.len(),
+                header.payload_size
+            );
+        }
+
+        match header.format {
+            IpcFormat::Uniforms => Ok(Self::Uniforms {
+                timestamp: header.timestamp,
+                values: serde_json::from_slice(payload).context("Failed to decode IPC uniforms")?,
+            }),
+            format => Ok(Self::Frame {
+                timestamp: header.timestamp,
+                width: header.width,
+                height: header.height,
+                format,
+                data: payload.to_vec(),
+            }),
+        }
+    }
+}
+
+// Accumulates stream bytes and splits them into messages, a decoding error leaves the
+// stream out of sync so the connection should be dropped
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IpcReader {
+    buffer: Vec<u8>,
+    offset: usize,
+    header: Option<IpcHeader>,
+}
+
+impl IpcReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        // Consumed bytes are only discarded once they make up most of the buffer, so
+        // large frames are not shifted for every message read
+        if self.offset == self.buffer.len() {
+            self.buffer.clear();
+            self.offset = 0;
+        } else if self.offset > self.buffer.len() / 2 {
+            self.buffer.drain(..self.offset);
+            self.offset = 0;
+        }
+
+        self.buffer.extend_from_slice(data);
+    }
+
+    pub fn get_pending_size(&self) -> usize {
+        self.buffer.len() - self.offset
+    }
+
+    pub fn next_message(&mut self) -> Result<Option<IpcMessage>> {
+        let header = match self.header {
+            Some(header) => header,
+            None if self.get_pending_size() >= IPC_HEADER_SIZE => {
+                let header = IpcHeader::decode(&self.buffer[self.offset..])?;
+                self.offset += IPC_HEADER_SIZE;
+                self.header = Some(header);
+                header
+            }
+            None => return Ok(None),
+        };
+
+        let payload_size = header.payload_size as usize;
+        if self.get_pending_size() < payload_size {
+            return Ok(None);
+        }
+
+        self.header = None;
+        let payload = &self.buffer[self.offset..self.offset + payload_size];
+        self.offset += payload_size;
+        IpcMessage::decode(&header, payload).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_frame() -> IpcMessage {
+        IpcMessage::Frame {
+            timestamp: 1.5,
+            width: 2,
+            height: 1,
+            format: IpcFormat::Rgba8,
+            data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        }
+    }
+
+    fn get_uniforms() -> IpcMessage {
+        let mut values = BTreeMap::new();
+        values.insert("level".to_string(), DataHolder::Float(0.5));
+        values.insert("active".to_string(), DataHolder::Bool(true));
+
+        IpcMessage::Uniforms {
+            timestamp: 2.0,
+            values,
+        }
+    }
+
+    #[test]
+    fn header_roundtrip() {
+        let header = IpcHeader {
+            format: IpcFormat::Gray8,
+            width: 4,
+            height: 2,
+            timestamp: 12.25,
+            payload_size: 8,
+        };
+
+        assert_eq!(IpcHeader::decode(&header.encode()).unwrap(), header);
+    }
+
+    #[test]
+    fn header_rejects_invalid_data() {
+        let mut header = IpcHeader {
+            format: IpcFormat::Rgb8,
+            width: 2,
+            height: 2,
+            timestamp: 0.0,
+            payload_size: 12,
+        }
+        .encode();
+
+        assert!(IpcHeader::decode(&header[..IPC_HEADER_SIZE - 1]).is_err());
+
+        header[24..28].copy_from_slice(&11u32.to_le_bytes());
+        assert!(IpcHeader::decode(&header).is_err());
+
+        header[0] = b'X';
+        assert!(IpcHeader::decode(&header).is_err());
+    }
+
+    #[test]
+    fn message_roundtrip() {
+        for message in [get_frame(), get_uniforms()] {
+            let data = message.encode().unwrap();
+            let header = IpcHeader::decode(&data).unwrap();
+
+            assert_eq!(
+                IpcMessage::decode(&header, &data[IPC_HEADER_SIZE..]).unwrap(),
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn frame_encoding_checks_dimensions() {
+        let message = IpcMessage::Frame {
+            timestamp: 0.0,
+            width: 3,
+            height: 1,
+            format: IpcFormat::Rgb8,
+            data: vec![0; 4],
+        };
+
+        assert!(message.encode().is_err());
+    }
+
+    #[test]
+    fn reader_splits_chunked_stream() {
+        let messages = [get_frame(), get_uniforms(), get_frame()];
+        let data: Vec<u8> = messages
+            .iter()
+            .flat_map(|message| message.encode().unwrap())
+            .collect();
+
+        let mut reader = IpcReader::new();
+        let mut decoded = Vec::new();
+        for chunk in data.chunks(5) {
+            reader.push(chunk);
+            while let Some(message) = reader.next_message().unwrap() {
+                decoded.push(message);
+            }
+        }
+
+        assert_eq!(decoded, messages);
+        assert_eq!(reader.get_pending_size(), 0);
+    }
+
+    #[test]
+    fn reader_compacts_consumed_bytes() {
+        let data = get_frame().encode().unwrap();
+        let mut reader = IpcReader::new();
+
+        for _ in 0..8 {
+            reader.push(&data);
+            reader.push(&data[..IPC_HEADER_SIZE]);
+            assert_eq!(reader.next_message().unwrap(), Some(get_frame()));
+            assert_eq!(reader.next_message().unwrap(), None);
+            reader.push(&data[IPC_HEADER_SIZE..]);
+            assert_eq!(reader.next_message().unwrap(), Some(get_frame()));
+        }
+
+        assert!(reader.buffer.len() <= data.len() * 3);
+    }
+
+    #[test]
+    fn reader_rejects_corrupted_stream() {
+        let mut data = get_uniforms().encode().unwrap();
+        data[1] = 0;
+
+        let mut reader = IpcReader::new();
+        reader.push(&data);
+        assert!(reader.next_message().is_err());
+    }
+}
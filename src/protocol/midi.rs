@@ -0,0 +1,257 @@
+pub const PITCH_BEND_CENTER: u16 = 8192;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    PolyPressure {
+        channel: u8,
+        note: u8,
+        pressure: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    ChannelPressure {
+        channel: u8,
+        pressure: u8,
+    },
+    PitchBend {
+        channel: u8,
+        value: u16,
+    },
+}
+
+impl MidiMessage {
+    pub fn get_channel(&self) -> u8 {
+        match *self {
+            Self::NoteOff { channel, .. }
+            | Self::NoteOn { channel, .. }
+            | Self::PolyPressure { channel, .. }
+            | Self::ControlChange { channel, .. }
+            | Self::ProgramChange { channel, .. }
+            | Self::ChannelPressure { channel, .. }
+            | Self::PitchBend { channel, .. } => channel,
+        }
+    }
+}
+
+fn get_data_length(status: u8) -> usize {
+    match status & 0xF0 {
+        0xC0 | 0xD0 => 1,
+        _ => 2,
+    }
+}
+
+fn build_message(status: u8, data: [u8; 2]) -> MidiMessage {
+    let channel = status & 0x0F;
+    match status & 0xF0 {
+        // Note on with a zero velocity is the usual running status shorthand for note off
+        0x90 if data[1] == 0 => MidiMessage::NoteOff {
+            channel,
+            note: data[0],
+            velocity: 0,
+        },
+        0x90 => MidiMessage::NoteOn {
+            channel,
+            note: data[0],
+            velocity: data[1],
+        },
+        0x80 => MidiMessage::NoteOff {
+            channel,
+            note: data[0],
+            velocity: data[1],
+        },
+        0xA0 => MidiMessage::PolyPressure {
+            channel,
+            note: data[0],
+            pressure: data[1],
+        },
+        0xB0 => MidiMessage::ControlChange {
+            channel,
+            controller: data[0],
+            value: data[1],
+        },
+        0xC0 => MidiMessage::ProgramChange {
+            channel,
+            program: data[0],
+        },
+        0xD0 => MidiMessage::ChannelPressure {
+            channel,
+            pressure: data[0],
+        },
+        _ => MidiMessage::PitchBend {
+            channel,
+            value: (data[1] as u16) << 7 | data[0] as u16,
+        },
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MidiParser {
+    status: Option<u8>,
+    data: [u8; 2],
+    data_length: usize,
+    skipped_length: usize,
+    in_sysex: bool,
+}
+
+impl MidiParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        match byte {
+            // Real-time messages can be interleaved anywhere and leave the running status alone
+            0xF8..=0xFF => None,
+            0xF0 => {
+                self.set_status(None, 0);
+                self.in_sysex = true;
+                None
+            }
+            0xF7 => {
+                self.in_sysex = false;
+                None
+            }
+            0xF1 | 0xF3 => {
+                self.set_status(None, 1);
+                None
+            }
+            0xF2 => {
+                self.set_status(None, 2);
+                None
+            }
+            0xF4..=0xF6 => {
+                self.set_status(None, 0);
+                None
+            }
+            0x80..=0xEF => {
+                self.set_status(Some(byte), 0);
+                None
+            }
+            _ => {
+                if self.in_sysex {
+                    return None;
+                }
+
+                if self.skipped_length > 0 {
+                    self.skipped_length -= 1;
+                    return None;
+                }
+
+                let status = self.status?;
+                self.data[self.data_length] = byte;
+                self.data_length += 1;
+                if self.data_length < get_data_length(status) {
+                    return None;
+                }
+
+                self.data_length = 0;
+                Some(build_message(status, self.data))
+            }
+        }
+    }
+
+    pub fn parse(&mut self, bytes: &[u8]) -> Vec<MidiMessage> {
+        bytes.iter().filter_map(|byte| self.push(*byte)).collect()
+    }
+
+    fn set_status(&mut self, status: Option<u8>, skipped_length: usize) {
+        self.status = status;
+        self.data_length = 0;
+        self.skipped_length = skipped_length;
+        self.in_sysex = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_status_reuses_the_last_status_byte() {
+        let mut parser = MidiParser::new();
+        assert_eq!(
+            parser.parse(&[0x91, 60, 100, 62, 0, 0xB1, 7, 127, 7, 0]),
+            [
+                MidiMessage::NoteOn {
+                    channel: 1,
+                    note: 60,
+                    velocity: 100
+                },
+                MidiMessage::NoteOff {
+                    channel: 1,
+                    note: 62,
+                    velocity: 0
+                },
+                MidiMessage::ControlChange {
+                    channel: 1,
+                    controller: 7,
+                    value: 127
+                },
+                MidiMessage::ControlChange {
+                    channel: 1,
+                    controller: 7,
+                    value: 0
+                },
+            ]
+        );
+
+        // Messages split across reads keep their state
+        assert_eq!(parser.parse(&[0xE0, 0x00]), []);
+        assert_eq!(
+            parser.parse(&[0xF8, 0x40, 0xC3, 5]),
+            [
+                MidiMessage::PitchBend {
+                    channel: 0,
+                    value: PITCH_BEND_CENTER
+                },
+                MidiMessage::ProgramChange {
+                    channel: 3,
+                    program: 5
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn system_messages_are_skipped() {
+        let mut parser = MidiParser::new();
+        assert_eq!(
+            parser.parse(&[0x90, 0xF0, 0x7E, 0x01, 0x60, 0xF7, 60, 100]),
+            []
+        );
+        assert_eq!(parser.parse(&[0xF2, 0x10, 0x20, 0x40]), []);
+        assert_eq!(parser.parse(&[0xF3, 0x01, 0x05]), []);
+        assert_eq!(
+            parser.parse(&[0xD2, 0xF1, 0x10, 0x30, 0xD2, 0x30]),
+            [MidiMessage::ChannelPressure {
+                channel: 2,
+                pressure: 0x30
+            }]
+        );
+
+        // Data without any status byte is ignored
+        parser.reset();
+        assert_eq!(parser.parse(&[0x10, 0x20]), []);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod ipc;
+pub mod midi;
+pub mod osc;
+
+pub use ipc::*;
+pub use midi::*;
+pub use osc::*;
@@ -0,0 +1,472 @@
+use std::convert::TryInto;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+
+const BUNDLE_TAG: &[u8] = b"#bundle\0";
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+const MAX_ARRAY_DEPTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OscTimeTag {
+    pub seconds: u32,
+    pub fraction: u32,
+}
+
+impl OscTimeTag {
+    pub const IMMEDIATE: Self = Self {
+        seconds: 0,
+        fraction: 1,
+    };
+
+    pub fn now() -> Self {
+        Self::from_system_time(SystemTime::now())
+    }
+
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let fraction = (since_epoch.subsec_nanos() as u64) << 32;
+
+        Self {
+            seconds: (since_epoch.as_secs() + NTP_UNIX_OFFSET) as u32,
+            fraction: (fraction / 1_000_000_000) as u32,
+        }
+    }
+
+    pub fn to_system_time(self) -> SystemTime {
+        let seconds = (self.seconds as u64).saturating_sub(NTP_UNIX_OFFSET);
+        let nanos = ((self.fraction as u64 * 1_000_000_000) >> 32) as u32;
+
+        UNIX_EPOCH + Duration::new(seconds, nanos)
+    }
+
+    pub fn is_immediate(&self) -> bool {
+        *self == Self::IMMEDIATE
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.seconds.to_be_bytes());
+        buffer.extend_from_slice(&self.fraction.to_be_bytes());
+    }
+
+    fn decode(data: &[u8]) -> Self {
+        Self {
+            seconds: u32::from_be_bytes(data[0..4].try_into().unwrap()),
+            fraction: u32::from_be_bytes(data[4..8].try_into().unwrap()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum OscArgument {
+    Int(i32),
+    Float(f32),
+    String(String),
+    Blob(Vec<u8>),
+    TimeTag(OscTimeTag),
+    Long(i64),
+    Double(f64),
+    Symbol(String),
+    Char(char),
+    Color([u8; 4]),
+    Midi([u8; 4]),
+    Bool(bool),
+    Nil,
+    Impulse,
+    Array(Vec<OscArgument>),
+}
+
+impl OscArgument {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Int(value) => Some(*value as f64),
+            Self::Float(value) => Some(*value as f64),
+            Self::Long(value) => Some(*value as f64),
+            Self::Double(value) => Some(*value),
+            Self::Bool(value) => Some(*value as u8 as f64),
+            Self::Impulse => Some(1.0),
+            Self::String(value) | Self::Symbol(value) => value.parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn get_type_tag(&self, tags: &mut String) {
+        match self {
+            Self::Int(_) => tags.push('i'),
+            Self::Float(_) => tags.push('f'),
+            Self::String(_) => tags.push('s'),
+            Self::Blob(_) => tags.push('b'),
+            Self::TimeTag(_) => tags.push('t'),
+            Self::Long(_) => tags.push('h'),
+            Self::Double(_) => tags.push('d'),
+            Self::Symbol(_) => tags.push('S'),
+            Self::Char(_) => tags.push('c'),
+            Self::Color(_) => tags.push('r'),
+            Self::Midi(_) => tags.push('m'),
+            Self::Bool(true) => tags.push('T'),
+            Self::Bool(false) => tags.push('F'),
+            Self::Nil => tags.push('N'),
+            Self::Impulse => tags.push('I'),
+            Self::Array(arguments) => {
+                tags.push('[');
+                for argument in arguments {
+                    argument.get_type_tag(tags);
+                }
+                tags.push(']');
+            }
+        }
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        match self {
+            Self::Int(value) => buffer.extend_from_slice(&value.to_be_bytes()),
+            Self::Float(value) => buffer.extend_from_slice(&value.to_be_bytes()),
+            Self::String(value) | Self::Symbol(value) => write_string(buffer, value),
+            Self::Blob(value) => {
+                buffer.extend_from_slice(&(value.len() as i32).to_be_bytes());
+                buffer.extend_from_slice(value);
+                pad(buffer);
+            }
+            Self::TimeTag(value) => value.encode(buffer),
+            Self::Long(value) => buffer.extend_from_slice(&value.to_be_bytes()),
+            Self::Double(value) => buffer.extend_from_slice(&value.to_be_bytes()),
+            Self::Char(value) => buffer.extend_from_slice(&(*value as u32).to_be_bytes()),
+            Self::Color(value) | Self::Midi(value) => buffer.extend_from_slice(value),
+            Self::Bool(_) | Self::Nil | Self::Impulse => (),
+            Self::Array(arguments) => {
+                for argument in arguments {
+                    argument.encode(buffer);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub arguments: Vec<OscArgument>,
+}
+
+impl OscMessage {
+    pub fn new(address: &str, arguments: Vec<OscArgument>) -> Self {
+        Self {
+            address: address.to_string(),
+            arguments,
+        }
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        write_string(buffer, &self.address);
+
+        let mut tags = String::from(",");
+        for argument in &self.arguments {
+            argument.get_type_tag(&mut tags);
+        }
+        write_string(buffer, &tags);
+
+        for argument in &self.arguments {
+            argument.encode(buffer);
+        }
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader { data, offset: 0 };
+        let address = reader.read_string().context("Invalid OSC address")?;
+        if !address.starts_with('/') {
+            bail!("Invalid OSC address: {}", address);
+        }
+
+        // OSC 1.0 allows omitting the type tag string, such messages carry no arguments we can read
+        if reader.is_empty() {
+            return Ok(Self::new(&address, Vec::new()));
+        }
+
+        let tags = reader.read_string().context("Invalid OSC type tags")?;
+        let tags = match tags.strip_prefix(',') {
+            Some(tags) => tags,
+            None => bail!("Invalid OSC type tags: {}", tags),
+        };
+
+        let mut tags = tags.chars();
+        let arguments = reader.read_arguments(&mut tags, 0)?;
+
+        Ok(Self::new(&address, arguments))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct OscBundle {
+    pub timetag: OscTimeTag,
+    pub content: Vec<OscPacket>,
+}
+
+impl OscBundle {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(BUNDLE_TAG);
+        self.timetag.encode(buffer);
+
+        for packet in &self.content {
+            let element = packet.encode();
+            buffer.extend_from_slice(&(element.len() as i32).to_be_bytes());
+            buffer.extend_from_slice(&element);
+        }
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < BUNDLE_TAG.len() + 8 {
+            bail!("Truncated OSC bundle");
+        }
+
+        let timetag = OscTimeTag::decode(&data[BUNDLE_TAG.len()..]);
+        let mut reader = Reader {
+            data,
+            offset: BUNDLE_TAG.len() + 8,
+        };
+
+        let mut content = Vec::new();
+        while !reader.is_empty() {
+            let size = reader.read_i32()?;
+            if size < 0 || size % 4 != 0 {
+                bail!("Invalid OSC bundle element size: {}", size);
+            }
+
+            content.push(OscPacket::decode(reader.read_bytes(size as usize)?)?);
+        }
+
+        Ok(Self { timetag, content })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum OscPacket {
+    Message(OscMessage),
+    Bundle(OscBundle),
+}
+
+impl OscPacket {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        match self {
+            Self::Message(message) => message.encode(&mut buffer),
+            Self::Bundle(bundle) => bundle.encode(&mut buffer),
+        }
+
+        buffer
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() % 4 != 0 {
+            bail!("OSC packet size is not a multiple of 4: {}", data.len());
+        }
+
+        if data.starts_with(BUNDLE_TAG) {
+            Ok(Self::Bundle(OscBundle::decode(data)?))
+        } else {
+            Ok(Self::Message(OscMessage::decode(data)?))
+        }
+    }
+
+    // Flattens nested bundles, inner bundles inherit the timetag of their parent when it is later
+    pub fn into_messages(self) -> Vec<(OscTimeTag, OscMessage)> {
+        let mut messages = Vec::new();
+        self.collect_messages(OscTimeTag::IMMEDIATE, &mut messages);
+
+        messages
+    }
+
+    fn collect_messages(self, timetag: OscTimeTag, messages: &mut Vec<(OscTimeTag, OscMessage)>) {
+        match self {
+            Self::Message(message) => messages.push((timetag, message)),
+            Self::Bundle(bundle) => {
+                let timetag = if bundle.timetag.is_immediate() {
+                    timetag
+                } else {
+                    bundle.timetag.max(timetag)
+                };
+                for packet in bundle.content {
+                    packet.collect_messages(timetag, messages);
+                }
+            }
+        }
+    }
+}
+
+fn pad(buffer: &mut Vec<u8>) {
+    while buffer.len() % 4 != 0 {
+        buffer.push(0);
+    }
+}
+
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(value.as_bytes());
+    buffer.push(0);
+    pad(buffer);
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.offset >= self.data.len()
+    }
+
+    fn read_bytes(&mut self, size: usize) -> Result<&'a [u8]> {
+        if self.offset + size > self.data.len() {
+            bail!("Truncated OSC packet");
+        }
+
+        let bytes = &self.data[self.offset..self.offset + size];
+        self.offset += size;
+        Ok(bytes)
+    }
+
+    fn read_i32(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.read_bytes(4)?.try_into()?))
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        let remaining = &self.data[self.offset.min(self.data.len())..];
+        let length = match remaining.iter().position(|byte| *byte == 0) {
+            Some(length) => length,
+            None => bail!("Unterminated OSC string"),
+        };
+
+        let value = String::from_utf8(remaining[..length].to_vec())?;
+        self.read_bytes((length + 4) & !3)?;
+
+        Ok(value)
+    }
+
+    fn read_arguments(
+        &mut self,
+        tags: &mut std::str::Chars,
+        depth: usize,
+    ) -> Result<Vec<OscArgument>> {
+        let mut arguments = Vec::new();
+
+        while let Some(tag) = tags.next() {
+            let argument = match tag {
+                'i' => OscArgument::Int(self.read_i32()?),
+                'f' => OscArgument::Float(f32::from_be_bytes(self.read_bytes(4)?.try_into()?)),
+                's' => OscArgument::String(self.read_string()?),
+                'S' => OscArgument::Symbol(self.read_string()?),
+                'b' => {
+                    let size = self.read_i32()?;
+                    if size < 0 {
+                        bail!("Invalid OSC blob size: {}", size);
+                    }
+                    let blob = self.read_bytes(size as usize)?.to_vec();
+                    self.read_bytes((4 - size as usize % 4) % 4)?;
+                    OscArgument::Blob(blob)
+                }
+                't' => OscArgument::TimeTag(OscTimeTag::decode(self.read_bytes(8)?)),
+                'h' => OscArgument::Long(i64::from_be_bytes(self.read_bytes(8)?.try_into()?)),
+                'd' => OscArgument::Double(f64::from_be_bytes(self.read_bytes(8)?.try_into()?)),
+                'c' => {
+                    let code = u32::from_be_bytes(self.read_bytes(4)?.try_into()?);
+                    OscArgument::Char(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+                }
+                'r' => OscArgument::Color(self.read_bytes(4)?.try_into()?),
+                'm' => OscArgument::Midi(self.read_bytes(4)?.try_into()?),
+                'T' => OscArgument::Bool(true),
+                'F' => OscArgument::Bool(false),
+                'N' => OscArgument::Nil,
+                'I' => OscArgument::Impulse,
+                // Nesting is bounded so that a crafted type tag string cannot exhaust the stack
+                '[' if depth >= MAX_ARRAY_DEPTH => {
+                    bail!("OSC arrays nested deeper than {} levels", MAX_ARRAY_DEPTH)
+                }
+                '[' => OscArgument::Array(self.read_arguments(tags, depth + 1)?),
+                ']' if depth > 0 => return Ok(arguments),
+                _ => bail!("Unsupported OSC type tag: {}", tag),
+            };
+
+            arguments.push(argument);
+        }
+
+        if depth > 0 {
+            bail!("Unterminated OSC array");
+        }
+
+        Ok(arguments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_raw_message(address: &str, tags: &str, payload: &[u8]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        write_string(&mut buffer, address);
+        write_string(&mut buffer, tags);
+        buffer.extend_from_slice(payload);
+
+        buffer
+    }
+
+    #[test]
+    fn message_roundtrip() {
+        let packet = OscPacket::Message(OscMessage::new(
+            "/layer/1/color",
+            vec![
+                OscArgument::Float(0.5),
+                OscArgument::Array(vec![OscArgument::Int(3), OscArgument::Bool(true)]),
+                OscArgument::String("hello".to_string()),
+                OscArgument::Blob(vec![1, 2, 3]),
+                OscArgument::Nil,
+            ],
+        ));
+
+        assert_eq!(OscPacket::decode(&packet.encode()).unwrap(), packet);
+    }
+
+    #[test]
+    fn nested_bundles_flatten_with_later_timetag() {
+        let later = OscTimeTag {
+            seconds: 10,
+            fraction: 0,
+        };
+        let packet = OscPacket::Bundle(OscBundle {
+            timetag: later,
+            content: vec![
+                OscPacket::Message(OscMessage::new("/a", Vec::new())),
+                OscPacket::Bundle(OscBundle {
+                    timetag: OscTimeTag::IMMEDIATE,
+                    content: vec![OscPacket::Message(OscMessage::new("/b", Vec::new()))],
+                }),
+            ],
+        });
+
+        let messages = OscPacket::decode(&packet.encode()).unwrap().into_messages();
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|(timetag, _)| *timetag == later));
+        assert_eq!(messages[1].1.address, "/b");
+    }
+
+    #[test]
+    fn array_nesting_is_bounded() {
+        let nested = format!(",{}{}", "[".repeat(16), "]".repeat(16));
+        assert!(OscPacket::decode(&encode_raw_message("/ok", &nested, &[])).is_ok());
+
+        let too_deep = format!(",{}{}", "[".repeat(17), "]".repeat(17));
+        assert!(OscPacket::decode(&encode_raw_message("/deep", &too_deep, &[])).is_err());
+
+        // Deep enough to overflow the stack if the recursion was unbounded
+        let malicious = format!(",{}", "[".repeat(60_000));
+        assert!(OscPacket::decode(&encode_raw_message("/evil", &malicious, &[])).is_err());
+    }
+
+    #[test]
+    fn malformed_packets_are_rejected() {
+        assert!(OscPacket::decode(b"/abc").is_err());
+        assert!(OscPacket::decode(&encode_raw_message("no_slash", ",", &[])).is_err());
+        assert!(OscPacket::decode(&encode_raw_message("/int", ",i", &[])).is_err());
+        assert!(OscPacket::decode(&encode_raw_message("/array", ",[i", &[0; 4])).is_err());
+        assert!(OscPacket::decode(&encode_raw_message("/tag", ",x", &[])).is_err());
+    }
+}
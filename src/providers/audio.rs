@@ -0,0 +1,1057 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::types::{
+    DataHolder, DataRange, DataType, InputError, InputProvider, InputResult, PropertyDescription,
+    UniformDescription, UniformStore, UpdateFrequency,
+};
+
+const MIN_BAND_FREQUENCY: f32 = 20.0;
+
+pub fn fft(real: &mut [f32], imaginary: &mut [f32]) {
+    let size = real.len();
+    assert!(size.is_power_of_two() && imaginary.len() == size);
+
+    let mut j = 0;
+    for i in 1..size {
+        let mut bit = size >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+
+        if i < j {
+            real.swap(i, j);
+            imaginary.swap(i, j);
+        }
+    }
+
+    let mut length = 2;
+    while length <= size {
+        let angle = -2.0 * std::f32::consts::PI / length as f32;
+        let (step_imaginary, step_real) = angle.sin_cos();
+
+        for start in (0..size).step_by(length) {
+            let (mut twiddle_real, mut twiddle_imaginary) = (1.0f32, 0.0f32);
+
+            for offset in 0..length / 2 {
+                let even = start + offset;
+                let odd = even + length / 2;
+
+                let odd_real = real[odd] * twiddle_real - imaginary[odd] * twiddle_imaginary;
+                let odd_imaginary = real[odd] * twiddle_imaginary + imaginary[odd] * twiddle_real;
+
+                real[odd] = real[even] - odd_real;
+                imaginary[odd] = imaginary[even] - odd_imaginary;
+                real[even] += odd_real;
+                imaginary[even] += odd_imaginary;
+
+                let next_twiddle_real =
+                    twiddle_real * step_real - twiddle_imaginary * step_imaginary;
+                twiddle_imaginary = twiddle_real * step_imaginary + twiddle_imaginary * step_real;
+                twiddle_real = next_twiddle_real;
+            }
+        }
+
+        length <<= 1;
+    }
+}
+
+pub struct AudioAnalyzer {
+    sample_rate: u32,
+    fft_size: usize,
+    band_count: usize,
+    attack: f32,
+    release: f32,
+    gain: f32,
+    onset_threshold: f32,
+
+    samples: VecDeque<f32>,
+    hann_window: Vec<f32>,
+
+    spectrum: Vec<f32>,
+    bands: Vec<f32>,
+    rms: f32,
+    peak: f32,
+    onset: bool,
+
+    flux_average: f32,
+}
+
+impl AudioAnalyzer {
+    pub fn new(sample_rate: u32, fft_size: usize, band_count: usize) -> Self {
+        let fft_size = fft_size.max(2).next_power_of_two();
+        let band_count = band_count.max(1);
+        let hann_window = (0..fft_size)
+            .map(|index| {
+                0.5 - 0.5
+                    * (2.0 * std::f32::consts::PI * index as f32 / (fft_size - 1) as f32).cos()
+            })
+            .collect();
+
+        Self {
+            sample_rate,
+            fft_size,
+            band_count,
+            attack: 0.8,
+            release: 0.2,
+            gain: 1.0,
+            onset_threshold: 1.5,
+
+            samples: VecDeque::from(vec![0.0; fft_size]),
+            hann_window,
+
+            spectrum: vec![0.0; fft_size / 2],
+            bands: vec![0.0; band_count],
+            rms: 0.0,
+            peak: 0.0,
+            onset: false,
+
+            flux_average: 0.0,
+        }
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn get_fft_size(&self) -> usize {
+        self.fft_size
+    }
+
+    pub fn get_attack(&self) -> f32 {
+        self.attack
+    }
+
+    pub fn get_release(&self) -> f32 {
+        self.release
+    }
+
+    pub fn get_gain(&self) -> f32 {
+        self.gain
+    }
+
+    pub fn get_onset_threshold(&self) -> f32 {
+        self.onset_threshold
+    }
+
+    pub fn get_band_count(&self) -> usize {
+        self.band_count
+    }
+
+    pub fn set_attack(&mut self, attack: f32) {
+        self.attack = attack.clamp(0.0, 1.0);
+    }
+
+    pub fn set_release(&mut self, release: f32) {
+        self.release = release.clamp(0.0, 1.0);
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    pub fn set_onset_threshold(&mut self, onset_threshold: f32) {
+        self.onset_threshold = onset_threshold;
+    }
+
+    pub fn set_band_count(&mut self, band_count: usize) -> Result<()> {
+        // Band edges are spaced by dividing by the band count
+        if band_count == 0 {
+            bail!("Audio analysis needs at least one band");
+        }
+
+        self.band_count = band_count;
+        self.bands = vec![0.0; band_count];
+
+        Ok(())
+    }
+
+    pub fn push_samples(&mut self, samples: &[f32], channel_count: usize) {
+        let channel_count = channel_count.max(1);
+        for frame in samples.chunks(channel_count) {
+            let sample = frame.iter().sum::<f32>() / frame.len() as f32;
+            self.samples.push_back(sample * self.gain);
+        }
+
+        while self.samples.len() > self.fft_size {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn clear_window(&mut self) {
+        self.samples = VecDeque::from(vec![0.0; self.fft_size]);
+    }
+
+    pub fn get_window(&self) -> impl Iterator<Item = &f32> {
+        self.samples.iter()
+    }
+
+    pub fn get_spectrum(&self) -> &[f32] {
+        &self.spectrum
+    }
+
+    pub fn get_bands(&self) -> &[f32] {
+        &self.bands
+    }
+
+    pub fn get_rms(&self) -> f32 {
+        self.rms
+    }
+
+    pub fn get_peak(&self) -> f32 {
+        self.peak
+    }
+
+    pub fn is_onset(&self) -> bool {
+        self.onset
+    }
+
+    pub fn update(&mut self) {
+        let mut real: Vec<f32> = self
+            .samples
+            .iter()
+            .zip(self.hann_window.iter())
+            .map(|(sample, window)| sample * window)
+            .collect();
+        let mut imaginary = vec![0.0; self.fft_size];
+        fft(&mut real, &mut imaginary);
+
+        let normalization = 2.0 / self.fft_size as f32;
+        let mut flux = 0.0;
+        for (bin, magnitude) in self.spectrum.iter_mut().enumerate() {
+            let target =
+                (real[bin] * real[bin] + imaginary[bin] * imaginary[bin]).sqrt() * normalization;
+            flux += (target - *magnitude).max(0.0);
+            *magnitude = smooth(*magnitude, target, self.attack, self.release);
+        }
+
+        let band_edges = self.get_band_edges();
+        for (band, edges) in self.bands.iter_mut().zip(band_edges.windows(2)) {
+            let (start, end) = (edges[0], edges[1].max(edges[0] + 1));
+            let energy = self.spectrum[start..end].iter().sum::<f32>() / (end - start) as f32;
+            *band = energy;
+        }
+
+        let sample_count = self.samples.len().max(1) as f32;
+        let rms = (self
+            .samples
+            .iter()
+            .map(|sample| sample * sample)
+            .sum::<f32>()
+            / sample_count)
+            .sqrt();
+        let peak = self
+            .samples
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        self.rms = smooth(self.rms, rms, self.attack, self.release);
+        self.peak = smooth(self.peak, peak, self.attack, self.release);
+
+        self.onset = flux > 1e-4 && flux > self.flux_average * self.onset_threshold;
+        self.flux_average = self.flux_average * 0.9 + flux * 0.1;
+    }
+
+    fn get_band_edges(&self) -> Vec<usize> {
+        let bin_count = self.spectrum.len();
+        let nyquist = self.sample_rate as f32 / 2.0;
+        let min_frequency = MIN_BAND_FREQUENCY.min(nyquist);
+        let ratio = (nyquist / min_frequency).max(1.0);
+
+        (0..=self.band_count)
+            .map(|index| {
+                let frequency = min_frequency * ratio.powf(index as f32 / self.band_count as f32);
+                ((frequency / nyquist * bin_count as f32) as usize).min(bin_count - 1)
+            })
+            .collect()
+    }
+}
+
+fn smooth(current: f32, target: f32, attack: f32, release: f32) -> f32 {
+    let factor = if target > current { attack } else { release };
+    current + (target - current) * factor
+}
+
+pub fn values_to_texture(values: &[f32]) -> DataHolder {
+    let data = values
+        .iter()
+        .flat_map(|value| {
+            let value = (value.clamp(0.0, 1.0) * 255.0) as u8;
+            [value; 3]
+        })
+        .collect();
+
+    DataHolder::Texture(((values.len() as u32, 1), data))
+}
+
+// Interleaved samples, channel count and sample rate
+pub type DecodedAudio = (Vec<f32>, usize, u32);
+
+fn decode_wav(path: &Path) -> Result<DecodedAudio> {
+    let reader = hound::WavReader::open(path)
+        .with_context(|| format!("Failed to open wav file: {:?}", path))?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .into_samples::<f32>()
+            .collect::<std::result::Result<Vec<f32>, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 * scale))
+                .collect::<std::result::Result<Vec<f32>, _>>()
+        }
+    }
+    .with_context(|| format!("Failed to decode wav file: {:?}", path))?;
+
+    Ok((samples, spec.channels as usize, spec.sample_rate))
+}
+
+fn decode_flac(path: &Path) -> Result<DecodedAudio> {
+    let mut reader = claxon::FlacReader::open(path)
+        .with_context(|| format!("Failed to open flac file: {:?}", path))?;
+    let info = reader.streaminfo();
+
+    let scale = 1.0 / (1u64 << (info.bits_per_sample - 1)) as f32;
+    let samples = reader
+        .samples()
+        .map(|sample| sample.map(|sample| sample as f32 * scale))
+        .collect::<std::result::Result<Vec<f32>, _>>()
+        .with_context(|| format!("Failed to decode flac file: {:?}", path))?;
+
+    Ok((samples, info.channels as usize, info.sample_rate))
+}
+
+fn decode_ogg(path: &Path) -> Result<DecodedAudio> {
+    let file = File::open(path).with_context(|| format!("Failed to open ogg file: {:?}", path))?;
+    let mut reader = lewton::inside_ogg::OggStreamReader::new(file)
+        .with_context(|| format!("Failed to read ogg vorbis headers: {:?}", path))?;
+
+    let mut samples = Vec::new();
+    while let Some(packet) = reader
+        .read_dec_packet_itl()
+        .with_context(|| format!("Failed to decode ogg file: {:?}", path))?
+    {
+        samples.extend(packet.iter().map(|sample| *sample as f32 / 32768.0));
+    }
+
+    Ok((
+        samples,
+        reader.ident_hdr.audio_channels as usize,
+        reader.ident_hdr.audio_sample_rate,
+    ))
+}
+
+pub fn decode_audio_file(path: &Path) -> Result<DecodedAudio> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "wav" | "wave" => decode_wav(path),
+        "flac" => decode_flac(path),
+        "ogg" | "oga" => decode_ogg(path),
+        _ => bail!("Unsupported audio file format: {:?}", path),
+    }
+}
+
+pub struct AudioAnalysisProvider {
+    name: String,
+    analyzer: AudioAnalyzer,
+
+    source: Option<(Vec<f32>, usize)>,
+    beat_sync: Option<f64>,
+    cursor: usize,
+    time_offset: f64,
+    playing: bool,
+    looping: bool,
+    uniforms: UniformStore,
+}
+
+impl AudioAnalysisProvider {
+    pub fn new(sample_rate: u32, fft_size: usize, band_count: usize) -> Self {
+        Self {
+            name: String::new(),
+            analyzer: AudioAnalyzer::new(sample_rate, fft_size, band_count),
+
+            source: None,
+            beat_sync: None,
+            cursor: 0,
+            time_offset: 0.0,
+            playing: true,
+            looping: false,
+            uniforms: UniformStore::default(),
+        }
+    }
+
+    pub fn from_wav(path: &Path, fft_size: usize, band_count: usize) -> Result<Self> {
+        Self::from_decoded(decode_wav(path)?, fft_size, band_count)
+    }
+
+    pub fn from_file(path: &Path, fft_size: usize, band_count: usize) -> Result<Self> {
+        Self::from_decoded(decode_audio_file(path)?, fft_size, band_count)
+    }
+
+    fn from_decoded(
+        (samples, channel_count, sample_rate): DecodedAudio,
+        fft_size: usize,
+        band_count: usize,
+    ) -> Result<Self> {
+        if channel_count == 0 || sample_rate == 0 {
+            bail!("Audio file has no channels or no sample rate");
+        }
+
+        let mut provider = Self::new(sample_rate, fft_size, band_count);
+        provider.source = Some((samples, channel_count));
+
+        Ok(provider)
+    }
+
+    pub fn get_beat_sync(&self) -> Option<f64> {
+        self.beat_sync
+    }
+
+    // When set, the file is positioned from the beat at the given tempo instead of the time
+    pub fn set_beat_sync(&mut self, bpm: Option<f64>) {
+        self.beat_sync = bpm.filter(|bpm| *bpm > 0.0);
+    }
+
+    pub fn get_analyzer(&self) -> &AudioAnalyzer {
+        &self.analyzer
+    }
+
+    pub fn push_samples(&mut self, samples: &[f32], channel_count: usize) {
+        self.analyzer.push_samples(samples, channel_count);
+        self.analyzer.update();
+
+        let waveform: Vec<f32> = self.analyzer.get_window().copied().collect();
+        let waveform_texture: Vec<f32> = waveform.iter().map(|sample| sample * 0.5 + 0.5).collect();
+        self.uniforms
+            .set("waveform", DataHolder::FloatArray(waveform));
+        self.uniforms
+            .set("waveform_texture", values_to_texture(&waveform_texture));
+
+        let spectrum = self.analyzer.get_spectrum();
+        self.uniforms
+            .set("spectrum", DataHolder::FloatArray(spectrum.to_vec()));
+        self.uniforms
+            .set("spectrum_texture", values_to_texture(spectrum));
+        self.uniforms.set(
+            "bands",
+            DataHolder::FloatArray(self.analyzer.get_bands().to_vec()),
+        );
+        self.uniforms
+            .set_if_changed("rms", DataHolder::Float(self.analyzer.get_rms()));
+        self.uniforms
+            .set_if_changed("peak", DataHolder::Float(self.analyzer.get_peak()));
+        self.uniforms
+            .set_if_changed("onset", DataHolder::Bool(self.analyzer.is_onset()));
+    }
+
+    fn get_local_name<'a>(&self, uniform_name: &'a str) -> InputResult<&'a str> {
+        match uniform_name
+            .strip_prefix(&self.name)
+            .and_then(|local_name| local_name.strip_prefix('_'))
+        {
+            Some(
+                local_name @ ("waveform" | "waveform_texture" | "spectrum" | "spectrum_texture"
+                | "bands" | "rms" | "peak" | "onset"),
+            ) => Ok(local_name),
+            _ => Err(InputError::UnknownUniform(uniform_name.to_string())),
+        }
+    }
+
+    fn advance(&mut self, time: f64) {
+        if self.playing {
+            self.seek(time + self.time_offset);
+        } else if let Some(position) = self.get_position() {
+            self.time_offset = position - time;
+        }
+    }
+
+    fn seek(&mut self, time: f64) {
+        let duration = match self.get_duration() {
+            Some(duration) => duration,
+            None => return,
+        };
+        let time = if self.looping && duration > 0.0 {
+            time.rem_euclid(duration)
+        } else {
+            time
+        };
+
+        let (samples, channel_count) = match self.source.take() {
+            Some(source) => source,
+            None => return,
+        };
+
+        let sample_rate = self.analyzer.get_sample_rate() as f64;
+        let frame_count = samples.len() / channel_count;
+        let target = ((time * sample_rate).max(0.0) as usize).min(frame_count);
+
+        // Only short forward moves continue the current window, any other seek refills it from
+        // silence so that no samples from the previous position are analyzed
+        let fft_size = self.analyzer.get_fft_size();
+        let discontinuous = target < self.cursor || target - self.cursor > fft_size;
+        let start = if discontinuous {
+            self.analyzer.clear_window();
+            target.saturating_sub(fft_size)
+        } else {
+            self.cursor
+        };
+
+        if discontinuous || start != target {
+            self.push_samples(
+                &samples[start * channel_count..target * channel_count],
+                channel_count,
+            );
+        }
+
+        self.cursor = target;
+        self.source = Some((samples, channel_count));
+    }
+}
+
+impl InputProvider for AudioAnalysisProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        vec![
+            format!("{}_waveform", self.name),
+            format!("{}_waveform_texture", self.name),
+            format!("{}_spectrum", self.name),
+            format!("{}_spectrum_texture", self.name),
+            format!("{}_bands", self.name),
+            format!("{}_rms", self.name),
+            format!("{}_peak", self.name),
+            format!("{}_onset", self.name),
+        ]
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        let unit_range = DataRange::FloatRange(0.0, 1.0, 0.001);
+
+        vec![
+            UniformDescription::new(
+                &format!("{}_waveform", self.name),
+                DataType::FloatArray,
+                DataRange::FloatRange(-1.0, 1.0, 0.001),
+                UpdateFrequency::PerFrame,
+            ),
+            UniformDescription::new(
+                &format!("{}_waveform_texture", self.name),
+                DataType::Texture,
+                DataRange::None,
+                UpdateFrequency::PerFrame,
+            ),
+            UniformDescription::new(
+                &format!("{}_spectrum", self.name),
+                DataType::FloatArray,
+                unit_range,
+                UpdateFrequency::PerFrame,
+            ),
+            UniformDescription::new(
+                &format!("{}_spectrum_texture", self.name),
+                DataType::Texture,
+                DataRange::None,
+                UpdateFrequency::PerFrame,
+            ),
+            UniformDescription::new(
+                &format!("{}_bands", self.name),
+                DataType::FloatArray,
+                unit_range,
+                UpdateFrequency::PerFrame,
+            ),
+            UniformDescription::new(
+                &format!("{}_rms", self.name),
+                DataType::Float,
+                unit_range,
+                UpdateFrequency::PerFrame,
+            ),
+            UniformDescription::new(
+                &format!("{}_peak", self.name),
+                DataType::Float,
+                unit_range,
+                UpdateFrequency::PerFrame,
+            ),
+            UniformDescription::new(
+                &format!("{}_onset", self.name),
+                DataType::Bool,
+                DataRange::None,
+                UpdateFrequency::PerFrame,
+            ),
+        ]
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        let local_name = self.get_local_name(uniform_name)?;
+        Ok(self.uniforms.get_generation(local_name))
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        let local_name = self.get_local_name(uniform_name)?;
+        Ok(self.uniforms.get(local_name))
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()> {
+        match self
+            .list_properties()
+            .iter()
+            .find(|description| description.name == property)
+        {
+            Some(description) => description.check(value)?,
+            None => return Err(InputError::UnknownProperty(property.to_string())),
+        }
+
+        match (property, value) {
+            ("attack", DataHolder::Float(attack)) => self.analyzer.set_attack(*attack),
+            ("release", DataHolder::Float(release)) => self.analyzer.set_release(*release),
+            ("gain", DataHolder::Float(gain)) => self.analyzer.set_gain(*gain),
+            ("onset_threshold", DataHolder::Float(onset_threshold)) => {
+                self.analyzer.set_onset_threshold(*onset_threshold)
+            }
+            ("band_count", DataHolder::Int(band_count)) => {
+                self.analyzer.set_band_count(*band_count as usize)?
+            }
+            _ => return Err(InputError::UnknownProperty(property.to_string())),
+        }
+
+        Ok(())
+    }
+
+    fn list_properties(&self) -> Vec<PropertyDescription> {
+        vec![
+            PropertyDescription::new(
+                "attack",
+                DataType::Float,
+                DataRange::FloatRange(0.0, 1.0, 0.01),
+                "Smoothing factor applied when a value rises",
+            ),
+            PropertyDescription::new(
+                "release",
+                DataType::Float,
+                DataRange::FloatRange(0.0, 1.0, 0.01),
+                "Smoothing factor applied when a value falls",
+            ),
+            PropertyDescription::new(
+                "gain",
+                DataType::Float,
+                DataRange::FloatRange(0.0, 16.0, 0.01),
+                "Input gain applied before analysis",
+            ),
+            PropertyDescription::new(
+                "onset_threshold",
+                DataType::Float,
+                DataRange::FloatRange(1.0, 10.0, 0.1),
+                "Spectral flux ratio above its running average that triggers an onset",
+            ),
+            PropertyDescription::new(
+                "band_count",
+                DataType::Int,
+                DataRange::IntRange(1, 64, 1),
+                "Number of log-spaced frequency bands",
+            ),
+        ]
+    }
+
+    fn get_property(&self, property: &str) -> InputResult<DataHolder> {
+        match property {
+            "attack" => Ok(DataHolder::Float(self.analyzer.get_attack())),
+            "release" => Ok(DataHolder::Float(self.analyzer.get_release())),
+            "gain" => Ok(DataHolder::Float(self.analyzer.get_gain())),
+            "onset_threshold" => Ok(DataHolder::Float(self.analyzer.get_onset_threshold())),
+            "band_count" => Ok(DataHolder::Int(self.analyzer.get_band_count() as i32)),
+            _ => Err(InputError::UnknownProperty(property.to_string())),
+        }
+    }
+
+    fn set_beat(&mut self, beat: f64, _sync: bool) {
+        if let Some(bpm) = self.beat_sync {
+            self.advance(beat * 60.0 / bpm);
+        }
+    }
+
+    fn set_time(&mut self, time: f64, _sync: bool) {
+        if self.beat_sync.is_none() {
+            self.advance(time);
+        }
+    }
+
+    fn is_seekable(&self) -> bool {
+        self.source.is_some()
+    }
+
+    fn seek_beat(&mut self, beat: f64) -> InputResult<()> {
+        match self.beat_sync {
+            Some(bpm) => self.seek_time(beat * 60.0 / bpm),
+            None => Err(InputError::Unsupported(
+                "seek_beat without beat sync".to_string(),
+            )),
+        }
+    }
+
+    fn seek_time(&mut self, time: f64) -> InputResult<()> {
+        match self.get_position() {
+            Some(position) => {
+                self.time_offset += time - position;
+                self.seek(time);
+                Ok(())
+            }
+            None => Err(InputError::Unsupported(
+                "seek_time on a live audio input".to_string(),
+            )),
+        }
+    }
+
+    fn get_duration(&self) -> Option<f64> {
+        self.source.as_ref().map(|(samples, channel_count)| {
+            (samples.len() / channel_count) as f64 / self.analyzer.get_sample_rate() as f64
+        })
+    }
+
+    fn get_position(&self) -> Option<f64> {
+        self.source
+            .as_ref()
+            .map(|_| self.cursor as f64 / self.analyzer.get_sample_rate() as f64)
+    }
+
+    fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    fn set_looping(&mut self, looping: bool) -> InputResult<()> {
+        self.looping = looping;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> InputResult<()> {
+        self.playing = false;
+        self.seek_time(0.0)
+    }
+
+    fn pause(&mut self) -> InputResult<()> {
+        self.playing = false;
+        Ok(())
+    }
+
+    fn play(&mut self) -> InputResult<()> {
+        self.playing = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn assert_close(value: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (value - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            value,
+            tolerance,
+            expected
+        );
+    }
+
+    fn get_analyzer() -> AudioAnalyzer {
+        let mut analyzer = AudioAnalyzer::new(48_000, 1024, 8);
+        analyzer.set_attack(1.0);
+        analyzer.set_release(1.0);
+
+        analyzer
+    }
+
+    // Frequencies centered on a bin keep all of their energy out of the other bins
+    fn get_sine(analyzer: &AudioAnalyzer, bin: usize, amplitude: f32) -> Vec<f32> {
+        let size = analyzer.get_fft_size();
+        (0..size)
+            .map(|index| amplitude * (2.0 * PI * (bin * index) as f32 / size as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn fft_of_an_impulse_is_flat() {
+        let mut real = vec![0.0; 8];
+        real[0] = 1.0;
+        let mut imaginary = vec![0.0; 8];
+        fft(&mut real, &mut imaginary);
+
+        for (real, imaginary) in real.iter().zip(imaginary.iter()) {
+            assert_close(*real, 1.0, 1e-6);
+            assert_close(*imaginary, 0.0, 1e-6);
+        }
+    }
+
+    #[test]
+    fn fft_matches_known_spectra() {
+        let mut real = vec![1.0; 8];
+        let mut imaginary = vec![0.0; 8];
+        fft(&mut real, &mut imaginary);
+        assert_close(real[0], 8.0, 1e-5);
+        assert!(real[1..].iter().all(|value| value.abs() < 1e-5));
+
+        // A cosine lands on its bin and the mirrored one, a sine on the imaginary axis
+        let size = 16;
+        let mut real: Vec<f32> = (0..size)
+            .map(|index| {
+                let phase = 2.0 * PI * index as f32 / size as f32;
+                (2.0 * phase).cos() + (3.0 * phase).sin()
+            })
+            .collect();
+        let mut imaginary = vec![0.0; size];
+        fft(&mut real, &mut imaginary);
+
+        for bin in 0..size {
+            let expected_real = if bin == 2 || bin == size - 2 {
+                8.0
+            } else {
+                0.0
+            };
+            let expected_imaginary = match bin {
+                3 => -8.0,
+                13 => 8.0,
+                _ => 0.0,
+            };
+            assert_close(real[bin], expected_real, 1e-4);
+            assert_close(imaginary[bin], expected_imaginary, 1e-4);
+        }
+    }
+
+    #[test]
+    fn analysis_reports_level_and_spectrum() {
+        let mut analyzer = get_analyzer();
+        let samples = get_sine(&analyzer, 64, 0.5);
+        analyzer.push_samples(&samples, 1);
+        analyzer.update();
+
+        assert_close(analyzer.get_rms(), 0.5 / 2.0f32.sqrt(), 1e-3);
+        assert_close(analyzer.get_peak(), 0.5, 1e-3);
+
+        // The Hann window halves the amplitude of a bin centered sine
+        let spectrum = analyzer.get_spectrum();
+        assert_close(spectrum[64], 0.25, 1e-3);
+        assert_close(spectrum[63], 0.125, 1e-3);
+        assert!(spectrum[..60].iter().all(|magnitude| *magnitude < 1e-3));
+
+        let band_edges = analyzer.get_band_edges();
+        let band = band_edges
+            .windows(2)
+            .position(|edges| (edges[0]..edges[1]).contains(&64))
+            .unwrap();
+        let bands = analyzer.get_bands();
+        assert!(bands[band] > 0.0);
+        assert!(bands[..band].iter().all(|energy| *energy < 1e-3));
+
+        analyzer.set_gain(2.0);
+        analyzer.push_samples(&samples, 1);
+        analyzer.update();
+        assert_close(analyzer.get_rms(), 1.0 / 2.0f32.sqrt(), 1e-3);
+        assert_close(analyzer.get_peak(), 1.0, 1e-3);
+    }
+
+    #[test]
+    fn stereo_frames_are_mixed_down() {
+        let mut analyzer = get_analyzer();
+        let samples: Vec<f32> = (0..analyzer.get_fft_size())
+            .flat_map(|_| [0.5, -0.5])
+            .collect();
+        analyzer.push_samples(&samples, 2);
+        analyzer.update();
+
+        assert_eq!(analyzer.get_rms(), 0.0);
+        assert_eq!(analyzer.get_peak(), 0.0);
+    }
+
+    #[test]
+    fn band_edges_are_increasing() {
+        let mut analyzer = get_analyzer();
+        assert!(analyzer.set_band_count(0).is_err());
+        assert_eq!(analyzer.get_band_count(), 8);
+
+        analyzer.set_band_count(16).unwrap();
+        let band_edges = analyzer.get_band_edges();
+        assert_eq!(band_edges.len(), 17);
+        assert!(band_edges.windows(2).all(|edges| edges[0] <= edges[1]));
+        assert!(band_edges.iter().all(|edge| *edge < 512));
+    }
+
+    #[test]
+    fn properties_are_range_checked() {
+        let mut provider = AudioAnalysisProvider::new(48_000, 1024, 8);
+
+        for (property, value) in [
+            ("gain", DataHolder::Float(-1.0)),
+            ("gain", DataHolder::Float(17.0)),
+            ("band_count", DataHolder::Int(0)),
+            ("band_count", DataHolder::Int(100_000)),
+            ("attack", DataHolder::Float(2.0)),
+        ] {
+            assert!(
+                matches!(
+                    provider.set_property(property, &value),
+                    Err(InputError::OutOfRange { .. })
+                ),
+                "{} {:?}",
+                property,
+                value
+            );
+        }
+        assert_eq!(provider.get_property("gain"), Ok(DataHolder::Float(1.0)));
+        assert_eq!(provider.get_property("band_count"), Ok(DataHolder::Int(8)));
+
+        provider
+            .set_property("band_count", &DataHolder::Int(32))
+            .unwrap();
+        assert_eq!(provider.get_analyzer().get_bands().len(), 32);
+        assert!(matches!(
+            provider.set_property("gain", &DataHolder::Int(2)),
+            Err(InputError::WrongValueType { .. })
+        ));
+        assert!(matches!(
+            provider.set_property("volume", &DataHolder::Float(1.0)),
+            Err(InputError::UnknownProperty(_))
+        ));
+    }
+
+    // Silent for the first half second, then a constant level
+    fn create_wav(file_name: &str, channel_count: u16) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("wvr-{}-{}.wav", file_name, std::process::id()));
+        let spec = hound::WavSpec {
+            channels: channel_count,
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for index in 0..1000 {
+            let sample = if index < 500 { 0 } else { i16::MAX / 2 + 1 };
+            for _ in 0..channel_count {
+                writer.write_sample(sample).unwrap();
+            }
+        }
+        writer.finalize().unwrap();
+
+        path
+    }
+
+    fn create_provider(path: &Path) -> AudioAnalysisProvider {
+        let mut provider = AudioAnalysisProvider::from_file(path, 64, 4).unwrap();
+        provider.set_name("audio");
+
+        provider
+    }
+
+    fn is_silent(provider: &AudioAnalysisProvider) -> bool {
+        provider
+            .get_analyzer()
+            .get_window()
+            .all(|sample| *sample == 0.0)
+    }
+
+    #[test]
+    fn wav_files_decode_to_interleaved_samples() {
+        let path = create_wav("audio-decode", 2);
+        let (samples, channel_count, sample_rate) = decode_audio_file(&path).unwrap();
+        assert_eq!((channel_count, sample_rate), (2, 1000));
+        assert_eq!(samples.len(), 2000);
+        assert_eq!(samples[999], 0.0);
+        assert_eq!(samples[1000], 0.5);
+        assert_eq!(samples[1001], 0.5);
+
+        let provider = create_provider(&path);
+        assert_eq!(provider.get_duration(), Some(1.0));
+        assert_eq!(provider.get_position(), Some(0.0));
+        assert!(provider.is_seekable());
+        assert!(decode_audio_file(&path.with_extension("mp3")).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn the_clock_follows_time_or_beat_sync() {
+        let path = create_wav("audio-clock", 1);
+        let mut provider = create_provider(&path);
+
+        provider.set_beat(1.0, true);
+        assert_eq!(provider.get_position(), Some(0.0));
+        provider.set_time(0.75, true);
+        assert_eq!(provider.get_position(), Some(0.75));
+        assert!(matches!(
+            provider.get("audio_peak").unwrap(),
+            Some(DataHolder::Float(peak)) if peak > 0.0
+        ));
+        assert!(provider.get_generation("audio_waveform").unwrap() > 0);
+        assert!(provider.seek_beat(1.0).is_err());
+
+        provider.set_beat_sync(Some(120.0));
+        provider.set_time(0.25, true);
+        assert_eq!(provider.get_position(), Some(0.75));
+        provider.set_beat(0.5, true);
+        assert_eq!(provider.get_position(), Some(0.25));
+        provider.seek_beat(1.5).unwrap();
+        assert_eq!(provider.get_position(), Some(0.75));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn seeks_back_start_from_silence() {
+        let path = create_wav("audio-seek", 1);
+        let mut provider = create_provider(&path);
+
+        provider.seek_time(0.9).unwrap();
+        assert!(provider
+            .get_analyzer()
+            .get_window()
+            .all(|sample| *sample == 0.5));
+
+        // Fewer samples than the window lie before the target
+        provider.seek_time(0.03).unwrap();
+        assert!(is_silent(&provider));
+
+        provider.seek_time(0.9).unwrap();
+        provider.stop().unwrap();
+        assert_eq!(provider.get_position(), Some(0.0));
+        assert!(is_silent(&provider));
+
+        // Stopped providers keep their position as the clock moves on
+        provider.set_time(10.0, true);
+        assert_eq!(provider.get_position(), Some(0.0));
+        provider.play().unwrap();
+        provider.set_time(10.5, true);
+        assert_eq!(provider.get_position(), Some(0.5));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn looping_wraps_the_position() {
+        let path = create_wav("audio-loop", 1);
+        let mut provider = create_provider(&path);
+
+        provider.set_time(1.5, true);
+        assert_eq!(provider.get_position(), Some(1.0));
+
+        provider.set_looping(true).unwrap();
+        assert!(provider.is_looping());
+        provider.set_time(2.25, true);
+        assert_eq!(provider.get_position(), Some(0.25));
+        assert!(is_silent(&provider));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,1109 @@
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::config::data_file::{DataFileClock, DataFileColumn, DataFileInterpolation};
+use crate::types::{
+    ArmedCue, DataHolder, DataRange, DataType, InputError, InputProvider, InputResult,
+    PlaybackConfig, PlaybackMode, PropertyDescription, UniformDescription, UniformStore,
+    UpdateFrequency,
+};
+
+const MAX_RATE: f64 = 16.0;
+
+fn parse_csv_cell(cell: &str) -> Result<Option<Vec<f64>>> {
+    let cell = cell.trim();
+    if cell.is_empty() {
+        return Ok(None);
+    }
+
+    // Array cells hold semicolon separated values
+    cell.split(';')
+        .map(|value| match value.trim() {
+            "true" => Ok(1.0),
+            "false" => Ok(0.0),
+            value => value
+                .parse::<f64>()
+                .with_context(|| format!("Invalid number: {}", value)),
+        })
+        .collect::<Result<Vec<f64>>>()
+        .map(Some)
+}
+
+fn flatten_json_value(value: &Value, numbers: &mut Vec<f64>) -> Result<()> {
+    match value {
+        Value::Number(number) => match number.as_f64() {
+            Some(number) => numbers.push(number),
+            None => bail!("Unsupported number: {}", number),
+        },
+        Value::Bool(value) => numbers.push(*value as u8 as f64),
+        Value::Array(values) => {
+            for value in values {
+                flatten_json_value(value, numbers)?;
+            }
+        }
+        _ => bail!("Unsupported data value: {}", value),
+    }
+
+    Ok(())
+}
+
+fn parse_json_cell(value: &Value) -> Result<Option<Vec<f64>>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+
+    let mut numbers = Vec::new();
+    flatten_json_value(value, &mut numbers)?;
+    Ok(Some(numbers))
+}
+
+pub fn numbers_to_data_holder(numbers: &[f64], data_type: DataType) -> Option<DataHolder> {
+    let floats: Vec<f32> = numbers.iter().map(|value| *value as f32).collect();
+    let ints: Vec<i32> = numbers.iter().map(|value| value.round() as i32).collect();
+
+    let holder = match data_type {
+        DataType::Float => DataHolder::Float(*floats.first()?),
+        DataType::Float2 => DataHolder::Float2(floats.try_into().ok()?),
+        DataType::Float3 => DataHolder::Float3(floats.try_into().ok()?),
+        DataType::Float4 => DataHolder::Float4(floats.try_into().ok()?),
+        DataType::FloatArray => DataHolder::FloatArray(floats),
+
+        DataType::Int => DataHolder::Int(*ints.first()?),
+        DataType::Int2 => DataHolder::Int2(ints.try_into().ok()?),
+        DataType::Int3 => DataHolder::Int3(ints.try_into().ok()?),
+        DataType::Int4 => DataHolder::Int4(ints.try_into().ok()?),
+        DataType::IntArray => DataHolder::IntArray(ints),
+
+        DataType::Mat2 => DataHolder::Mat2([
+            floats.get(0..2)?.try_into().ok()?,
+            floats.get(2..4)?.try_into().ok()?,
+        ]),
+        DataType::Mat3 => DataHolder::Mat3([
+            floats.get(0..3)?.try_into().ok()?,
+            floats.get(3..6)?.try_into().ok()?,
+            floats.get(6..9)?.try_into().ok()?,
+        ]),
+        DataType::Mat4 => DataHolder::Mat4([
+            floats.get(0..4)?.try_into().ok()?,
+            floats.get(4..8)?.try_into().ok()?,
+            floats.get(8..12)?.try_into().ok()?,
+            floats.get(12..16)?.try_into().ok()?,
+        ]),
+
+        DataType::Bool => DataHolder::Bool(*numbers.first()? != 0.0),
+        DataType::BoolArray => {
+            DataHolder::BoolArray(numbers.iter().map(|value| *value != 0.0).collect())
+        }
+
+        DataType::ByteArray => DataHolder::ByteArray(
+            numbers
+                .iter()
+                .map(|value| value.clamp(0.0, 255.0) as u8)
+                .collect(),
+        ),
+
+        DataType::String | DataType::Texture | DataType::SrgbTexture => return None,
+    };
+
+    Some(holder)
+}
+
+fn infer_type(width: usize) -> DataType {
+    match width {
+        1 => DataType::Float,
+        2 => DataType::Float2,
+        3 => DataType::Float3,
+        4 => DataType::Float4,
+        _ => DataType::FloatArray,
+    }
+}
+
+fn get_rate_description() -> PropertyDescription {
+    PropertyDescription::new(
+        "rate",
+        DataType::Float,
+        DataRange::FloatRange(-MAX_RATE, MAX_RATE, 0.01),
+        "Playback rate relative to the clock",
+    )
+}
+
+fn get_local_name(column: &str) -> String {
+    column
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// Keys are relative to the first sample, missing cells repeat the previous value
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DataSeries {
+    keys: Vec<f64>,
+    columns: BTreeMap<String, Vec<Vec<f64>>>,
+}
+
+impl DataSeries {
+    pub fn from_rows(
+        time_column: &str,
+        column_names: &[String],
+        rows: Vec<Vec<Option<Vec<f64>>>>,
+    ) -> Result<Self> {
+        let time_index = match column_names.iter().position(|name| name == time_column) {
+            Some(time_index) => time_index,
+            None => bail!("Missing time column: {}", time_column),
+        };
+
+        let mut rows = rows
+            .into_iter()
+            .enumerate()
+            .map(|(index, row)| match row.get(time_index) {
+                Some(Some(key)) if key.len() == 1 && key[0].is_finite() => Ok((key[0], row)),
+                _ => bail!("Invalid time value in row {}", index + 1),
+            })
+            .collect::<Result<Vec<(f64, Vec<Option<Vec<f64>>>)>>>()?;
+
+        if rows.is_empty() {
+            bail!("Data file has no rows");
+        }
+
+        rows.sort_by(|(key_a, _), (key_b, _)| key_a.total_cmp(key_b));
+        let first_key = rows[0].0;
+
+        let mut columns = BTreeMap::new();
+        for (column_index, column_name) in column_names.iter().enumerate() {
+            if column_index == time_index {
+                continue;
+            }
+
+            // Leading gaps take the first known value
+            let mut last_value = rows
+                .iter()
+                .find_map(|(_, row)| row.get(column_index).cloned().flatten())
+                .unwrap_or_default();
+            let values: Vec<Vec<f64>> = rows
+                .iter()
+                .map(|(_, row)| {
+                    if let Some(Some(value)) = row.get(column_index) {
+                        last_value = value.clone();
+                    }
+                    last_value.clone()
+                })
+                .collect();
+
+            columns.insert(column_name.clone(), values);
+        }
+
+        Ok(Self {
+            keys: rows.iter().map(|(key, _)| key - first_key).collect(),
+            columns,
+        })
+    }
+
+    pub fn from_csv<R: Read>(reader: R, time_column: &str) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let column_names: Vec<String> = reader
+            .headers()
+            .context("Failed to read CSV header")?
+            .iter()
+            .map(String::from)
+            .collect();
+
+        let rows = reader
+            .records()
+            .enumerate()
+            .map(|(index, record)| {
+                let record = record.context("Failed to read CSV record")?;
+                record
+                    .iter()
+                    .map(parse_csv_cell)
+                    .collect::<Result<Vec<Option<Vec<f64>>>>>()
+                    .with_context(|| format!("Invalid CSV row {}", index + 1))
+            })
+            .collect::<Result<Vec<Vec<Option<Vec<f64>>>>>>()?;
+
+        Self::from_rows(time_column, &column_names, rows)
+    }
+
+    // Accepts either an array of records or an object of columns
+    pub fn from_json(value: &Value, time_column: &str) -> Result<Self> {
+        match value {
+            Value::Array(records) => {
+                let mut column_names: Vec<String> = Vec::new();
+                for record in records {
+                    match record {
+                        Value::Object(record) => {
+                            for name in record.keys() {
+                                if !column_names.contains(name) {
+                                    column_names.push(name.clone());
+                                }
+                            }
+                        }
+                        _ => bail!("Data records must be objects"),
+                    }
+                }
+
+                let rows = records
+                    .iter()
+                    .map(|record| {
+                        column_names
+                            .iter()
+                            .map(|name| match record.get(name) {
+                                Some(value) => parse_json_cell(value),
+                                None => Ok(None),
+                            })
+                            .collect()
+                    })
+                    .collect::<Result<Vec<Vec<Option<Vec<f64>>>>>>()?;
+
+                Self::from_rows(time_column, &column_names, rows)
+            }
+            Value::Object(columns) => {
+                let column_names: Vec<String> = columns.keys().cloned().collect();
+                let columns = columns
+                    .values()
+                    .map(|column| match column {
+                        Value::Array(values) => values
+                            .iter()
+                            .map(parse_json_cell)
+                            .collect::<Result<Vec<Option<Vec<f64>>>>>(),
+                        _ => bail!("Data columns must be arrays"),
+                    })
+                    .collect::<Result<Vec<Vec<Option<Vec<f64>>>>>>()?;
+
+                let row_count = columns.iter().map(Vec::len).max().unwrap_or_default();
+                let rows = (0..row_count)
+                    .map(|index| {
+                        columns
+                            .iter()
+                            .map(|column| column.get(index).cloned().flatten())
+                            .collect()
+                    })
+                    .collect();
+
+                Self::from_rows(time_column, &column_names, rows)
+            }
+            _ => bail!("Data file must hold an array of records or an object of columns"),
+        }
+    }
+
+    pub fn load(path: &Path, time_column: &str) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open data file: {:?}", path))?;
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        match extension.as_deref() {
+            Some("csv") => Self::from_csv(BufReader::new(file), time_column),
+            Some("json") => {
+                let value: Value = serde_json::from_reader(BufReader::new(file))
+                    .with_context(|| format!("Failed to parse data file: {:?}", path))?;
+                Self::from_json(&value, time_column)
+            }
+            _ => bail!("Unsupported data file format: {:?}", path),
+        }
+        .with_context(|| format!("Failed to load data file: {:?}", path))
+    }
+
+    pub fn get_duration(&self) -> f64 {
+        self.keys.last().copied().unwrap_or_default()
+    }
+
+    pub fn get_sample_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn get_column_names(&self) -> Vec<&str> {
+        self.columns.keys().map(String::as_str).collect()
+    }
+
+    pub fn get_values(&self, column: &str) -> Option<&[Vec<f64>]> {
+        self.columns.get(column).map(Vec::as_slice)
+    }
+
+    pub fn sample(
+        &self,
+        column: &str,
+        position: f64,
+        interpolation: DataFileInterpolation,
+    ) -> Option<Vec<f64>> {
+        let values = self.columns.get(column)?;
+        let next = self.keys.partition_point(|key| *key <= position);
+        let index = next.saturating_sub(1);
+
+        let (current, following) = match (values.get(index), values.get(next)) {
+            (Some(current), Some(following))
+                if next > 0 && interpolation == DataFileInterpolation::Linear =>
+            {
+                (current, following)
+            }
+            (Some(current), _) => return Some(current.clone()),
+            _ => return None,
+        };
+
+        // Cells of different sizes cannot be blended
+        if current.len() != following.len() {
+            return Some(current.clone());
+        }
+
+        let span = self.keys[next] - self.keys[index];
+        let progress = if span > 0.0 {
+            (position - self.keys[index]) / span
+        } else {
+            0.0
+        };
+
+        Some(
+            current
+                .iter()
+                .zip(following)
+                .map(|(current, following)| current + (following - current) * progress)
+                .collect(),
+        )
+    }
+}
+
+struct DataFileMapping {
+    local_name: String,
+    columns: Vec<String>,
+    data_type: DataType,
+}
+
+pub struct DataFileProvider {
+    name: String,
+    series: DataSeries,
+    mappings: Vec<DataFileMapping>,
+    clock: DataFileClock,
+    interpolation: DataFileInterpolation,
+    rate: f64,
+    playback: PlaybackConfig,
+
+    last_clock: Option<f64>,
+    beat: f64,
+    cursor: f64,
+    playing: bool,
+    armed_cue: Option<ArmedCue>,
+    error: Option<InputError>,
+    uniforms: UniformStore,
+}
+
+impl DataFileProvider {
+    pub fn new(
+        series: DataSeries,
+        columns: &[DataFileColumn],
+        clock: DataFileClock,
+        interpolation: DataFileInterpolation,
+        rate: f64,
+        playback: PlaybackConfig,
+    ) -> Result<Self> {
+        // Without explicit columns, every data column becomes its own uniform
+        let columns: Vec<DataFileColumn> = if columns.is_empty() {
+            series
+                .get_column_names()
+                .iter()
+                .map(|column| DataFileColumn {
+                    name: get_local_name(column),
+                    columns: vec![column.to_string()],
+                    data_type: None,
+                })
+                .collect()
+        } else {
+            columns.to_vec()
+        };
+
+        if !(-MAX_RATE..=MAX_RATE).contains(&rate) {
+            bail!(
+                "Data file rate {} is out of range -{}..{}",
+                rate,
+                MAX_RATE,
+                MAX_RATE
+            );
+        }
+
+        let mut mappings = Vec::new();
+        for column in columns {
+            let sources = column
+                .columns
+                .iter()
+                .map(|source| match series.get_values(source) {
+                    Some(values) => Ok(values),
+                    None => bail!("Unknown data column: {}", source),
+                })
+                .collect::<Result<Vec<&[Vec<f64>]>>>()?;
+            let samples: Vec<Vec<f64>> = (0..series.get_sample_count())
+                .map(|index| {
+                    sources
+                        .iter()
+                        .flat_map(|values| values[index].iter().copied())
+                        .collect()
+                })
+                .collect();
+
+            // Every sample is checked up front so that playback never hits a bad cell,
+            // cells of varying width can only be held by an array
+            let data_type = match column.data_type {
+                Some(data_type) => data_type,
+                None if samples
+                    .iter()
+                    .all(|sample| sample.len() == samples[0].len()) =>
+                {
+                    infer_type(samples[0].len())
+                }
+                None => DataType::FloatArray,
+            };
+            if let Some(index) = samples
+                .iter()
+                .position(|sample| numbers_to_data_holder(sample, data_type).is_none())
+            {
+                bail!(
+                    "Data columns {:?} cannot be mapped to {:?} at sample {}",
+                    column.columns,
+                    data_type,
+                    index + 1
+                );
+            }
+
+            mappings.push(DataFileMapping {
+                local_name: column.name,
+                columns: column.columns,
+                data_type,
+            });
+        }
+
+        let mut provider = Self {
+            name: String::new(),
+            series,
+            mappings,
+            clock,
+            interpolation,
+            rate,
+            playback,
+            last_clock: None,
+            beat: 0.0,
+            cursor: 0.0,
+            playing: true,
+            armed_cue: None,
+            error: None,
+            uniforms: UniformStore::default(),
+        };
+        provider.update_values();
+
+        Ok(provider)
+    }
+
+    pub fn from_file(
+        path: &Path,
+        time_column: &str,
+        columns: &[DataFileColumn],
+        clock: DataFileClock,
+        interpolation: DataFileInterpolation,
+        rate: f64,
+        playback: PlaybackConfig,
+    ) -> Result<Self> {
+        Self::new(
+            DataSeries::load(path, time_column)?,
+            columns,
+            clock,
+            interpolation,
+            rate,
+            playback,
+        )
+    }
+
+    pub fn get_series(&self) -> &DataSeries {
+        &self.series
+    }
+
+    // Positions are expressed in the unit of the time column, seconds or beats
+    fn get_position_units(&self) -> f64 {
+        self.playback
+            .resolve_position(self.cursor, self.series.get_duration())
+    }
+
+    fn advance(&mut self, clock: f64) {
+        if let Some(last_clock) = self.last_clock {
+            if self.playing {
+                let rate = self.playback.get_rate(self.rate, self.beat);
+                self.cursor += (clock - last_clock) * rate;
+            }
+        }
+
+        self.last_clock = Some(clock);
+        self.update_values();
+    }
+
+    // The jump happens on the first beat update at or past the cue quantization
+    pub fn trigger_cue(&mut self, name: &str) -> InputResult<()> {
+        self.armed_cue = Some(self.playback.arm_cue(name, self.beat)?);
+        self.check_cue();
+        Ok(())
+    }
+
+    fn check_cue(&mut self) {
+        if self
+            .armed_cue
+            .as_ref()
+            .is_some_and(|armed_cue| armed_cue.is_due(self.beat))
+        {
+            if let Some(armed_cue) = self.armed_cue.take() {
+                self.seek_units(armed_cue.position);
+            }
+        }
+    }
+
+    fn seek_units(&mut self, position: f64) {
+        let (start, end) = self.playback.get_bounds(self.series.get_duration());
+        let offset = match self.playback.mode {
+            PlaybackMode::Reverse => end - position,
+            PlaybackMode::Forward | PlaybackMode::PingPong => position - start,
+        };
+
+        self.cursor = offset - self.playback.start_offset;
+        self.update_values();
+    }
+
+    fn update_values(&mut self) {
+        let position = self.get_position_units();
+        for mapping in &self.mappings {
+            let numbers: Vec<f64> = mapping
+                .columns
+                .iter()
+                .filter_map(|column| self.series.sample(column, position, self.interpolation))
+                .flatten()
+                .collect();
+
+            match numbers_to_data_holder(&numbers, mapping.data_type) {
+                Some(value) => {
+                    self.uniforms.set_if_changed(&mapping.local_name, value);
+                }
+                None => {
+                    self.error = Some(InputError::Other(format!(
+                        "Data columns {:?} cannot be mapped to {:?} at {}",
+                        mapping.columns, mapping.data_type, position
+                    )))
+                }
+            }
+        }
+    }
+
+    fn get_mapping(&self, uniform_name: &str) -> InputResult<&DataFileMapping> {
+        uniform_name
+            .strip_prefix(&self.name)
+            .and_then(|local_name| local_name.strip_prefix('_'))
+            .and_then(|local_name| {
+                self.mappings
+                    .iter()
+                    .find(|mapping| mapping.local_name == local_name)
+            })
+            .ok_or_else(|| InputError::UnknownUniform(uniform_name.to_string()))
+    }
+}
+
+impl InputProvider for DataFileProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.mappings
+            .iter()
+            .map(|mapping| format!("{}_{}", self.name, mapping.local_name))
+            .collect()
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        self.mappings
+            .iter()
+            .map(|mapping| {
+                UniformDescription::new(
+                    &format!("{}_{}", self.name, mapping.local_name),
+                    mapping.data_type,
+                    DataRange::None,
+                    UpdateFrequency::OnChange,
+                )
+            })
+            .collect()
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        let mapping = self.get_mapping(uniform_name)?;
+        Ok(self.uniforms.get_generation(&mapping.local_name))
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        let local_name = self.get_mapping(uniform_name)?.local_name.clone();
+        Ok(self.uniforms.get(&local_name))
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()> {
+        match self
+            .list_properties()
+            .iter()
+            .find(|description| description.name == property)
+        {
+            Some(description) => description.check(value)?,
+            None => return Err(InputError::UnknownProperty(property.to_string())),
+        }
+
+        match (property, value) {
+            ("rate", DataHolder::Float(rate)) => self.rate = *rate as f64,
+            ("cue", DataHolder::String(name)) => self.trigger_cue(name)?,
+            ("interpolation", DataHolder::String(interpolation)) => {
+                self.interpolation =
+                    DataFileInterpolation::from_name(interpolation).ok_or_else(|| {
+                        InputError::invalid_value(property, interpolation, &["Step", "Linear"])
+                    })?;
+            }
+            _ => self.playback.set_property(property, value)?,
+        }
+
+        self.update_values();
+        Ok(())
+    }
+
+    fn list_properties(&self) -> Vec<PropertyDescription> {
+        let mut properties = vec![
+            get_rate_description(),
+            PropertyDescription::new(
+                "interpolation",
+                DataType::String,
+                DataRange::None,
+                "Step or Linear",
+            ),
+        ];
+        properties.extend(PlaybackConfig::list_properties());
+
+        properties
+    }
+
+    fn get_property(&self, property: &str) -> InputResult<DataHolder> {
+        match property {
+            "rate" => Ok(DataHolder::Float(self.rate as f32)),
+            "interpolation" => Ok(DataHolder::String(self.interpolation.name().to_string())),
+            "cue" => Ok(DataHolder::String(
+                self.armed_cue
+                    .as_ref()
+                    .map(|armed_cue| armed_cue.name.clone())
+                    .unwrap_or_default(),
+            )),
+            _ => self
+                .playback
+                .get_properties()
+                .into_iter()
+                .find(|(name, _)| name == property)
+                .map(|(_, value)| value)
+                .ok_or_else(|| InputError::UnknownProperty(property.to_string())),
+        }
+    }
+
+    fn set_beat(&mut self, beat: f64, _sync: bool) {
+        self.beat = beat;
+        if self.clock == DataFileClock::Beat {
+            self.advance(beat);
+        }
+        self.check_cue();
+    }
+
+    fn set_time(&mut self, time: f64, _sync: bool) {
+        if self.clock == DataFileClock::Time {
+            self.advance(time);
+        }
+    }
+
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn seek_time(&mut self, time: f64) -> InputResult<()> {
+        if self.clock != DataFileClock::Time {
+            return Err(InputError::Unsupported(
+                "seek_time on a beat clocked data file".to_string(),
+            ));
+        }
+
+        self.seek_units(time);
+        Ok(())
+    }
+
+    fn seek_beat(&mut self, beat: f64) -> InputResult<()> {
+        if self.clock != DataFileClock::Beat {
+            return Err(InputError::Unsupported(
+                "seek_beat on a time clocked data file".to_string(),
+            ));
+        }
+
+        self.seek_units(beat);
+        Ok(())
+    }
+
+    // In seconds or beats, following the clock
+    fn get_duration(&self) -> Option<f64> {
+        Some(self.series.get_duration())
+    }
+
+    fn get_position(&self) -> Option<f64> {
+        Some(self.get_position_units())
+    }
+
+    fn is_looping(&self) -> bool {
+        self.playback.looping
+    }
+
+    fn set_looping(&mut self, looping: bool) -> InputResult<()> {
+        self.playback.looping = looping;
+        self.update_values();
+        Ok(())
+    }
+
+    fn stop(&mut self) -> InputResult<()> {
+        self.playing = false;
+        self.cursor = 0.0;
+        self.update_values();
+        Ok(())
+    }
+
+    fn pause(&mut self) -> InputResult<()> {
+        self.playing = false;
+        Ok(())
+    }
+
+    fn play(&mut self) -> InputResult<()> {
+        self.playing = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::types::{Automation, CueMarker, Lfo, LfoType};
+
+    // One second per sample, `level` follows the time column
+    const RAMP_CSV: &str = "time,level\n0,0\n1,1\n2,2\n3,3\n4,4\n";
+
+    fn create_provider(source: &str, clock: DataFileClock, rate: f64) -> DataFileProvider {
+        let series = DataSeries::from_csv(source.as_bytes(), "time").unwrap();
+        let mut provider = DataFileProvider::new(
+            series,
+            &[],
+            clock,
+            DataFileInterpolation::Linear,
+            rate,
+            PlaybackConfig::default(),
+        )
+        .unwrap();
+        provider.set_name("data");
+
+        provider
+    }
+
+    fn get_level(provider: &mut DataFileProvider) -> f32 {
+        match provider.borrow("data_level").unwrap() {
+            Some(DataHolder::Float(level)) => *level,
+            value => panic!("Unexpected level: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn csv_rows_are_sorted_and_filled() {
+        let series = DataSeries::from_csv(
+            "time,level,position\n11,,3;4\n10,0,1;2\n12,2,\n".as_bytes(),
+            "time",
+        )
+        .unwrap();
+
+        assert_eq!(series.get_sample_count(), 3);
+        assert_eq!(series.get_duration(), 2.0);
+        assert_eq!(
+            series.get_values("level").unwrap(),
+            &[vec![0.0], vec![0.0], vec![2.0]]
+        );
+        assert_eq!(
+            series.get_values("position").unwrap(),
+            &[vec![1.0, 2.0], vec![3.0, 4.0], vec![3.0, 4.0]]
+        );
+        assert_eq!(
+            series.sample("position", 0.5, DataFileInterpolation::Linear),
+            Some(vec![2.0, 3.0])
+        );
+        assert_eq!(
+            series.sample("position", 0.5, DataFileInterpolation::Step),
+            Some(vec![1.0, 2.0])
+        );
+    }
+
+    #[test]
+    fn json_layouts_are_equivalent() {
+        let records = json!([
+            { "time": 0.0, "level": 1.0, "active": true },
+            { "time": 0.5, "level": [2.0] },
+        ]);
+        let columns = json!({
+            "time": [0.0, 0.5],
+            "level": [1.0, 2.0],
+            "active": [true, null],
+        });
+
+        assert_eq!(
+            DataSeries::from_json(&records, "time").unwrap(),
+            DataSeries::from_json(&columns, "time").unwrap()
+        );
+        assert!(DataSeries::from_json(&json!([{ "time": 0, "name": "a" }]), "time").is_err());
+        assert!(DataSeries::from_json(&json!([{ "level": 1 }]), "time").is_err());
+    }
+
+    #[test]
+    fn varying_widths_are_inferred_as_arrays() {
+        let mut provider = create_provider(
+            "time,values\n0,1;2\n1,1;2;3\n2,4\n",
+            DataFileClock::Time,
+            1.0,
+        );
+
+        assert_eq!(provider.describe()[0].data_type, Some(DataType::FloatArray));
+        provider.set_time(0.0, false);
+        assert_eq!(
+            provider.borrow("data_values").unwrap(),
+            Some(&DataHolder::FloatArray(vec![1.0, 2.0]))
+        );
+        provider.set_time(1.0, false);
+        assert_eq!(
+            provider.borrow("data_values").unwrap(),
+            Some(&DataHolder::FloatArray(vec![1.0, 2.0, 3.0]))
+        );
+    }
+
+    #[test]
+    fn declared_types_are_checked_on_every_sample() {
+        let series =
+            DataSeries::from_csv("time,values\n0,1;2\n1,1;2;3\n".as_bytes(), "time").unwrap();
+        let columns = [DataFileColumn {
+            name: "values".to_string(),
+            columns: vec!["values".to_string()],
+            data_type: Some(DataType::Float2),
+        }];
+
+        assert!(DataFileProvider::new(
+            series,
+            &columns,
+            DataFileClock::Time,
+            DataFileInterpolation::Step,
+            1.0,
+            PlaybackConfig::default(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rate_is_range_checked() {
+        let series = DataSeries::from_csv(RAMP_CSV.as_bytes(), "time").unwrap();
+        assert!(DataFileProvider::new(
+            series,
+            &[],
+            DataFileClock::Time,
+            DataFileInterpolation::Linear,
+            32.0,
+            PlaybackConfig::default(),
+        )
+        .is_err());
+
+        let mut provider = create_provider(RAMP_CSV, DataFileClock::Time, 1.0);
+        assert!(matches!(
+            provider.set_property("rate", &DataHolder::Float(20.0)),
+            Err(InputError::OutOfRange { .. })
+        ));
+        assert_eq!(provider.get_property("rate"), Ok(DataHolder::Float(1.0)));
+
+        provider
+            .set_property("rate", &DataHolder::Float(-2.0))
+            .unwrap();
+        assert_eq!(provider.get_property("rate"), Ok(DataHolder::Float(-2.0)));
+    }
+
+    #[test]
+    fn invalid_string_properties_are_not_fatal() {
+        let mut provider = create_provider(RAMP_CSV, DataFileClock::Time, 1.0);
+
+        for (property, value) in [("interpolation", "Cubic"), ("playback_mode", "Sideways")] {
+            let error = provider
+                .set_property(property, &DataHolder::String(value.to_string()))
+                .unwrap_err();
+            assert!(
+                matches!(error, InputError::InvalidValue { .. }),
+                "{}: {:?}",
+                property,
+                error
+            );
+            assert!(!error.is_fatal());
+        }
+        assert_eq!(
+            provider.get_property("interpolation"),
+            Ok(DataHolder::String("Linear".to_string()))
+        );
+        assert_eq!(
+            provider.get_property("playback_mode"),
+            Ok(DataHolder::String("Forward".to_string()))
+        );
+    }
+
+    #[test]
+    fn negative_rate_plays_backwards() {
+        let mut provider = create_provider(RAMP_CSV, DataFileClock::Time, -1.0);
+
+        provider.set_time(0.0, false);
+        assert_eq!(get_level(&mut provider), 0.0);
+        provider.set_time(1.0, false);
+        assert_eq!(get_level(&mut provider), 3.0);
+        provider.set_time(2.5, false);
+        assert_eq!(get_level(&mut provider), 1.5);
+
+        provider.set_looping(false).unwrap();
+        provider.set_time(5.0, false);
+        assert_eq!(get_level(&mut provider), 0.0);
+        assert_eq!(provider.get_position(), Some(0.0));
+    }
+
+    #[test]
+    fn negative_rate_wraps_ping_pong() {
+        let mut provider = create_provider(RAMP_CSV, DataFileClock::Time, -1.0);
+        provider
+            .set_property("playback_mode", &DataHolder::String("PingPong".to_string()))
+            .unwrap();
+
+        provider.set_time(0.0, false);
+        provider.set_time(1.0, false);
+        assert_eq!(get_level(&mut provider), 1.0);
+        provider.set_time(5.0, false);
+        assert_eq!(get_level(&mut provider), 3.0);
+    }
+
+    #[test]
+    fn speed_automation_follows_the_beat() {
+        let series = DataSeries::from_csv(RAMP_CSV.as_bytes(), "time").unwrap();
+        let playback = PlaybackConfig {
+            speed_automation: Automation::Lfo(Lfo {
+                lfo_type: LfoType::Square,
+                numerator: 1.0,
+                denominator: 1.0,
+                phase: 0.0,
+                amplitude: 1.0,
+                signed: false,
+            }),
+            ..PlaybackConfig::default()
+        };
+        let mut provider = DataFileProvider::new(
+            series,
+            &[],
+            DataFileClock::Time,
+            DataFileInterpolation::Linear,
+            1.0,
+            playback,
+        )
+        .unwrap();
+        provider.set_name("data");
+
+        // The square wave is high in the second half of each beat, doubling the rate
+        provider.set_beat(0.75, false);
+        provider.set_time(0.0, false);
+        provider.set_time(1.0, false);
+        assert_eq!(get_level(&mut provider), 2.0);
+
+        provider.set_beat(1.25, false);
+        provider.set_time(2.0, false);
+        assert_eq!(get_level(&mut provider), 3.0);
+    }
+
+    #[test]
+    fn cues_jump_on_the_quantized_beat() {
+        let series = DataSeries::from_csv(RAMP_CSV.as_bytes(), "time").unwrap();
+        let playback = PlaybackConfig {
+            cues: vec![CueMarker {
+                name: "drop".to_string(),
+                position: 3.0,
+                quantization: 1.0,
+            }],
+            ..PlaybackConfig::default()
+        };
+        let mut provider = DataFileProvider::new(
+            series,
+            &[],
+            DataFileClock::Time,
+            DataFileInterpolation::Linear,
+            1.0,
+            playback,
+        )
+        .unwrap();
+        provider.set_name("data");
+
+        provider.set_time(0.0, false);
+        provider.set_beat(0.25, false);
+        provider
+            .set_property("cue", &DataHolder::String("drop".to_string()))
+            .unwrap();
+        assert_eq!(
+            provider.get_property("cue"),
+            Ok(DataHolder::String("drop".to_string()))
+        );
+
+        provider.set_time(0.5, false);
+        provider.set_beat(0.75, false);
+        assert_eq!(get_level(&mut provider), 0.5);
+
+        provider.set_time(0.75, false);
+        provider.set_beat(1.0, false);
+        assert_eq!(provider.get_position(), Some(3.0));
+        assert_eq!(get_level(&mut provider), 3.0);
+        assert_eq!(
+            provider.get_property("cue"),
+            Ok(DataHolder::String(String::new()))
+        );
+
+        provider.set_time(1.25, false);
+        assert_eq!(get_level(&mut provider), 3.5);
+
+        assert!(matches!(
+            provider.set_property("cue", &DataHolder::String("missing".to_string())),
+            Err(InputError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn beat_clock_reports_position_in_beats() {
+        let mut provider = create_provider(RAMP_CSV, DataFileClock::Beat, 1.0);
+
+        provider.set_beat(0.0, false);
+        provider.set_time(10.0, false);
+        provider.set_beat(1.5, false);
+        assert_eq!(provider.get_position(), Some(1.5));
+        assert_eq!(provider.get_duration(), Some(4.0));
+        assert_eq!(get_level(&mut provider), 1.5);
+
+        assert!(matches!(
+            provider.seek_time(1.0),
+            Err(InputError::Unsupported(_))
+        ));
+        provider.seek_beat(3.0).unwrap();
+        assert_eq!(provider.get_position(), Some(3.0));
+        assert_eq!(get_level(&mut provider), 3.0);
+    }
+}
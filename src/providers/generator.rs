@@ -0,0 +1,742 @@
+use anyhow::Result;
+
+use crate::config::generator::{GeneratorOverlay, GeneratorPattern};
+use crate::types::{
+    DataHolder, DataRange, DataType, InputError, InputProvider, InputResult, PropertyDescription,
+    UniformDescription, UniformStore, UpdateFrequency,
+};
+
+const TEST_CARD_BARS: [[f32; 3]; 7] = [
+    [0.75, 0.75, 0.75],
+    [0.75, 0.75, 0.0],
+    [0.0, 0.75, 0.75],
+    [0.0, 0.75, 0.0],
+    [0.75, 0.0, 0.75],
+    [0.75, 0.0, 0.0],
+    [0.0, 0.0, 0.75],
+];
+
+const TEST_CARD_CASTELLATIONS: [[f32; 3]; 7] = [
+    [0.0, 0.0, 0.75],
+    [0.075, 0.075, 0.075],
+    [0.75, 0.0, 0.75],
+    [0.075, 0.075, 0.075],
+    [0.0, 0.75, 0.75],
+    [0.075, 0.075, 0.075],
+    [0.75, 0.75, 0.75],
+];
+
+const TEST_CARD_PLUGE: [[f32; 3]; 6] = [
+    [0.0, 0.129, 0.298],
+    [1.0, 1.0, 1.0],
+    [0.196, 0.0, 0.416],
+    [0.075, 0.075, 0.075],
+    [0.035, 0.035, 0.035],
+    [0.114, 0.114, 0.114],
+];
+
+// 3x5 glyphs for digits and the timecode separator, one byte per row
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+const DIGIT_GLYPHS: [[u8; GLYPH_HEIGHT]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+const SEPARATOR_GLYPH: [u8; GLYPH_HEIGHT] = [0b000, 0b010, 0b000, 0b010, 0b000];
+const DROP_FRAME_SEPARATOR_GLYPH: [u8; GLYPH_HEIGHT] = [0b000, 0b010, 0b000, 0b010, 0b100];
+
+fn hash(x: i32, y: i32, z: i32, seed: u32) -> u32 {
+    let mut value = seed
+        ^ (x as u32).wrapping_mul(0x8DA6_B343)
+        ^ (y as u32).wrapping_mul(0xD816_3841)
+        ^ (z as u32).wrapping_mul(0xCB1A_B31F);
+    value = (value ^ (value >> 16)).wrapping_mul(0x7FEB_352D);
+    value = (value ^ (value >> 15)).wrapping_mul(0x846C_A68B);
+    value ^ (value >> 16)
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn mix_color(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [
+        lerp(a[0], b[0], t),
+        lerp(a[1], b[1], t),
+        lerp(a[2], b[2], t),
+    ]
+}
+
+fn trilinear<F: Fn(i32, i32, i32, f32, f32, f32) -> f32>(x: f32, y: f32, z: f32, corner: F) -> f32 {
+    let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+    let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+    let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+
+    let mut layers = [0.0; 2];
+    for (dz, layer) in layers.iter_mut().enumerate() {
+        let mut rows = [0.0; 2];
+        for (dy, row) in rows.iter_mut().enumerate() {
+            let left = corner(
+                ix,
+                iy + dy as i32,
+                iz + dz as i32,
+                fx,
+                fy - dy as f32,
+                fz - dz as f32,
+            );
+            let right = corner(
+                ix + 1,
+                iy + dy as i32,
+                iz + dz as i32,
+                fx - 1.0,
+                fy - dy as f32,
+                fz - dz as f32,
+            );
+            *row = lerp(left, right, u);
+        }
+        *layer = lerp(rows[0], rows[1], v);
+    }
+
+    lerp(layers[0], layers[1], w)
+}
+
+pub fn value_noise(x: f32, y: f32, z: f32, seed: u32) -> f32 {
+    trilinear(x, y, z, |ix, iy, iz, _, _, _| {
+        hash(ix, iy, iz, seed) as f32 / u32::MAX as f32
+    })
+}
+
+pub fn perlin_noise(x: f32, y: f32, z: f32, seed: u32) -> f32 {
+    let value = trilinear(x, y, z, |ix, iy, iz, dx, dy, dz| {
+        // Pick one of the 12 cube edge directions, as in the reference implementation
+        match hash(ix, iy, iz, seed) % 12 {
+            0 => dx + dy,
+            1 => -dx + dy,
+            2 => dx - dy,
+            3 => -dx - dy,
+            4 => dx + dz,
+            5 => -dx + dz,
+            6 => dx - dz,
+            7 => -dx - dz,
+            8 => dy + dz,
+            9 => -dy + dz,
+            10 => dy - dz,
+            _ => -dy - dz,
+        }
+    });
+
+    (value * 0.5 + 0.5).clamp(0.0, 1.0)
+}
+
+pub fn render_pattern(
+    pattern: &GeneratorPattern,
+    width: usize,
+    height: usize,
+    time: f64,
+) -> Vec<u8> {
+    let mut data = Vec::with_capacity(width * height * 3);
+    let (width_f, height_f) = (width.max(1) as f32, height.max(1) as f32);
+
+    for y in 0..height {
+        for x in 0..width {
+            let (u, v) = ((x as f32 + 0.5) / width_f, (y as f32 + 0.5) / height_f);
+
+            let color = match *pattern {
+                GeneratorPattern::SolidColor { color } => color,
+                GeneratorPattern::LinearGradient { from, to, angle } => {
+                    let (sin, cos) = angle.to_radians().sin_cos();
+                    let extent = cos.abs() + sin.abs();
+                    let t = ((u - 0.5) * cos + (v - 0.5) * sin) / extent + 0.5;
+                    mix_color(from, to, t.clamp(0.0, 1.0))
+                }
+                GeneratorPattern::RadialGradient { inner, outer } => {
+                    let distance = ((u - 0.5).powi(2) + (v - 0.5).powi(2)).sqrt() / 0.5f32.sqrt();
+                    mix_color(inner, outer, distance.clamp(0.0, 1.0))
+                }
+                GeneratorPattern::Checkerboard {
+                    cell_size,
+                    even,
+                    odd,
+                } => {
+                    let cell_size = cell_size.max(1);
+                    if (x / cell_size + y / cell_size) % 2 == 0 {
+                        even
+                    } else {
+                        odd
+                    }
+                }
+                GeneratorPattern::TestCard => get_test_card_color(u, v),
+                GeneratorPattern::ValueNoise { scale, seed, speed } => {
+                    let value = value_noise(u * scale, v * scale, time as f32 * speed, seed);
+                    [value; 3]
+                }
+                GeneratorPattern::PerlinNoise {
+                    scale,
+                    seed,
+                    speed,
+                    octaves,
+                } => {
+                    let mut value = 0.0;
+                    let mut amplitude = 0.5;
+                    let mut frequency = scale;
+                    let mut total_amplitude = 0.0;
+                    for octave in 0..octaves.max(1) {
+                        value += amplitude
+                            * perlin_noise(
+                                u * frequency,
+                                v * frequency,
+                                time as f32 * speed,
+                                seed.wrapping_add(octave as u32),
+                            );
+                        total_amplitude += amplitude;
+                        amplitude *= 0.5;
+                        frequency *= 2.0;
+                    }
+                    [value / total_amplitude; 3]
+                }
+            };
+
+            data.extend(
+                color
+                    .iter()
+                    .map(|channel| (channel.clamp(0.0, 1.0) * 255.0) as u8),
+            );
+        }
+    }
+
+    data
+}
+
+fn get_test_card_color(u: f32, v: f32) -> [f32; 3] {
+    if v < 0.67 {
+        TEST_CARD_BARS[((u * 7.0) as usize).min(6)]
+    } else if v < 0.75 {
+        TEST_CARD_CASTELLATIONS[((u * 7.0) as usize).min(6)]
+    } else if u < 5.0 / 7.0 {
+        TEST_CARD_PLUGE[((u * 7.0 / 5.0 * 4.0) as usize).min(3)]
+    } else {
+        let step = ((u - 5.0 / 7.0) * 7.0 * 3.0) as usize;
+        TEST_CARD_PLUGE[3 + step.min(2)]
+    }
+}
+
+pub fn draw_digits(data: &mut [u8], width: usize, height: usize, text: &str) {
+    let scale = (height / 60).max(1);
+    let margin = 2 * scale;
+    let glyph_advance = (GLYPH_WIDTH + 1) * scale;
+    let box_width = (text.len() * glyph_advance + margin * 2).min(width);
+    let box_height = (GLYPH_HEIGHT * scale + margin * 2).min(height);
+
+    for y in 0..box_height {
+        for x in 0..box_width {
+            let offset = (y * width + x) * 3;
+            data[offset..offset + 3].copy_from_slice(&[0, 0, 0]);
+        }
+    }
+
+    for (index, character) in text.chars().enumerate() {
+        let glyph = match character {
+            '0'..='9' => DIGIT_GLYPHS[character as usize - '0' as usize],
+            ':' => SEPARATOR_GLYPH,
+            ';' => DROP_FRAME_SEPARATOR_GLYPH,
+            _ => continue,
+        };
+
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+
+                for sub_y in 0..scale {
+                    for sub_x in 0..scale {
+                        let x = margin + index * glyph_advance + column * scale + sub_x;
+                        let y = margin + row * scale + sub_y;
+                        if x < width && y < height {
+                            let offset = (y * width + x) * 3;
+                            data[offset..offset + 3].copy_from_slice(&[255, 255, 255]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn get_frame_index(time: f64, fps: f32) -> u64 {
+    (time.max(0.0) * fps.max(1.0) as f64).floor() as u64
+}
+
+// Frames are counted at the actual rate and labelled at the nominal one, 29.97 and 59.94 use
+// drop-frame labels so that the timecode keeps up with the wall clock
+pub fn format_timecode(time: f64, fps: f32) -> String {
+    let fps = fps.max(1.0) as f64;
+    let nominal_fps = fps.round();
+    let is_drop_frame = (nominal_fps == 30.0 || nominal_fps == 60.0)
+        && (fps - nominal_fps * 1000.0 / 1001.0).abs() < 0.01;
+
+    // Rates typed as 29.97 stand for the exact 30000/1001
+    let fps = if is_drop_frame {
+        nominal_fps * 1000.0 / 1001.0
+    } else {
+        fps
+    };
+    let frames_per_second = nominal_fps as u64;
+    let mut frame_index = (time.max(0.0) * fps).floor() as u64;
+
+    if is_drop_frame {
+        // Two labels per 30 frames are skipped each minute, except every tenth minute
+        let dropped_frames = frames_per_second / 15;
+        let frames_per_minute = frames_per_second * 60 - dropped_frames;
+        let frames_per_ten_minutes = frames_per_minute * 10 + dropped_frames;
+
+        let ten_minutes = frame_index / frames_per_ten_minutes;
+        let remainder = frame_index % frames_per_ten_minutes;
+        frame_index += dropped_frames * 9 * ten_minutes;
+        if remainder > dropped_frames {
+            frame_index += dropped_frames * ((remainder - dropped_frames) / frames_per_minute);
+        }
+    }
+
+    let frames = frame_index % frames_per_second;
+    let seconds = frame_index / frames_per_second;
+
+    format!(
+        "{:02}:{:02}:{:02}{}{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        if is_drop_frame { ';' } else { ':' },
+        frames
+    )
+}
+
+pub struct GeneratorProvider {
+    name: String,
+    width: usize,
+    height: usize,
+    pattern: GeneratorPattern,
+    overlay: GeneratorOverlay,
+
+    time: f64,
+    uniforms: UniformStore,
+}
+
+impl GeneratorProvider {
+    pub fn new(
+        width: usize,
+        height: usize,
+        pattern: GeneratorPattern,
+        overlay: GeneratorOverlay,
+    ) -> Result<Self> {
+        let mut provider = Self {
+            name: String::new(),
+            width,
+            height,
+            pattern,
+            overlay,
+
+            time: 0.0,
+            uniforms: UniformStore::default(),
+        };
+
+        // Configured values go through the same checks as the properties that change them
+        for description in provider.list_properties() {
+            description.check(&provider.get_property(&description.name)?)?;
+        }
+        provider.update();
+
+        Ok(provider)
+    }
+
+    // Frames that render the same as the previous one keep its generation
+    fn update(&mut self) {
+        let mut data = render_pattern(&self.pattern, self.width, self.height, self.time);
+        match self.overlay {
+            GeneratorOverlay::None => (),
+            GeneratorOverlay::FrameCounter { fps } => draw_digits(
+                &mut data,
+                self.width,
+                self.height,
+                &format!("{:06}", get_frame_index(self.time, fps)),
+            ),
+            GeneratorOverlay::Timecode { fps } => draw_digits(
+                &mut data,
+                self.width,
+                self.height,
+                &format_timecode(self.time, fps),
+            ),
+        }
+
+        self.uniforms.set_if_changed(
+            "texture",
+            DataHolder::Texture(((self.width as u32, self.height as u32), data)),
+        );
+    }
+
+    fn check_uniform(&self, uniform_name: &str) -> InputResult<()> {
+        if uniform_name != self.name {
+            return Err(InputError::UnknownUniform(uniform_name.to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+fn color_property(name: &str, description: &str) -> PropertyDescription {
+    PropertyDescription::new(name, DataType::Float3, DataRange::ColorRange, description)
+}
+
+impl InputProvider for GeneratorProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        vec![self.name.clone()]
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        let update_frequency =
+            if self.pattern.is_animated() || self.overlay != GeneratorOverlay::None {
+                UpdateFrequency::PerFrame
+            } else {
+                UpdateFrequency::OnChange
+            };
+
+        vec![UniformDescription::new(
+            &self.name,
+            DataType::Texture,
+            DataRange::None,
+            update_frequency,
+        )]
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        self.check_uniform(uniform_name)?;
+        Ok(self.uniforms.get_generation("texture"))
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        self.check_uniform(uniform_name)?;
+        Ok(self.uniforms.get("texture"))
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()> {
+        match self
+            .list_properties()
+            .iter()
+            .find(|description| description.name == property)
+        {
+            Some(description) => description.check(value)?,
+            None => return Err(InputError::UnknownProperty(property.to_string())),
+        }
+
+        match (&mut self.pattern, property, value) {
+            (GeneratorPattern::SolidColor { color }, "color", DataHolder::Float3(value))
+            | (
+                GeneratorPattern::LinearGradient { from: color, .. },
+                "from",
+                DataHolder::Float3(value),
+            )
+            | (
+                GeneratorPattern::LinearGradient { to: color, .. },
+                "to",
+                DataHolder::Float3(value),
+            )
+            | (
+                GeneratorPattern::RadialGradient { inner: color, .. },
+                "inner",
+                DataHolder::Float3(value),
+            )
+            | (
+                GeneratorPattern::RadialGradient { outer: color, .. },
+                "outer",
+                DataHolder::Float3(value),
+            )
+            | (
+                GeneratorPattern::Checkerboard { even: color, .. },
+                "even",
+                DataHolder::Float3(value),
+            )
+            | (
+                GeneratorPattern::Checkerboard { odd: color, .. },
+                "odd",
+                DataHolder::Float3(value),
+            ) => *color = *value,
+            (GeneratorPattern::LinearGradient { angle, .. }, "angle", DataHolder::Float(value)) => {
+                *angle = *value
+            }
+            (
+                GeneratorPattern::Checkerboard { cell_size, .. },
+                "cell_size",
+                DataHolder::Int(value),
+            ) => *cell_size = *value as usize,
+            (GeneratorPattern::ValueNoise { scale, .. }, "scale", DataHolder::Float(value))
+            | (GeneratorPattern::PerlinNoise { scale, .. }, "scale", DataHolder::Float(value)) => {
+                *scale = *value
+            }
+            (GeneratorPattern::ValueNoise { speed, .. }, "speed", DataHolder::Float(value))
+            | (GeneratorPattern::PerlinNoise { speed, .. }, "speed", DataHolder::Float(value)) => {
+                *speed = *value
+            }
+            (GeneratorPattern::ValueNoise { seed, .. }, "seed", DataHolder::Int(value))
+            | (GeneratorPattern::PerlinNoise { seed, .. }, "seed", DataHolder::Int(value)) => {
+                *seed = *value as u32
+            }
+            (GeneratorPattern::PerlinNoise { octaves, .. }, "octaves", DataHolder::Int(value)) => {
+                *octaves = *value as usize
+            }
+            _ => match (&mut self.overlay, property, value) {
+                (GeneratorOverlay::FrameCounter { fps }, "fps", DataHolder::Float(value))
+                | (GeneratorOverlay::Timecode { fps }, "fps", DataHolder::Float(value)) => {
+                    *fps = *value
+                }
+                _ => return Err(InputError::UnknownProperty(property.to_string())),
+            },
+        }
+
+        self.update();
+        Ok(())
+    }
+
+    fn list_properties(&self) -> Vec<PropertyDescription> {
+        let mut properties = match self.pattern {
+            GeneratorPattern::SolidColor { .. } => vec![color_property("color", "Fill color")],
+            GeneratorPattern::LinearGradient { .. } => vec![
+                color_property("from", "Gradient start color"),
+                color_property("to", "Gradient end color"),
+                PropertyDescription::new(
+                    "angle",
+                    DataType::Float,
+                    DataRange::FloatRange(0.0, 360.0, 1.0),
+                    "Gradient direction, in degrees",
+                ),
+            ],
+            GeneratorPattern::RadialGradient { .. } => vec![
+                color_property("inner", "Center color"),
+                color_property("outer", "Corner color"),
+            ],
+            GeneratorPattern::Checkerboard { .. } => vec![
+                PropertyDescription::new(
+                    "cell_size",
+                    DataType::Int,
+                    DataRange::IntRange(1, 512, 1),
+                    "Cell size, in pixels",
+                ),
+                color_property("even", "Even cell color"),
+                color_property("odd", "Odd cell color"),
+            ],
+            GeneratorPattern::TestCard => Vec::new(),
+            GeneratorPattern::ValueNoise { .. } | GeneratorPattern::PerlinNoise { .. } => vec![
+                PropertyDescription::new(
+                    "scale",
+                    DataType::Float,
+                    DataRange::FloatRange(0.1, 256.0, 0.1),
+                    "Noise lattice cells across the texture",
+                ),
+                PropertyDescription::new(
+                    "speed",
+                    DataType::Float,
+                    DataRange::FloatRange(0.0, 10.0, 0.01),
+                    "Noise evolution speed, in lattice cells per second",
+                ),
+                PropertyDescription::new(
+                    "seed",
+                    DataType::Int,
+                    DataRange::IntRange(0, i32::MAX as i64, 1),
+                    "Noise seed",
+                ),
+            ],
+        };
+
+        if let GeneratorPattern::PerlinNoise { .. } = self.pattern {
+            properties.push(PropertyDescription::new(
+                "octaves",
+                DataType::Int,
+                DataRange::IntRange(1, 8, 1),
+                "Number of summed noise octaves",
+            ));
+        }
+
+        if let GeneratorOverlay::FrameCounter { .. } | GeneratorOverlay::Timecode { .. } =
+            self.overlay
+        {
+            properties.push(PropertyDescription::new(
+                "fps",
+                DataType::Float,
+                DataRange::FloatRange(1.0, 120.0, 0.01),
+                "Overlay frame rate",
+            ));
+        }
+
+        properties
+    }
+
+    fn get_property(&self, property: &str) -> InputResult<DataHolder> {
+        let value = match (self.pattern, property) {
+            (GeneratorPattern::SolidColor { color }, "color")
+            | (GeneratorPattern::LinearGradient { from: color, .. }, "from")
+            | (GeneratorPattern::LinearGradient { to: color, .. }, "to")
+            | (GeneratorPattern::RadialGradient { inner: color, .. }, "inner")
+            | (GeneratorPattern::RadialGradient { outer: color, .. }, "outer")
+            | (GeneratorPattern::Checkerboard { even: color, .. }, "even")
+            | (GeneratorPattern::Checkerboard { odd: color, .. }, "odd") => {
+                DataHolder::Float3(color)
+            }
+            (GeneratorPattern::LinearGradient { angle, .. }, "angle") => DataHolder::Float(angle),
+            (GeneratorPattern::Checkerboard { cell_size, .. }, "cell_size") => {
+                DataHolder::Int(cell_size as i32)
+            }
+            (GeneratorPattern::ValueNoise { scale, .. }, "scale")
+            | (GeneratorPattern::PerlinNoise { scale, .. }, "scale") => DataHolder::Float(scale),
+            (GeneratorPattern::ValueNoise { speed, .. }, "speed")
+            | (GeneratorPattern::PerlinNoise { speed, .. }, "speed") => DataHolder::Float(speed),
+            (GeneratorPattern::ValueNoise { seed, .. }, "seed")
+            | (GeneratorPattern::PerlinNoise { seed, .. }, "seed") => DataHolder::Int(seed as i32),
+            (GeneratorPattern::PerlinNoise { octaves, .. }, "octaves") => {
+                DataHolder::Int(octaves as i32)
+            }
+            _ => match (self.overlay, property) {
+                (GeneratorOverlay::FrameCounter { fps }, "fps")
+                | (GeneratorOverlay::Timecode { fps }, "fps") => DataHolder::Float(fps),
+                _ => return Err(InputError::UnknownProperty(property.to_string())),
+            },
+        };
+
+        Ok(value)
+    }
+
+    fn set_time(&mut self, time: f64, _sync: bool) {
+        self.time = time;
+
+        if self.pattern.is_animated() || self.overlay != GeneratorOverlay::None {
+            self.update();
+        }
+    }
+
+    fn stop(&mut self) -> InputResult<()> {
+        self.time = 0.0;
+        self.update();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DROP_FRAME_FPS: f64 = 30_000.0 / 1001.0;
+
+    // Samples the middle of a frame so that rounding never lands on its neighbour
+    fn get_drop_frame_timecode(frame_index: u64) -> String {
+        format_timecode((frame_index as f64 + 0.5) / DROP_FRAME_FPS, 29.97)
+    }
+
+    fn create_noise() -> GeneratorProvider {
+        let pattern = GeneratorPattern::ValueNoise {
+            scale: 4.0,
+            seed: 7,
+            speed: 0.0,
+        };
+        let mut provider = GeneratorProvider::new(8, 8, pattern, GeneratorOverlay::None).unwrap();
+        provider.set_name("noise");
+
+        provider
+    }
+
+    #[test]
+    fn drop_frame_timecode_skips_labels_each_minute() {
+        assert_eq!(get_drop_frame_timecode(0), "00:00:00;00");
+        assert_eq!(get_drop_frame_timecode(1799), "00:00:59;29");
+        assert_eq!(get_drop_frame_timecode(1800), "00:01:00;02");
+        assert_eq!(get_drop_frame_timecode(1801), "00:01:00;03");
+        assert_eq!(get_drop_frame_timecode(3597), "00:01:59;29");
+        assert_eq!(get_drop_frame_timecode(3598), "00:02:00;02");
+
+        // Every tenth minute keeps all of its labels
+        assert_eq!(get_drop_frame_timecode(17981), "00:09:59;29");
+        assert_eq!(get_drop_frame_timecode(17982), "00:10:00;00");
+        assert_eq!(get_drop_frame_timecode(17983), "00:10:00;01");
+
+        // An hour of drop-frame labels matches an hour of wall clock, to the frame
+        assert_eq!(get_drop_frame_timecode(107_892), "01:00:00;00");
+    }
+
+    #[test]
+    fn integer_rates_use_plain_timecode() {
+        assert_eq!(format_timecode(59.99, 30.0), "00:00:59:29");
+        assert_eq!(format_timecode(60.0, 30.0), "00:01:00:00");
+        assert_eq!(format_timecode(3661.5, 25.0), "01:01:01:12");
+        assert_eq!(get_frame_index(2.0, 24.0), 48);
+        assert_eq!(get_frame_index(-1.0, 24.0), 0);
+    }
+
+    #[test]
+    fn properties_are_range_checked() {
+        let mut provider = create_noise();
+
+        for (property, value) in [
+            ("seed", DataHolder::Int(-1)),
+            ("scale", DataHolder::Float(0.0)),
+            ("speed", DataHolder::Float(f32::INFINITY)),
+        ] {
+            assert!(
+                matches!(
+                    provider.set_property(property, &value),
+                    Err(InputError::OutOfRange { .. })
+                ),
+                "{} {:?}",
+                property,
+                value
+            );
+        }
+        assert_eq!(provider.get_property("seed"), Ok(DataHolder::Int(7)));
+        assert_eq!(
+            provider.set_property("octaves", &DataHolder::Int(2)),
+            Err(InputError::UnknownProperty("octaves".to_string()))
+        );
+
+        let pattern = GeneratorPattern::ValueNoise {
+            scale: 4.0,
+            seed: u32::MAX,
+            speed: 0.0,
+        };
+        assert!(GeneratorProvider::new(8, 8, pattern, GeneratorOverlay::None).is_err());
+    }
+
+    #[test]
+    fn generations_follow_rendered_changes() {
+        let mut provider = create_noise();
+        let generation = provider.get_generation("noise").unwrap();
+        assert!(generation > 0);
+
+        // Static patterns keep their texture, and so their generation, across frames
+        provider.set_time(1.0, true);
+        assert_eq!(provider.get_generation("noise").unwrap(), generation);
+        provider.set_property("seed", &DataHolder::Int(7)).unwrap();
+        assert_eq!(provider.get_generation("noise").unwrap(), generation);
+
+        provider.set_property("seed", &DataHolder::Int(8)).unwrap();
+        let changed_generation = provider.get_generation("noise").unwrap();
+        assert!(changed_generation > generation);
+        provider.borrow("noise").unwrap();
+        assert_eq!(
+            provider.get_generation("noise").unwrap(),
+            changed_generation
+        );
+    }
+}
@@ -0,0 +1,788 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use anyhow::{bail, Context, Result};
+use image::imageops::FilterType;
+
+use crate::types::{
+    ArmedCue, DataHolder, DataRange, DataType, InputError, InputProvider, InputResult,
+    PlaybackConfig, PlaybackMode, PropertyDescription, Speed, UniformDescription, UniformStore,
+    UpdateFrequency,
+};
+
+const SEQUENCE_EXTENSIONS: [&str; 3] = ["png", "jpg", "jpeg"];
+
+fn get_sort_key(path: &Path) -> (String, u64) {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // Trimming keeps the split on a character boundary, whatever precedes the digits
+    let prefix = stem.trim_end_matches(|character: char| character.is_ascii_digit());
+
+    let number = stem[prefix.len()..].parse().unwrap_or_default();
+    (prefix.to_string(), number)
+}
+
+pub fn list_sequence_frames(path: &str) -> Result<Vec<PathBuf>> {
+    let mut frames: Vec<PathBuf> = if Path::new(path).is_dir() {
+        Path::new(path)
+            .read_dir()
+            .with_context(|| format!("Failed to list image sequence directory: {}", path))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .map(|extension| {
+                        let extension = extension.to_string_lossy().to_lowercase();
+                        SEQUENCE_EXTENSIONS.contains(&extension.as_str())
+                    })
+                    .unwrap_or(false)
+            })
+            .collect()
+    } else {
+        glob::glob(path)
+            .with_context(|| format!("Invalid image sequence pattern: {}", path))?
+            .filter_map(|entry| entry.ok())
+            .collect()
+    };
+
+    if frames.is_empty() {
+        bail!("No frames found for image sequence: {}", path);
+    }
+
+    frames.sort_by_cached_key(|path| get_sort_key(path));
+    Ok(frames)
+}
+
+pub fn load_texture(path: &Path, width: usize, height: usize) -> Result<DataHolder> {
+    let image = image::open(path).with_context(|| format!("Failed to decode image: {:?}", path))?;
+    let image = if image.width() as usize != width || image.height() as usize != height {
+        image.resize_exact(width as u32, height as u32, FilterType::Triangle)
+    } else {
+        image
+    };
+
+    Ok(DataHolder::Texture((
+        (width as u32, height as u32),
+        image.to_rgb8().into_raw(),
+    )))
+}
+
+fn get_speed_description() -> PropertyDescription {
+    PropertyDescription::new(
+        "speed",
+        DataType::Float,
+        DataRange::FloatRange(0.01, 240.0, 0.01),
+        "Frames per second, or per beat for beat-synced sequences",
+    )
+}
+
+fn get_preload_description() -> PropertyDescription {
+    PropertyDescription::new(
+        "preload",
+        DataType::Int,
+        DataRange::IntRange(0, 256, 1),
+        "Number of frames decoded ahead of the playhead",
+    )
+}
+
+type DecodedFrame = (usize, std::result::Result<DataHolder, String>);
+
+pub struct ImageSequenceProvider {
+    name: String,
+    frames: Arc<Vec<PathBuf>>,
+    width: usize,
+    height: usize,
+    speed: Speed,
+    playback: PlaybackConfig,
+    preload: usize,
+
+    cache: HashMap<usize, DataHolder>,
+    requested: HashSet<usize>,
+    request_tx: Option<Sender<usize>>,
+    frame_rx: Receiver<DecodedFrame>,
+    worker: Option<JoinHandle<()>>,
+
+    clock: Option<f64>,
+    beat: f64,
+    cursor: f64,
+    playing: bool,
+    armed_cue: Option<ArmedCue>,
+    current_frame: Option<usize>,
+    error: Option<InputError>,
+    uniforms: UniformStore,
+}
+
+impl ImageSequenceProvider {
+    pub fn new(
+        path: &str,
+        width: usize,
+        height: usize,
+        speed: Speed,
+        playback: PlaybackConfig,
+        preload: usize,
+    ) -> Result<Self> {
+        match speed {
+            Speed::Fps(rate) | Speed::Fpb(rate) => {
+                get_speed_description().check(&DataHolder::Float(rate))?
+            }
+        }
+        get_preload_description().check(&DataHolder::Int(preload.min(i32::MAX as usize) as i32))?;
+
+        let frames = Arc::new(list_sequence_frames(path)?);
+
+        let (request_tx, request_rx) = channel::<usize>();
+        let (frame_tx, frame_rx) = channel();
+        let worker_frames = frames.clone();
+        let worker = thread::spawn(move || {
+            for index in request_rx {
+                let frame = load_texture(&worker_frames[index], width, height)
+                    .map_err(|e| format!("{:#}", e));
+                if frame_tx.send((index, frame)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut provider = Self {
+            name: String::new(),
+            frames,
+            width,
+            height,
+            speed,
+            playback,
+            preload,
+
+            cache: HashMap::new(),
+            requested: HashSet::new(),
+            request_tx: Some(request_tx),
+            frame_rx,
+            worker: Some(worker),
+
+            clock: None,
+            beat: 0.0,
+            cursor: 0.0,
+            playing: true,
+            armed_cue: None,
+            current_frame: None,
+            error: None,
+            uniforms: UniformStore::default(),
+        };
+        provider.update_frame();
+
+        Ok(provider)
+    }
+
+    pub fn get_frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    // Playback positions are expressed in seconds for Fps sequences and in beats for Fpb ones
+    fn get_base_rate(&self) -> f64 {
+        match self.speed {
+            Speed::Fps(rate) | Speed::Fpb(rate) => rate as f64,
+        }
+    }
+
+    fn get_length(&self) -> f64 {
+        self.frames.len() as f64 / self.get_base_rate()
+    }
+
+    fn get_position_units(&self) -> f64 {
+        self.playback
+            .resolve_position(self.cursor / self.get_base_rate(), self.get_length())
+    }
+
+    // Speed automation is keyed by beat whichever clock drives the sequence
+    fn advance(&mut self, clock: f64) {
+        if let Some(last_clock) = self.clock {
+            if self.playing {
+                let speed = match self.playback.get_speed(self.speed, self.beat) {
+                    Speed::Fps(rate) | Speed::Fpb(rate) => rate as f64,
+                };
+                self.cursor += (clock - last_clock) * speed;
+            }
+        }
+
+        self.clock = Some(clock);
+        self.update_frame();
+    }
+
+    // The jump happens on the first beat update at or past the cue quantization
+    pub fn trigger_cue(&mut self, name: &str) -> InputResult<()> {
+        self.armed_cue = Some(self.playback.arm_cue(name, self.beat)?);
+        self.check_cue();
+        Ok(())
+    }
+
+    fn check_cue(&mut self) {
+        if self
+            .armed_cue
+            .as_ref()
+            .is_some_and(|armed_cue| armed_cue.is_due(self.beat))
+        {
+            if let Some(armed_cue) = self.armed_cue.take() {
+                self.seek_units(armed_cue.position);
+            }
+        }
+    }
+
+    fn seek_units(&mut self, position: f64) {
+        let (start, end) = self.playback.get_bounds(self.get_length());
+        let offset = match self.playback.mode {
+            PlaybackMode::Reverse => end - position,
+            PlaybackMode::Forward | PlaybackMode::PingPong => position - start,
+        };
+
+        self.cursor = (offset - self.playback.start_offset) * self.get_base_rate();
+        self.update_frame();
+    }
+
+    fn update_frame(&mut self) {
+        let frame_count = self.frames.len();
+        let frame =
+            ((self.get_position_units() * self.get_base_rate()) as usize).min(frame_count - 1);
+
+        while let Ok((index, decoded_frame)) = self.frame_rx.try_recv() {
+            self.requested.remove(&index);
+            match decoded_frame {
+                Ok(decoded_frame) => {
+                    self.cache.insert(index, decoded_frame);
+                }
+                Err(error) => self.error = Some(InputError::Other(error)),
+            }
+        }
+
+        if self.current_frame != Some(frame) {
+            let texture = match self.cache.get(&frame) {
+                Some(texture) => Ok(texture.clone()),
+                None => load_texture(&self.frames[frame], self.width, self.height),
+            };
+
+            match texture {
+                Ok(texture) => {
+                    self.uniforms.set("texture", texture);
+                    self.current_frame = Some(frame);
+                }
+                Err(error) => self.error = Some(error.into()),
+            }
+        }
+
+        self.preload_around(frame);
+    }
+
+    fn preload_around(&mut self, frame: usize) {
+        let frame_count = self.frames.len() as isize;
+        let preload = self.preload as isize;
+        let window: Vec<usize> = match self.playback.mode {
+            PlaybackMode::Forward => (1..=preload).collect::<Vec<isize>>(),
+            PlaybackMode::Reverse => (1..=preload).map(|offset| -offset).collect(),
+            PlaybackMode::PingPong => (-preload / 2..=preload / 2).collect(),
+        }
+        .into_iter()
+        .map(|offset| (frame as isize + offset).rem_euclid(frame_count) as usize)
+        .collect();
+
+        self.cache
+            .retain(|index, _| *index == frame || window.contains(index));
+
+        if let Some(request_tx) = &self.request_tx {
+            for index in window {
+                if !self.cache.contains_key(&index) && self.requested.insert(index) {
+                    let _ = request_tx.send(index);
+                }
+            }
+        }
+    }
+
+    fn check_uniform(&self, uniform_name: &str) -> InputResult<()> {
+        if uniform_name != self.name {
+            return Err(InputError::UnknownUniform(uniform_name.to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+impl InputProvider for ImageSequenceProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        vec![self.name.clone()]
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        vec![UniformDescription::new(
+            &self.name,
+            DataType::Texture,
+            DataRange::None,
+            UpdateFrequency::Rate(self.get_base_rate() as f32),
+        )]
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        self.check_uniform(uniform_name)?;
+        Ok(self.uniforms.get_generation("texture"))
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        self.check_uniform(uniform_name)?;
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        Ok(self.uniforms.get("texture"))
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()> {
+        match self
+            .list_properties()
+            .iter()
+            .find(|description| description.name == property)
+        {
+            Some(description) => description.check(value)?,
+            None => return Err(InputError::UnknownProperty(property.to_string())),
+        }
+
+        match (property, value) {
+            ("speed", DataHolder::Float(rate)) => {
+                self.speed = match self.speed {
+                    Speed::Fps(_) => Speed::Fps(*rate),
+                    Speed::Fpb(_) => Speed::Fpb(*rate),
+                };
+            }
+            ("preload", DataHolder::Int(preload)) => self.preload = *preload as usize,
+            ("cue", DataHolder::String(name)) => self.trigger_cue(name)?,
+            _ => self.playback.set_property(property, value)?,
+        }
+
+        self.update_frame();
+        Ok(())
+    }
+
+    fn list_properties(&self) -> Vec<PropertyDescription> {
+        let mut properties = vec![get_speed_description(), get_preload_description()];
+        properties.extend(PlaybackConfig::list_properties());
+
+        properties
+    }
+
+    fn get_property(&self, property: &str) -> InputResult<DataHolder> {
+        match property {
+            "speed" => match self.speed {
+                Speed::Fps(rate) | Speed::Fpb(rate) => Ok(DataHolder::Float(rate)),
+            },
+            "preload" => Ok(DataHolder::Int(self.preload as i32)),
+            "cue" => Ok(DataHolder::String(
+                self.armed_cue
+                    .as_ref()
+                    .map(|armed_cue| armed_cue.name.clone())
+                    .unwrap_or_default(),
+            )),
+            _ => self
+                .playback
+                .get_properties()
+                .into_iter()
+                .find(|(name, _)| name == property)
+                .map(|(_, value)| value)
+                .ok_or_else(|| InputError::UnknownProperty(property.to_string())),
+        }
+    }
+
+    fn set_beat(&mut self, beat: f64, _sync: bool) {
+        self.beat = beat;
+        if let Speed::Fpb(_) = self.speed {
+            self.advance(beat);
+        }
+        self.check_cue();
+    }
+
+    fn set_time(&mut self, time: f64, _sync: bool) {
+        if let Speed::Fps(_) = self.speed {
+            self.advance(time);
+        }
+    }
+
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn seek_time(&mut self, time: f64) -> InputResult<()> {
+        if let Speed::Fps(_) = self.speed {
+            self.seek_units(time);
+            Ok(())
+        } else {
+            Err(InputError::Unsupported(
+                "seek_time on a beat clocked image sequence".to_string(),
+            ))
+        }
+    }
+
+    fn seek_beat(&mut self, beat: f64) -> InputResult<()> {
+        if let Speed::Fpb(_) = self.speed {
+            self.seek_units(beat);
+            Ok(())
+        } else {
+            Err(InputError::Unsupported(
+                "seek_beat on a time clocked image sequence".to_string(),
+            ))
+        }
+    }
+
+    fn get_duration(&self) -> Option<f64> {
+        match self.speed {
+            Speed::Fps(_) => Some(self.get_length()),
+            Speed::Fpb(_) => None,
+        }
+    }
+
+    fn get_position(&self) -> Option<f64> {
+        match self.speed {
+            Speed::Fps(_) => Some(self.get_position_units()),
+            Speed::Fpb(_) => None,
+        }
+    }
+
+    fn is_looping(&self) -> bool {
+        self.playback.looping
+    }
+
+    fn set_looping(&mut self, looping: bool) -> InputResult<()> {
+        self.playback.looping = looping;
+        self.update_frame();
+        Ok(())
+    }
+
+    fn stop(&mut self) -> InputResult<()> {
+        self.playing = false;
+        self.cursor = 0.0;
+        self.update_frame();
+        Ok(())
+    }
+
+    fn pause(&mut self) -> InputResult<()> {
+        self.playing = false;
+        Ok(())
+    }
+
+    fn play(&mut self) -> InputResult<()> {
+        self.playing = true;
+        Ok(())
+    }
+}
+
+impl Drop for ImageSequenceProvider {
+    fn drop(&mut self) {
+        // The worker only leaves its receive loop once the last sender is gone
+        drop(self.request_tx.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+    use crate::types::{Automation, CueMarker, Lfo, LfoType};
+
+    fn create_sequence(directory_name: &str, file_names: &[&str]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("wvr-{}-{}", directory_name, std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        for (index, file_name) in file_names.iter().enumerate() {
+            RgbImage::from_pixel(1, 1, Rgb([index as u8, 0, 0]))
+                .save(directory.join(file_name))
+                .unwrap();
+        }
+
+        directory
+    }
+
+    fn get_red(provider: &mut ImageSequenceProvider) -> u8 {
+        match provider.borrow("sequence").unwrap() {
+            Some(DataHolder::Texture((_, data))) => data[0],
+            value => panic!("Unexpected frame: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn frames_sort_by_their_number() {
+        let mut paths: Vec<PathBuf> = ["frame10.png", "frame9.png", "frame100.png", "frame1.png"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        paths.sort_by_cached_key(|path| get_sort_key(path));
+        assert_eq!(
+            paths,
+            ["frame1.png", "frame9.png", "frame10.png", "frame100.png"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<PathBuf>>()
+        );
+
+        assert_eq!(
+            get_sort_key(Path::new("été_07.png")),
+            ("été_".to_string(), 7)
+        );
+        assert_eq!(
+            get_sort_key(Path::new("cover.png")),
+            ("cover".to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn sequences_play_in_numeric_order() {
+        let directory = create_sequence("sequence-order", &["f2.png", "f10.png", "f1.png"]);
+        let mut provider = ImageSequenceProvider::new(
+            directory.to_str().unwrap(),
+            1,
+            1,
+            Speed::Fps(1.0),
+            PlaybackConfig::default(),
+            0,
+        )
+        .unwrap();
+        provider.set_name("sequence");
+
+        // Red channels hold the index of the file in creation order
+        let mut reds = Vec::new();
+        for time in [0.0, 1.0, 2.0] {
+            provider.set_time(time, true);
+            reds.push(get_red(&mut provider));
+        }
+        assert_eq!(reds, [2, 0, 1]);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn speed_and_preload_are_range_checked() {
+        let directory = create_sequence("sequence-properties", &["f1.png", "f2.png"]);
+        let path = directory.to_str().unwrap();
+        let mut provider =
+            ImageSequenceProvider::new(path, 1, 1, Speed::Fps(2.0), PlaybackConfig::default(), 4)
+                .unwrap();
+
+        for (property, value) in [
+            ("speed", DataHolder::Float(0.0)),
+            ("speed", DataHolder::Float(-1.0)),
+            ("speed", DataHolder::Float(f32::NAN)),
+            ("preload", DataHolder::Int(-1)),
+            ("preload", DataHolder::Int(100_000)),
+        ] {
+            assert!(
+                matches!(
+                    provider.set_property(property, &value),
+                    Err(InputError::OutOfRange { .. })
+                ),
+                "{} {:?}",
+                property,
+                value
+            );
+        }
+        assert!(matches!(
+            provider.set_property("speed", &DataHolder::Int(2)),
+            Err(InputError::WrongValueType { .. })
+        ));
+        assert_eq!(provider.get_property("speed"), Ok(DataHolder::Float(2.0)));
+        assert_eq!(provider.get_duration(), Some(1.0));
+
+        provider
+            .set_property("speed", &DataHolder::Float(4.0))
+            .unwrap();
+        assert_eq!(provider.get_duration(), Some(0.5));
+
+        for speed in [Speed::Fps(0.0), Speed::Fpb(-2.0)] {
+            assert!(
+                ImageSequenceProvider::new(path, 1, 1, speed, PlaybackConfig::default(), 4)
+                    .is_err()
+            );
+        }
+
+        drop(provider);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    fn create_provider(
+        directory: &Path,
+        speed: Speed,
+        playback: PlaybackConfig,
+        preload: usize,
+    ) -> ImageSequenceProvider {
+        let mut provider =
+            ImageSequenceProvider::new(directory.to_str().unwrap(), 1, 1, speed, playback, preload)
+                .unwrap();
+        provider.set_name("sequence");
+
+        provider
+    }
+
+    fn get_reds(provider: &mut ImageSequenceProvider, times: &[f64]) -> Vec<u8> {
+        times
+            .iter()
+            .map(|time| {
+                provider.set_time(*time, true);
+                get_red(provider)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reverse_and_ping_pong_playback() {
+        let directory =
+            create_sequence("sequence-modes", &["f0.png", "f1.png", "f2.png", "f3.png"]);
+        // Playback starts on the first update, frames are then sampled halfway through
+        let times: Vec<f64> = std::iter::once(0.0)
+            .chain((0..9).map(|index| index as f64 + 0.5))
+            .collect();
+
+        let playback = PlaybackConfig {
+            mode: PlaybackMode::Reverse,
+            ..PlaybackConfig::default()
+        };
+        let mut provider = create_provider(&directory, Speed::Fps(1.0), playback, 0);
+        assert_eq!(
+            get_reds(&mut provider, &times),
+            [3, 3, 2, 1, 0, 3, 2, 1, 0, 3]
+        );
+
+        let playback = PlaybackConfig {
+            mode: PlaybackMode::PingPong,
+            ..PlaybackConfig::default()
+        };
+        let mut provider = create_provider(&directory, Speed::Fps(1.0), playback, 0);
+        assert_eq!(
+            get_reds(&mut provider, &times),
+            [0, 0, 1, 2, 3, 3, 2, 1, 0, 0]
+        );
+
+        let playback = PlaybackConfig {
+            mode: PlaybackMode::Reverse,
+            looping: false,
+            ..PlaybackConfig::default()
+        };
+        let mut provider = create_provider(&directory, Speed::Fps(1.0), playback, 0);
+        assert_eq!(
+            get_reds(&mut provider, &times),
+            [3, 3, 2, 1, 0, 0, 0, 0, 0, 0]
+        );
+
+        drop(provider);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn cues_jump_on_their_quantized_beat() {
+        let directory = create_sequence("sequence-cues", &["f0.png", "f1.png", "f2.png", "f3.png"]);
+        let playback = PlaybackConfig {
+            cues: vec![CueMarker {
+                name: "end".to_string(),
+                position: 3.0,
+                quantization: 1.0,
+            }],
+            ..PlaybackConfig::default()
+        };
+        let mut provider = create_provider(&directory, Speed::Fps(1.0), playback, 0);
+
+        provider.set_beat(0.5, true);
+        provider
+            .set_property("cue", &DataHolder::String("end".to_string()))
+            .unwrap();
+        assert_eq!(
+            provider.get_property("cue"),
+            Ok(DataHolder::String("end".to_string()))
+        );
+        assert_eq!(get_red(&mut provider), 0);
+
+        provider.set_beat(1.0, true);
+        assert_eq!(get_red(&mut provider), 3);
+        assert_eq!(
+            provider.get_property("cue"),
+            Ok(DataHolder::String(String::new()))
+        );
+        assert!(matches!(
+            provider.set_property("cue", &DataHolder::String("start".to_string())),
+            Err(InputError::InvalidValue { .. })
+        ));
+
+        drop(provider);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn speed_automation_follows_the_beat() {
+        let directory = create_sequence(
+            "sequence-automation",
+            &["f0.png", "f1.png", "f2.png", "f3.png", "f4.png"],
+        );
+        // Doubles the speed during the second half of every beat
+        let playback = PlaybackConfig {
+            looping: false,
+            speed_automation: Automation::Lfo(Lfo {
+                lfo_type: LfoType::Square,
+                numerator: 1.0,
+                denominator: 1.0,
+                phase: 0.0,
+                amplitude: 1.0,
+                signed: false,
+            }),
+            ..PlaybackConfig::default()
+        };
+        let mut provider = create_provider(&directory, Speed::Fps(1.0), playback, 0);
+
+        provider.set_beat(0.0, true);
+        assert_eq!(get_reds(&mut provider, &[0.0, 1.0]), [0, 1]);
+
+        // Wall time lands on whole seconds, only the beat enters the second half
+        provider.set_beat(0.5, true);
+        assert_eq!(get_reds(&mut provider, &[2.0]), [3]);
+
+        drop(provider);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn preloaded_frames_come_from_the_cache() {
+        let directory = create_sequence(
+            "sequence-preload",
+            &["f0.png", "f1.png", "f2.png", "f3.png"],
+        );
+        let mut provider =
+            create_provider(&directory, Speed::Fps(1.0), PlaybackConfig::default(), 2);
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !provider.requested.is_empty() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "Frames were not preloaded"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            provider.update_frame();
+        }
+        let mut cached: Vec<usize> = provider.cache.keys().copied().collect();
+        cached.sort_unstable();
+        assert_eq!(cached, [1, 2]);
+
+        // Only the decoded copy is left for the next frame
+        std::fs::remove_file(directory.join("f1.png")).unwrap();
+        assert_eq!(get_reds(&mut provider, &[0.0, 1.0]), [0, 1]);
+        assert!(provider.cache.contains_key(&2));
+        assert!(provider.requested.contains(&3) || provider.cache.contains_key(&3));
+
+        provider
+            .set_property("preload", &DataHolder::Int(0))
+            .unwrap();
+        assert!(provider.cache.keys().all(|index| *index == 1));
+
+        drop(provider);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,690 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::protocol::ipc::{get_ipc_timestamp, IpcFormat, IpcMessage, IpcReader};
+use crate::types::{
+    DataHolder, DataRange, DataType, InputError, InputProvider, InputResult, UniformDescription,
+    UniformStore, UpdateFrequency,
+};
+
+use self::transport::{connect, IpcClientStream, IpcListener, IpcStream};
+
+const READ_BUFFER_SIZE: usize = 65_536;
+const MAX_QUEUED_ERRORS: usize = 64;
+
+#[cfg(unix)]
+mod transport {
+    use std::fs;
+    use std::io::{self, ErrorKind};
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+
+    use anyhow::{bail, Context, Result};
+
+    pub type IpcStream = UnixStream;
+    pub type IpcClientStream = UnixStream;
+
+    pub fn connect(path: &Path) -> io::Result<IpcClientStream> {
+        UnixStream::connect(path)
+    }
+
+    pub struct IpcListener {
+        path: PathBuf,
+        listener: UnixListener,
+    }
+
+    impl IpcListener {
+        pub fn bind(path: &Path) -> Result<Self> {
+            if let Ok(metadata) = fs::symlink_metadata(path) {
+                if !metadata.file_type().is_socket() {
+                    bail!("IPC path {} exists and is not a socket", path.display());
+                }
+
+                // Only a socket nobody listens on anymore is left over from a previous run
+                match UnixStream::connect(path) {
+                    Ok(_) => bail!("IPC socket {} is already in use", path.display()),
+                    Err(e) if e.kind() == ErrorKind::ConnectionRefused => fs::remove_file(path)
+                        .with_context(|| {
+                            format!("Failed to remove stale socket {}", path.display())
+                        })?,
+                    Err(e) => {
+                        return Err(e).with_context(|| {
+                            format!("Failed to check IPC socket {}", path.display())
+                        })
+                    }
+                }
+            }
+
+            let listener = UnixListener::bind(path)
+                .with_context(|| format!("Failed to bind IPC socket {}", path.display()))?;
+            listener
+                .set_nonblocking(true)
+                .context("Failed to configure IPC socket")?;
+
+            Ok(Self {
+                path: path.to_path_buf(),
+                listener,
+            })
+        }
+
+        pub fn accept(&mut self) -> io::Result<Option<IpcStream>> {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    Ok(Some(stream))
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    impl Drop for IpcListener {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod transport {
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, ErrorKind, Read};
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+    use std::path::{Path, PathBuf};
+    use std::ptr;
+
+    use anyhow::{bail, Context, Result};
+    use windows_sys::Win32::Foundation::{
+        ERROR_ACCESS_DENIED, ERROR_NO_DATA, ERROR_PIPE_CONNECTED, ERROR_PIPE_LISTENING,
+        INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_INBOUND,
+    };
+    use windows_sys::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, PIPE_NOWAIT, PIPE_READMODE_BYTE,
+        PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES,
+    };
+
+    const PIPE_PREFIX: &str = r"\\.\pipe\";
+    const PIPE_BUFFER_SIZE: u32 = 65_536;
+
+    // Configured paths that are not already pipe names are mapped into the pipe namespace
+    pub fn get_pipe_name(path: &Path) -> PathBuf {
+        let path = path.to_string_lossy();
+        if path.starts_with(PIPE_PREFIX) {
+            PathBuf::from(path.as_ref())
+        } else {
+            PathBuf::from(format!("{}{}", PIPE_PREFIX, path.replace('\\', "/")))
+        }
+    }
+
+    fn create_instance(name: &[u16], is_first: bool) -> io::Result<OwnedHandle> {
+        let mut open_mode = PIPE_ACCESS_INBOUND;
+        if is_first {
+            open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+        }
+
+        // Non blocking pipes let the provider poll clients without overlapped IO
+        let handle = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                open_mode,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_NOWAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                0,
+                PIPE_BUFFER_SIZE,
+                0,
+                ptr::null(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(unsafe { OwnedHandle::from_raw_handle(handle) })
+    }
+
+    pub type IpcClientStream = File;
+
+    pub fn connect(path: &Path) -> io::Result<IpcClientStream> {
+        OpenOptions::new().write(true).open(get_pipe_name(path))
+    }
+
+    pub struct IpcStream(File);
+
+    impl Read for IpcStream {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            match self.0.read(buffer) {
+                Err(e) if e.raw_os_error() == Some(ERROR_NO_DATA as i32) => {
+                    Err(ErrorKind::WouldBlock.into())
+                }
+                result => result,
+            }
+        }
+    }
+
+    pub struct IpcListener {
+        name: Vec<u16>,
+        pending: OwnedHandle,
+    }
+
+    impl IpcListener {
+        pub fn bind(path: &Path) -> Result<Self> {
+            let pipe_name = get_pipe_name(path);
+            let name: Vec<u16> = pipe_name.as_os_str().encode_wide().chain(Some(0)).collect();
+
+            let pending = match create_instance(&name, true) {
+                Ok(pending) => pending,
+                Err(e) if e.raw_os_error() == Some(ERROR_ACCESS_DENIED as i32) => {
+                    bail!("IPC pipe {} is already in use", pipe_name.display())
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to create IPC pipe {}", pipe_name.display())
+                    })
+                }
+            };
+
+            Ok(Self { name, pending })
+        }
+
+        pub fn accept(&mut self) -> io::Result<Option<IpcStream>> {
+            let is_connected =
+                unsafe { ConnectNamedPipe(self.pending.as_raw_handle(), ptr::null_mut()) } != 0;
+            if !is_connected {
+                let error = io::Error::last_os_error();
+                match error.raw_os_error().map(|code| code as u32) {
+                    // A client that already closed its end may still have data to read
+                    Some(ERROR_PIPE_CONNECTED) | Some(ERROR_NO_DATA) => (),
+                    Some(ERROR_PIPE_LISTENING) => return Ok(None),
+                    _ => return Err(error),
+                }
+            }
+
+            let pending = create_instance(&self.name, false)?;
+            let stream = std::mem::replace(&mut self.pending, pending);
+
+            Ok(Some(IpcStream(File::from(stream))))
+        }
+    }
+}
+
+fn to_rgb(format: IpcFormat, data: Vec<u8>) -> Vec<u8> {
+    match format {
+        IpcFormat::Rgba8 => data
+            .chunks_exact(4)
+            .flat_map(|pixel| pixel[..3].to_vec())
+            .collect(),
+        IpcFormat::Gray8 => data.iter().flat_map(|value| [*value; 3]).collect(),
+        IpcFormat::Rgb8 | IpcFormat::Uniforms => data,
+    }
+}
+
+fn is_valid_uniform_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_')
+}
+
+pub struct IpcSender {
+    stream: IpcClientStream,
+}
+
+impl IpcSender {
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let stream = connect(path.as_ref()).with_context(|| {
+            format!(
+                "Failed to connect to IPC endpoint {}",
+                path.as_ref().display()
+            )
+        })?;
+
+        Ok(Self { stream })
+    }
+
+    pub fn send(&mut self, message: &IpcMessage) -> Result<()> {
+        self.stream
+            .write_all(&message.encode()?)
+            .context("Failed to send IPC message")
+    }
+
+    pub fn send_frame(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: IpcFormat,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        self.send(&IpcMessage::Frame {
+            timestamp: get_ipc_timestamp(),
+            width,
+            height,
+            format,
+            data,
+        })
+    }
+
+    pub fn send_uniforms(&mut self, values: BTreeMap<String, DataHolder>) -> Result<()> {
+        self.send(&IpcMessage::Uniforms {
+            timestamp: get_ipc_timestamp(),
+            values,
+        })
+    }
+}
+
+pub struct IpcProvider {
+    name: String,
+    path: PathBuf,
+    listener: IpcListener,
+    connections: Vec<(usize, IpcStream, IpcReader)>,
+    next_connection_id: usize,
+    last_timestamp: Option<f64>,
+    value_types: BTreeMap<String, DataType>,
+    values: UniformStore,
+    // Listener errors concern the whole input, value errors the uniform they were sent for
+    // and errors that concern a single connection are queued for take_errors
+    error: Option<InputError>,
+    value_errors: HashMap<String, InputError>,
+    connection_errors: VecDeque<(String, InputError)>,
+    uniforms: UniformStore,
+}
+
+impl IpcProvider {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let listener = IpcListener::bind(&path)?;
+
+        Ok(Self {
+            name: String::new(),
+            path,
+            listener,
+            connections: Vec::new(),
+            next_connection_id: 0,
+            last_timestamp: None,
+            value_types: BTreeMap::new(),
+            values: UniformStore::default(),
+            error: None,
+            value_errors: HashMap::new(),
+            connection_errors: VecDeque::new(),
+            uniforms: UniformStore::default(),
+        })
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_connection_count(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn get_last_timestamp(&self) -> Option<f64> {
+        self.last_timestamp
+    }
+
+    // Errors tagged with the connection that raised them, oldest first
+    pub fn take_errors(&mut self) -> Vec<(String, InputError)> {
+        self.connection_errors.drain(..).collect()
+    }
+
+    fn push_connection_error(&mut self, connection_id: usize, error: InputError) {
+        if self.connection_errors.len() >= MAX_QUEUED_ERRORS {
+            if let Some((connection, error)) = self.connection_errors.pop_front() {
+                log::warn!("Dropped IPC error from {}: {}", connection, error);
+            }
+        }
+
+        self.connection_errors
+            .push_back((format!("connection {}", connection_id), error));
+    }
+
+    pub fn poll(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok(Some(stream)) => {
+                    self.connections
+                        .push((self.next_connection_id, stream, IpcReader::new()));
+                    self.next_connection_id += 1;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    self.error = Some(InputError::DeviceLost(e.to_string()));
+                    break;
+                }
+            }
+        }
+
+        let mut messages = Vec::new();
+        let mut buffer = vec![0; READ_BUFFER_SIZE];
+        let mut errors = Vec::new();
+        self.connections
+            .retain_mut(|(connection_id, stream, reader)| {
+                let is_open = loop {
+                    match stream.read(&mut buffer) {
+                        Ok(0) => break false,
+                        Ok(size) => reader.push(&buffer[..size]),
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => break true,
+                        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                        Err(e) => {
+                            errors.push((*connection_id, InputError::from(e)));
+                            break false;
+                        }
+                    }
+                };
+
+                loop {
+                    match reader.next_message() {
+                        Ok(Some(message)) => messages.push((*connection_id, message)),
+                        Ok(None) => break is_open,
+                        Err(e) => {
+                            errors.push((*connection_id, InputError::from(e)));
+                            break false;
+                        }
+                    }
+                }
+            });
+
+        for (connection_id, error) in errors {
+            self.push_connection_error(connection_id, error);
+        }
+
+        // Only the most recent frame matters, uniform updates are applied in order
+        let last_frame = messages
+            .iter()
+            .rposition(|(_, message)| matches!(message, IpcMessage::Frame { .. }));
+        for (index, (connection_id, message)) in messages.into_iter().enumerate() {
+            self.last_timestamp = Some(message.get_timestamp());
+            match message {
+                IpcMessage::Frame {
+                    width,
+                    height,
+                    format,
+                    data,
+                    ..
+                } if Some(index) == last_frame => {
+                    self.uniforms.set(
+                        "texture",
+                        DataHolder::Texture(((width, height), to_rgb(format, data))),
+                    );
+                }
+                IpcMessage::Frame { .. } => (),
+                IpcMessage::Uniforms { values, .. } => self.apply(connection_id, values),
+            }
+        }
+    }
+
+    fn apply(&mut self, connection_id: usize, values: BTreeMap<String, DataHolder>) {
+        for (local_name, value) in values {
+            if !is_valid_uniform_name(&local_name) {
+                let error = InputError::UnknownUniform(format!("{}_{}", self.name, local_name));
+                self.push_connection_error(connection_id, error);
+                continue;
+            }
+
+            let data_type = *self
+                .value_types
+                .entry(local_name.clone())
+                .or_insert_with(|| value.get_type());
+            if value.get_type() != data_type {
+                let error = InputError::wrong_value_type(
+                    &format!("{}_{}", self.name, local_name),
+                    &[data_type],
+                    value.get_type(),
+                );
+                self.value_errors.insert(local_name, error);
+                continue;
+            }
+
+            self.values.set(&local_name, value);
+        }
+    }
+
+    fn get_local_name<'a>(&self, uniform_name: &'a str) -> InputResult<Option<&'a str>> {
+        if uniform_name == self.name {
+            return Ok(None);
+        }
+
+        match uniform_name
+            .strip_prefix(&self.name)
+            .and_then(|local_name| local_name.strip_prefix('_'))
+        {
+            Some(local_name) if self.value_types.contains_key(local_name) => Ok(Some(local_name)),
+            _ => Err(InputError::UnknownUniform(uniform_name.to_string())),
+        }
+    }
+}
+
+impl InputProvider for IpcProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        let mut uniform_names = vec![self.name.clone()];
+        uniform_names.extend(
+            self.value_types
+                .keys()
+                .map(|local_name| format!("{}_{}", self.name, local_name)),
+        );
+
+        uniform_names
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        let mut descriptions = vec![UniformDescription::new(
+            &self.name,
+            DataType::Texture,
+            DataRange::None,
+            UpdateFrequency::OnChange,
+        )];
+        descriptions.extend(self.value_types.iter().map(|(local_name, data_type)| {
+            UniformDescription::new(
+                &format!("{}_{}", self.name, local_name),
+                *data_type,
+                DataRange::None,
+                UpdateFrequency::OnChange,
+            )
+        }));
+
+        descriptions
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        match self.get_local_name(uniform_name)? {
+            Some(local_name) => Ok(self.values.get_generation(local_name)),
+            None => Ok(self.uniforms.get_generation("texture")),
+        }
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        match self.get_local_name(uniform_name)? {
+            Some(local_name) => match self.value_errors.remove(local_name) {
+                Some(error) => Err(error),
+                None => Ok(self.values.get(local_name)),
+            },
+            None => Ok(self.uniforms.get("texture")),
+        }
+    }
+
+    fn set_property(&mut self, property: &str, _value: &DataHolder) -> InputResult<()> {
+        Err(InputError::UnknownProperty(property.to_string()))
+    }
+
+    fn set_beat(&mut self, _beat: f64, _sync: bool) {
+        self.poll();
+    }
+
+    fn set_time(&mut self, _time: f64, _sync: bool) {
+        self.poll();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::protocol::IPC_HEADER_SIZE;
+
+    fn get_test_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("wvr-ipc-{}-{}.sock", std::process::id(), name))
+    }
+
+    fn poll_until<F: Fn(&mut IpcProvider) -> bool>(provider: &mut IpcProvider, condition: F) {
+        let start = Instant::now();
+        while !condition(provider) {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "IPC poll timed out"
+            );
+            thread::sleep(Duration::from_millis(5));
+            provider.set_time(0.0, false);
+        }
+    }
+
+    #[test]
+    fn sender_roundtrip() {
+        let path = get_test_path("roundtrip");
+        let mut provider = IpcProvider::new(&path).unwrap();
+        provider.set_name("ipc");
+
+        let mut sender = IpcSender::connect(&path).unwrap();
+        sender
+            .send_frame(2, 1, IpcFormat::Rgba8, vec![1, 2, 3, 4, 5, 6, 7, 8])
+            .unwrap();
+        let mut values = BTreeMap::new();
+        values.insert("level".to_string(), DataHolder::Float(0.25));
+        sender.send_uniforms(values).unwrap();
+
+        poll_until(&mut provider, |provider| {
+            provider.provides().contains(&"ipc_level".to_string())
+        });
+
+        assert_eq!(provider.get_connection_count(), 1);
+        assert!(provider.get_last_timestamp().is_some());
+        assert_eq!(
+            provider.borrow("ipc").unwrap(),
+            Some(&DataHolder::Texture(((2, 1), vec![1, 2, 3, 5, 6, 7])))
+        );
+        assert_eq!(
+            provider.borrow("ipc_level").unwrap(),
+            Some(&DataHolder::Float(0.25))
+        );
+
+        drop(sender);
+        poll_until(&mut provider, |provider| {
+            provider.get_connection_count() == 0
+        });
+    }
+
+    #[test]
+    fn uniform_type_changes_are_rejected() {
+        let path = get_test_path("types");
+        let mut provider = IpcProvider::new(&path).unwrap();
+        provider.set_name("ipc");
+
+        let mut sender = IpcSender::connect(&path).unwrap();
+        let mut values = BTreeMap::new();
+        values.insert("level".to_string(), DataHolder::Float(0.25));
+        sender.send_uniforms(values.clone()).unwrap();
+        values.insert("level".to_string(), DataHolder::Bool(true));
+        sender.send_uniforms(values).unwrap();
+
+        poll_until(&mut provider, |provider| {
+            provider.get_last_timestamp().is_some()
+        });
+
+        assert!(provider.borrow("ipc").is_ok());
+        assert!(matches!(
+            provider.borrow("ipc_level"),
+            Err(InputError::WrongValueType { .. })
+        ));
+        assert_eq!(
+            provider.borrow("ipc_level").unwrap(),
+            Some(&DataHolder::Float(0.25))
+        );
+    }
+
+    #[test]
+    fn connection_errors_are_tagged_and_kept() {
+        let path = get_test_path("errors");
+        let mut provider = IpcProvider::new(&path).unwrap();
+        provider.set_name("ipc");
+
+        let mut invalid = IpcSender::connect(&path).unwrap();
+        let mut values = BTreeMap::new();
+        values.insert("not-a-name".to_string(), DataHolder::Float(0.5));
+        values.insert("level".to_string(), DataHolder::Float(0.25));
+        invalid.send_uniforms(values).unwrap();
+        poll_until(&mut provider, |provider| {
+            provider.get_last_timestamp().is_some()
+        });
+
+        let mut garbage = IpcSender::connect(&path).unwrap();
+        garbage.stream.write_all(&[0xff; IPC_HEADER_SIZE]).unwrap();
+        poll_until(&mut provider, |provider| {
+            provider.connection_errors.len() == 2
+        });
+
+        assert_eq!(
+            provider.borrow("ipc_level").unwrap(),
+            Some(&DataHolder::Float(0.25))
+        );
+
+        let errors = provider.take_errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].0, "connection 0");
+        assert!(matches!(errors[0].1, InputError::UnknownUniform(_)));
+        assert_eq!(errors[1].0, "connection 1");
+        assert!(provider.take_errors().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stale_socket_is_replaced() {
+        let path = get_test_path("stale");
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let provider = IpcProvider::new(&path).unwrap();
+        assert!(IpcSender::connect(provider.get_path()).is_ok());
+
+        drop(provider);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_in_use_is_kept() {
+        let path = get_test_path("in-use");
+        let provider = IpcProvider::new(&path).unwrap();
+
+        assert!(IpcProvider::new(&path).is_err());
+        assert!(IpcSender::connect(provider.get_path()).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn regular_file_is_kept() {
+        let path = get_test_path("file");
+        std::fs::write(&path, b"data").unwrap();
+
+        assert!(IpcProvider::new(&path).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"data");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,855 @@
+use std::collections::{HashMap, VecDeque};
+#[cfg(target_os = "linux")]
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+use anyhow::{bail, Context, Result};
+
+use crate::config::midi::{
+    MidiMapping, MidiSource, MidiTakeover, MidiTarget, DATA_ENTRY_LSB, DATA_ENTRY_MSB,
+    NRPN_PARAMETER_LSB, NRPN_PARAMETER_MSB, RPN_PARAMETER_LSB, RPN_PARAMETER_MSB,
+};
+use crate::config::project::ProjectConfig;
+use crate::protocol::midi::{MidiMessage, MidiParser, PITCH_BEND_CENTER};
+use crate::timing::ParameterChange;
+use crate::types::{
+    DataHolder, DataRange, DataType, InputError, InputProvider, InputResult, PropertyDescription,
+    UniformDescription, UniformStore, UpdateFrequency,
+};
+
+const PICKUP_TOLERANCE: f32 = 0.01;
+const MAX_BUFFERED_MESSAGES: usize = 4096;
+const ALL_CHANNELS: i32 = -1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct MappingState {
+    picked_up: bool,
+    last_input: Option<f32>,
+    last_output: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct ChannelState {
+    controller_msb: [u8; 32],
+    nrpn_parameter: Option<(u8, Option<u8>)>,
+    nrpn_msb: u8,
+}
+
+fn get_numeric_value(value: &DataHolder) -> Option<f32> {
+    match value {
+        DataHolder::Float(value) => Some(*value),
+        DataHolder::Int(value) => Some(*value as f32),
+        DataHolder::Bool(value) => Some(*value as u8 as f32),
+        _ => None,
+    }
+}
+
+// Mapped values keep the type of the variable they drive
+fn to_data_holder(value: f32, current: Option<&DataHolder>) -> DataHolder {
+    match current {
+        Some(DataHolder::Int(_)) => DataHolder::Int(value.round() as i32),
+        Some(DataHolder::Bool(_)) => DataHolder::Bool(value >= 0.5),
+        _ => DataHolder::Float(value),
+    }
+}
+
+fn to_change(target: &MidiTarget, value: DataHolder) -> ParameterChange {
+    match target {
+        MidiTarget::ProjectVariable { name } => ParameterChange::ProjectVariable {
+            name: name.clone(),
+            value,
+        },
+        MidiTarget::StageVariable { stage, name } => ParameterChange::StageVariable {
+            stage: stage.clone(),
+            name: name.clone(),
+            value,
+        },
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MidiMapper {
+    mappings: Vec<MidiMapping>,
+    states: Vec<MappingState>,
+    channels: HashMap<u8, ChannelState>,
+}
+
+impl MidiMapper {
+    pub fn new(mappings: Vec<MidiMapping>) -> Self {
+        Self {
+            states: vec![MappingState::default(); mappings.len()],
+            mappings,
+            channels: HashMap::new(),
+        }
+    }
+
+    pub fn get_mappings(&self) -> &[MidiMapping] {
+        &self.mappings
+    }
+
+    pub fn add_mapping(&mut self, mapping: MidiMapping) {
+        self.mappings.push(mapping);
+        self.states.push(MappingState::default());
+    }
+
+    pub fn remove_mappings(&mut self, target: &MidiTarget) {
+        let mut index = 0;
+        while index < self.mappings.len() {
+            if &self.mappings[index].target == target {
+                self.mappings.remove(index);
+                self.states.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    fn decode(&mut self, message: &MidiMessage) -> Vec<(MidiSource, u16)> {
+        let mut sources = Vec::new();
+
+        match *message {
+            MidiMessage::NoteOn { note, velocity, .. } => {
+                sources.push((MidiSource::Note { note }, velocity as u16))
+            }
+            MidiMessage::NoteOff { note, .. } => sources.push((MidiSource::Note { note }, 0)),
+            MidiMessage::PitchBend { value, .. } => sources.push((MidiSource::PitchBend, value)),
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => {
+                sources.push((MidiSource::ControlChange { controller }, value as u16));
+
+                let state = self.channels.entry(channel).or_default();
+                match controller {
+                    NRPN_PARAMETER_MSB => state.nrpn_parameter = Some((value, None)),
+                    NRPN_PARAMETER_LSB => {
+                        if let Some((msb, _)) = state.nrpn_parameter {
+                            state.nrpn_parameter = Some((msb, Some(value)));
+                        }
+                    }
+                    RPN_PARAMETER_MSB | RPN_PARAMETER_LSB => state.nrpn_parameter = None,
+                    _ => (),
+                }
+
+                if let Some((parameter_msb, Some(parameter_lsb))) = state.nrpn_parameter {
+                    let parameter = (parameter_msb as u16) << 7 | parameter_lsb as u16;
+                    match controller {
+                        DATA_ENTRY_MSB => {
+                            state.nrpn_msb = value;
+                            sources.push((MidiSource::Nrpn { parameter }, (value as u16) << 7));
+                        }
+                        DATA_ENTRY_LSB => sources.push((
+                            MidiSource::Nrpn { parameter },
+                            (state.nrpn_msb as u16) << 7 | value as u16,
+                        )),
+                        _ => (),
+                    }
+                }
+
+                // A new MSB resets the LSB, as senders only resend the LSB when it changes
+                match controller {
+                    0..=31 => {
+                        state.controller_msb[controller as usize] = value;
+                        sources.push((
+                            MidiSource::ControlChange14 { controller },
+                            (value as u16) << 7,
+                        ));
+                    }
+                    32..=63 => {
+                        let msb_controller = controller - 32;
+                        sources.push((
+                            MidiSource::ControlChange14 {
+                                controller: msb_controller,
+                            },
+                            (state.controller_msb[msb_controller as usize] as u16) << 7
+                                | value as u16,
+                        ));
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+
+        sources
+    }
+
+    pub fn process(
+        &mut self,
+        message: &MidiMessage,
+        project: &ProjectConfig,
+    ) -> Vec<ParameterChange> {
+        self.process_values(message, |target| target.get_value(project))
+            .into_iter()
+            .map(|(target, value)| to_change(&target, value))
+            .collect()
+    }
+
+    // Pickup compares against whatever get_value reports as the current target value
+    pub fn process_values<'a, F>(
+        &mut self,
+        message: &MidiMessage,
+        get_value: F,
+    ) -> Vec<(MidiTarget, DataHolder)>
+    where
+        F: Fn(&MidiTarget) -> Option<&'a DataHolder>,
+    {
+        let channel = message.get_channel();
+        let mut values = Vec::new();
+
+        for (source, raw_value) in self.decode(message) {
+            for (mapping, state) in self.mappings.iter().zip(self.states.iter_mut()) {
+                if !mapping.matches(source, channel) {
+                    continue;
+                }
+
+                let input = mapping.map_value(raw_value);
+                let current = get_value(&mapping.target);
+                let current_value = current.and_then(get_numeric_value);
+
+                if mapping.takeover == MidiTakeover::Pickup {
+                    // Someone else moved the variable since we last wrote it
+                    if current_value.is_some() && current_value != state.last_output {
+                        state.picked_up = false;
+                    }
+
+                    if !state.picked_up {
+                        let tolerance =
+                            (mapping.range.1 - mapping.range.0).abs() * PICKUP_TOLERANCE;
+                        state.picked_up = match (current_value, state.last_input) {
+                            (None, _) => true,
+                            (Some(current_value), _)
+                                if (input - current_value).abs() <= tolerance =>
+                            {
+                                true
+                            }
+                            (Some(current_value), Some(last_input)) => {
+                                (last_input - current_value).signum()
+                                    != (input - current_value).signum()
+                            }
+                            (Some(_), None) => false,
+                        };
+                    }
+
+                    state.last_input = Some(input);
+                    if !state.picked_up {
+                        continue;
+                    }
+                }
+
+                let value = to_data_holder(input, current);
+                state.last_output = get_numeric_value(&value);
+                values.push((mapping.target.clone(), value));
+            }
+        }
+
+        values
+    }
+}
+
+const MIDI_UNIFORMS: [&str; 6] = [
+    "notes",
+    "cc",
+    "pitch_bend",
+    "pressure",
+    "last_note",
+    "last_velocity",
+];
+
+// Raw MIDI devices are looked up by card name on ALSA, anything else must be a device path
+pub fn find_midi_device(name: &str) -> Result<PathBuf> {
+    if Path::new(name).exists() {
+        return Ok(PathBuf::from(name));
+    }
+
+    #[cfg(target_os = "linux")]
+    let device_path = find_alsa_midi_device(name);
+    #[cfg(not(target_os = "linux"))]
+    let device_path: Option<PathBuf> = None;
+
+    match device_path {
+        Some(device_path) => Ok(device_path),
+        None => bail!("MIDI device not found: {}", name),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn find_alsa_midi_device(name: &str) -> Option<PathBuf> {
+    let cards = fs::read_to_string("/proc/asound/cards").unwrap_or_default();
+    for line in cards.lines() {
+        let (index, description) = match line.trim().split_once(' ') {
+            Some((index, description)) => (index, description),
+            None => continue,
+        };
+
+        if let Ok(index) = index.parse::<usize>() {
+            let device_path = PathBuf::from(format!("/dev/snd/midiC{}D0", index));
+            if description.contains(name) && device_path.exists() {
+                return Some(device_path);
+            }
+        }
+    }
+
+    None
+}
+
+pub struct MidiProvider {
+    name: String,
+    parser: MidiParser,
+    channel_filter: Option<u8>,
+    mapper: MidiMapper,
+    byte_rx: Option<Receiver<Vec<u8>>>,
+    messages: VecDeque<MidiMessage>,
+    notes: Vec<f32>,
+    controllers: Vec<f32>,
+    error: Option<InputError>,
+    uniforms: UniformStore,
+}
+
+impl Default for MidiProvider {
+    fn default() -> Self {
+        let mut provider = Self {
+            name: String::new(),
+            parser: MidiParser::new(),
+            channel_filter: None,
+            mapper: MidiMapper::default(),
+            byte_rx: None,
+            messages: VecDeque::new(),
+            notes: vec![0.0; 128],
+            controllers: vec![0.0; 128],
+            error: None,
+            uniforms: UniformStore::default(),
+        };
+        provider.reset_uniforms();
+
+        provider
+    }
+}
+
+impl MidiProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut device =
+            File::open(path).with_context(|| format!("Failed to open MIDI device: {:?}", path))?;
+
+        // The reader blocks on the device, it exits on the first read after the provider is dropped
+        let (byte_tx, byte_rx) = channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 256];
+            loop {
+                match device.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(size) => {
+                        if byte_tx.send(buffer[..size].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            byte_rx: Some(byte_rx),
+            ..Self::default()
+        })
+    }
+
+    // Mapped targets are exposed as uniforms named after the variable they drive
+    pub fn set_mappings(&mut self, mappings: Vec<MidiMapping>) {
+        for target in self.get_mapped_names() {
+            self.uniforms.remove(&target);
+        }
+        self.mapper = MidiMapper::new(mappings);
+    }
+
+    pub fn get_mapper(&self) -> &MidiMapper {
+        &self.mapper
+    }
+
+    pub fn get_mapper_mut(&mut self) -> &mut MidiMapper {
+        &mut self.mapper
+    }
+
+    fn get_mapped_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .mapper
+            .get_mappings()
+            .iter()
+            .map(|mapping| mapping.target.get_uniform_name())
+            .filter(|name| !MIDI_UNIFORMS.contains(&name.as_str()))
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+
+        names
+    }
+
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        for message in self.parser.parse(bytes) {
+            self.apply(&message);
+        }
+    }
+
+    // Holds the messages received since the last set_time
+    pub fn drain_messages(&mut self) -> Vec<MidiMessage> {
+        self.messages.drain(..).collect()
+    }
+
+    fn reset_uniforms(&mut self) {
+        self.notes.iter_mut().for_each(|velocity| *velocity = 0.0);
+        self.controllers.iter_mut().for_each(|value| *value = 0.0);
+        self.uniforms
+            .set("notes", DataHolder::FloatArray(self.notes.clone()));
+        self.uniforms
+            .set("cc", DataHolder::FloatArray(self.controllers.clone()));
+        self.uniforms.set("pitch_bend", DataHolder::Float(0.0));
+        self.uniforms.set("pressure", DataHolder::Float(0.0));
+        self.uniforms.set("last_note", DataHolder::Int(-1));
+        self.uniforms.set("last_velocity", DataHolder::Float(0.0));
+    }
+
+    fn poll(&mut self) {
+        let mut bytes = Vec::new();
+        if let Some(byte_rx) = &self.byte_rx {
+            loop {
+                match byte_rx.try_recv() {
+                    Ok(chunk) => bytes.extend(chunk),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.error = Some(InputError::DeviceLost(format!(
+                            "MIDI input {} disconnected",
+                            self.name
+                        )));
+                        self.byte_rx = None;
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.push_bytes(&bytes);
+    }
+
+    fn apply(&mut self, message: &MidiMessage) {
+        if let Some(channel) = self.channel_filter {
+            if message.get_channel() != channel {
+                return;
+            }
+        }
+
+        // Messages only live for a frame, the cap covers callers that never drain them
+        if self.messages.len() >= MAX_BUFFERED_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(*message);
+        let uniforms = &self.uniforms;
+        let changes = self
+            .mapper
+            .process_values(message, |target| uniforms.get(&target.get_uniform_name()));
+        for (target, value) in changes {
+            let local_name = target.get_uniform_name();
+            if !MIDI_UNIFORMS.contains(&local_name.as_str()) {
+                self.uniforms.set(&local_name, value);
+            }
+        }
+
+        match *message {
+            MidiMessage::NoteOn { note, velocity, .. } => {
+                let velocity = velocity as f32 / 127.0;
+                self.notes[note as usize] = velocity;
+                self.uniforms
+                    .set("notes", DataHolder::FloatArray(self.notes.clone()));
+                self.uniforms.set("last_note", DataHolder::Int(note as i32));
+                self.uniforms
+                    .set("last_velocity", DataHolder::Float(velocity));
+            }
+            MidiMessage::NoteOff { note, .. } => {
+                self.notes[note as usize] = 0.0;
+                self.uniforms
+                    .set("notes", DataHolder::FloatArray(self.notes.clone()));
+            }
+            MidiMessage::ControlChange {
+                controller, value, ..
+            } => {
+                self.controllers[controller as usize] = value as f32 / 127.0;
+                self.uniforms
+                    .set("cc", DataHolder::FloatArray(self.controllers.clone()));
+            }
+            MidiMessage::PitchBend { value, .. } => {
+                let bend = (value as f32 - PITCH_BEND_CENTER as f32) / PITCH_BEND_CENTER as f32;
+                self.uniforms
+                    .set("pitch_bend", DataHolder::Float(bend.max(-1.0)));
+            }
+            MidiMessage::ChannelPressure { pressure, .. } => {
+                self.uniforms
+                    .set("pressure", DataHolder::Float(pressure as f32 / 127.0));
+            }
+            MidiMessage::PolyPressure { .. } | MidiMessage::ProgramChange { .. } => (),
+        }
+    }
+
+    fn get_local_name<'a>(&self, uniform_name: &'a str) -> InputResult<&'a str> {
+        match uniform_name
+            .strip_prefix(&self.name)
+            .and_then(|local_name| local_name.strip_prefix('_'))
+        {
+            Some(local_name)
+                if MIDI_UNIFORMS.contains(&local_name)
+                    || self
+                        .mapper
+                        .get_mappings()
+                        .iter()
+                        .any(|mapping| mapping.target.get_uniform_name() == local_name) =>
+            {
+                Ok(local_name)
+            }
+            _ => Err(InputError::UnknownUniform(uniform_name.to_string())),
+        }
+    }
+}
+
+impl InputProvider for MidiProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        MIDI_UNIFORMS
+            .iter()
+            .map(|local_name| local_name.to_string())
+            .chain(self.get_mapped_names())
+            .map(|local_name| format!("{}_{}", self.name, local_name))
+            .collect()
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        let unit_range = DataRange::FloatRange(0.0, 1.0, 1.0 / 127.0);
+
+        let mut description = vec![
+            UniformDescription::new(
+                &format!("{}_notes", self.name),
+                DataType::FloatArray,
+                unit_range,
+                UpdateFrequency::OnChange,
+            ),
+            UniformDescription::new(
+                &format!("{}_cc", self.name),
+                DataType::FloatArray,
+                unit_range,
+                UpdateFrequency::OnChange,
+            ),
+            UniformDescription::new(
+                &format!("{}_pitch_bend", self.name),
+                DataType::Float,
+                DataRange::FloatRange(-1.0, 1.0, 1.0 / PITCH_BEND_CENTER as f64),
+                UpdateFrequency::OnChange,
+            ),
+            UniformDescription::new(
+                &format!("{}_pressure", self.name),
+                DataType::Float,
+                unit_range,
+                UpdateFrequency::OnChange,
+            ),
+            UniformDescription::new(
+                &format!("{}_last_note", self.name),
+                DataType::Int,
+                DataRange::IntRange(-1, 127, 1),
+                UpdateFrequency::OnChange,
+            ),
+            UniformDescription::new(
+                &format!("{}_last_velocity", self.name),
+                DataType::Float,
+                unit_range,
+                UpdateFrequency::OnChange,
+            ),
+        ];
+
+        // Several mappings may drive one target, the first one describes it
+        for local_name in self.get_mapped_names() {
+            let mapping = self
+                .mapper
+                .get_mappings()
+                .iter()
+                .find(|mapping| mapping.target.get_uniform_name() == local_name);
+            if let Some(mapping) = mapping {
+                let (min, max) = mapping.range;
+                description.push(UniformDescription::new(
+                    &format!("{}_{}", self.name, local_name),
+                    DataType::Float,
+                    DataRange::FloatRange(
+                        min.min(max) as f64,
+                        min.max(max) as f64,
+                        (max - min).abs() as f64 / mapping.source.get_resolution() as f64,
+                    ),
+                    UpdateFrequency::OnChange,
+                ));
+            }
+        }
+
+        description
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        let local_name = self.get_local_name(uniform_name)?;
+        Ok(self.uniforms.get_generation(local_name))
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        let local_name = self.get_local_name(uniform_name)?;
+        Ok(self.uniforms.get(local_name))
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()> {
+        match self
+            .list_properties()
+            .iter()
+            .find(|description| description.name == property)
+        {
+            Some(description) => description.check(value)?,
+            None => return Err(InputError::UnknownProperty(property.to_string())),
+        }
+
+        match (property, value) {
+            ("channel", DataHolder::Int(ALL_CHANNELS)) => self.channel_filter = None,
+            ("channel", DataHolder::Int(channel)) => self.channel_filter = Some(*channel as u8),
+            _ => return Err(InputError::UnknownProperty(property.to_string())),
+        }
+
+        Ok(())
+    }
+
+    fn list_properties(&self) -> Vec<PropertyDescription> {
+        vec![PropertyDescription::new(
+            "channel",
+            DataType::Int,
+            DataRange::IntRange(ALL_CHANNELS as i64, 15, 1),
+            "MIDI channel to listen to, -1 for all channels",
+        )]
+    }
+
+    fn get_property(&self, property: &str) -> InputResult<DataHolder> {
+        match property {
+            "channel" => Ok(DataHolder::Int(
+                self.channel_filter
+                    .map_or(ALL_CHANNELS, |channel| channel as i32),
+            )),
+            _ => Err(InputError::UnknownProperty(property.to_string())),
+        }
+    }
+
+    fn set_beat(&mut self, _beat: f64, _sync: bool) {
+        self.poll();
+    }
+
+    fn set_time(&mut self, _time: f64, _sync: bool) {
+        self.messages.clear();
+        self.poll();
+    }
+
+    fn stop(&mut self) -> InputResult<()> {
+        self.parser.reset();
+        self.messages.clear();
+        self.reset_uniforms();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control_change(controller: u8, value: u8) -> MidiMessage {
+        MidiMessage::ControlChange {
+            channel: 0,
+            controller,
+            value,
+        }
+    }
+
+    fn variable(name: &str) -> MidiTarget {
+        MidiTarget::ProjectVariable {
+            name: name.to_string(),
+        }
+    }
+
+    fn get_values(mapper: &mut MidiMapper, messages: &[MidiMessage]) -> Vec<f32> {
+        messages
+            .iter()
+            .flat_map(|message| mapper.process_values(message, |_| None))
+            .filter_map(|(_, value)| get_numeric_value(&value))
+            .collect()
+    }
+
+    #[test]
+    fn control_change_14_combines_msb_and_lsb() {
+        let mut mapper = MidiMapper::new(vec![MidiMapping::new(
+            MidiSource::ControlChange14 { controller: 7 },
+            None,
+            variable("level"),
+        )]);
+
+        let values = get_values(
+            &mut mapper,
+            &[
+                control_change(7, 64),
+                control_change(39, 127),
+                control_change(39, 0),
+                control_change(7, 127),
+            ],
+        );
+        assert_eq!(
+            values,
+            [
+                (64 << 7) as f32 / 16383.0,
+                ((64 << 7) | 127) as f32 / 16383.0,
+                (64 << 7) as f32 / 16383.0,
+                (127 << 7) as f32 / 16383.0,
+            ]
+        );
+
+        // Other channels keep their own MSB
+        let other_channel = MidiMessage::ControlChange {
+            channel: 1,
+            controller: 39,
+            value: 1,
+        };
+        assert_eq!(get_values(&mut mapper, &[other_channel]), [1.0 / 16383.0]);
+    }
+
+    #[test]
+    fn nrpn_needs_both_parameter_bytes() {
+        let parameter = (3 << 7) | 5;
+        let mut mapper = MidiMapper::new(vec![MidiMapping::new(
+            MidiSource::Nrpn { parameter },
+            None,
+            variable("level"),
+        )]);
+
+        assert!(get_values(
+            &mut mapper,
+            &[
+                control_change(NRPN_PARAMETER_MSB, 3),
+                control_change(DATA_ENTRY_MSB, 10)
+            ]
+        )
+        .is_empty());
+
+        let values = get_values(
+            &mut mapper,
+            &[
+                control_change(NRPN_PARAMETER_LSB, 5),
+                control_change(DATA_ENTRY_MSB, 100),
+                control_change(DATA_ENTRY_LSB, 27),
+                control_change(RPN_PARAMETER_MSB, 0),
+                control_change(DATA_ENTRY_MSB, 1),
+            ],
+        );
+        assert_eq!(
+            values,
+            [
+                (100 << 7) as f32 / 16383.0,
+                ((100 << 7) | 27) as f32 / 16383.0,
+            ]
+        );
+    }
+
+    #[test]
+    fn pickup_waits_for_the_control_to_reach_the_value() {
+        let mut mapping = MidiMapping::new(
+            MidiSource::ControlChange { controller: 64 },
+            None,
+            variable("level"),
+        );
+        mapping.takeover = MidiTakeover::Pickup;
+        let mut mapper = MidiMapper::new(vec![mapping]);
+
+        let current = DataHolder::Float(0.5);
+        let mut process = |value: u8| -> Vec<(MidiTarget, DataHolder)> {
+            mapper.process_values(&control_change(64, value), |_| Some(&current))
+        };
+
+        assert!(process(0).is_empty());
+        assert!(process(30).is_empty());
+        assert_eq!(
+            process(70),
+            [(variable("level"), DataHolder::Float(70.0 / 127.0))]
+        );
+    }
+
+    #[test]
+    fn provider_pickup_ignores_jumps_from_another_control() {
+        let mut jump = MidiMapping::new(
+            MidiSource::ControlChange { controller: 64 },
+            None,
+            variable("level"),
+        );
+        jump.range = (0.0, 127.0);
+        let mut pickup = MidiMapping::new(
+            MidiSource::ControlChange { controller: 65 },
+            None,
+            variable("level"),
+        );
+        pickup.range = (0.0, 127.0);
+        pickup.takeover = MidiTakeover::Pickup;
+
+        let mut provider = MidiProvider::new();
+        provider.set_name("midi");
+        provider.set_mappings(vec![jump, pickup]);
+        let mut get_level = |bytes: &[u8]| {
+            provider.push_bytes(bytes);
+            provider.get("midi_level").unwrap()
+        };
+
+        assert_eq!(get_level(&[0xB0, 64, 100]), Some(DataHolder::Float(100.0)));
+        assert_eq!(get_level(&[0xB0, 65, 10]), Some(DataHolder::Float(100.0)));
+        assert_eq!(get_level(&[0xB0, 65, 50]), Some(DataHolder::Float(100.0)));
+        assert_eq!(get_level(&[0xB0, 65, 101]), Some(DataHolder::Float(101.0)));
+        assert_eq!(get_level(&[0xB0, 65, 20]), Some(DataHolder::Float(20.0)));
+    }
+
+    #[test]
+    fn channel_filter_rejects_out_of_range_channels() {
+        let mut provider = MidiProvider::new();
+        provider.set_name("midi");
+        provider
+            .set_property("channel", &DataHolder::Int(2))
+            .unwrap();
+        provider.push_bytes(&[0x92, 62, 127, 0x90, 60, 127]);
+        assert_eq!(
+            provider.get("midi_last_note").unwrap(),
+            Some(DataHolder::Int(62))
+        );
+
+        for channel in [16, -2] {
+            assert!(matches!(
+                provider.set_property("channel", &DataHolder::Int(channel)),
+                Err(InputError::OutOfRange { .. })
+            ));
+        }
+        assert_eq!(
+            provider.get_property("channel").unwrap(),
+            DataHolder::Int(2)
+        );
+
+        provider
+            .set_property("channel", &DataHolder::Int(ALL_CHANNELS))
+            .unwrap();
+        provider.push_bytes(&[0x95, 64, 127]);
+        assert_eq!(
+            provider.get("midi_last_note").unwrap(),
+            Some(DataHolder::Int(64))
+        );
+    }
+}
@@ -0,0 +1,29 @@
+pub mod audio;
+pub mod data_file;
+pub mod generator;
+pub mod image_sequence;
+#[cfg(any(unix, windows))]
+pub mod ipc;
+pub mod midi;
+pub mod osc;
+pub mod pointer;
+pub mod record;
+pub mod registry;
+pub mod script;
+pub mod text;
+pub mod threaded;
+
+pub use audio::*;
+pub use data_file::*;
+pub use generator::*;
+pub use image_sequence::*;
+#[cfg(any(unix, windows))]
+pub use ipc::*;
+pub use midi::*;
+pub use osc::*;
+pub use pointer::*;
+pub use record::*;
+pub use registry::*;
+pub use script::*;
+pub use text::*;
+pub use threaded::*;
@@ -0,0 +1,642 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::TryInto;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+
+use crate::protocol::osc::{OscArgument, OscMessage, OscPacket};
+use crate::types::{
+    DataHolder, DataRange, DataType, InputError, InputProvider, InputResult, PropertyDescription,
+    UniformDescription, UniformStore, UpdateFrequency,
+};
+
+const MAX_PACKET_SIZE: usize = 65_536;
+const MAX_PENDING_MESSAGES: usize = 4096;
+
+fn flatten_arguments(arguments: &[OscArgument], flattened: &mut Vec<OscArgument>) {
+    for argument in arguments {
+        match argument {
+            OscArgument::Array(arguments) => flatten_arguments(arguments, flattened),
+            OscArgument::Color(color) => flattened.extend(
+                color
+                    .iter()
+                    .map(|channel| OscArgument::Float(*channel as f32 / 255.0)),
+            ),
+            _ => flattened.push(argument.clone()),
+        }
+    }
+}
+
+fn infer_type(arguments: &[OscArgument]) -> Option<DataType> {
+    let is_int =
+        |argument: &OscArgument| matches!(argument, OscArgument::Int(_) | OscArgument::Long(_));
+    let is_number = |argument: &OscArgument| {
+        is_int(argument) || matches!(argument, OscArgument::Float(_) | OscArgument::Double(_))
+    };
+    let is_bool = |argument: &OscArgument| {
+        matches!(
+            argument,
+            OscArgument::Bool(_) | OscArgument::Impulse | OscArgument::Nil
+        )
+    };
+
+    match arguments {
+        [] => Some(DataType::Bool),
+        [OscArgument::String(_) | OscArgument::Symbol(_) | OscArgument::Char(_)] => {
+            Some(DataType::String)
+        }
+        [OscArgument::Blob(_)] => Some(DataType::ByteArray),
+        [OscArgument::Midi(_)] => Some(DataType::Int4),
+        [argument] if is_int(argument) => Some(DataType::Int),
+        [argument] if is_number(argument) => Some(DataType::Float),
+        [argument] if is_bool(argument) => Some(DataType::Bool),
+        _ if arguments.iter().all(is_int) => Some(match arguments.len() {
+            2 => DataType::Int2,
+            3 => DataType::Int3,
+            4 => DataType::Int4,
+            _ => DataType::IntArray,
+        }),
+        _ if arguments.iter().all(is_number) => Some(match arguments.len() {
+            2 => DataType::Float2,
+            3 => DataType::Float3,
+            4 => DataType::Float4,
+            _ => DataType::FloatArray,
+        }),
+        _ if arguments.iter().all(is_bool) => Some(DataType::BoolArray),
+        _ => None,
+    }
+}
+
+fn to_floats<const N: usize>(numbers: &[f64]) -> Option<[f32; N]> {
+    let values: Vec<f32> = numbers.iter().take(N).map(|value| *value as f32).collect();
+    values.try_into().ok()
+}
+
+fn to_ints<const N: usize>(numbers: &[f64]) -> Option<[i32; N]> {
+    let values: Vec<i32> = numbers
+        .iter()
+        .take(N)
+        .map(|value| value.round() as i32)
+        .collect();
+    values.try_into().ok()
+}
+
+fn to_matrix<const N: usize>(numbers: &[f64]) -> Option<[[f32; N]; N]> {
+    if numbers.len() < N * N {
+        return None;
+    }
+
+    let mut matrix = [[0.0; N]; N];
+    for (index, value) in numbers.iter().take(N * N).enumerate() {
+        matrix[index / N][index % N] = *value as f32;
+    }
+
+    Some(matrix)
+}
+
+pub fn osc_to_data_holder(
+    arguments: &[OscArgument],
+    data_type: Option<DataType>,
+) -> Option<DataHolder> {
+    let mut flattened = Vec::new();
+    flatten_arguments(arguments, &mut flattened);
+
+    let data_type = match data_type {
+        Some(data_type) => data_type,
+        None => infer_type(&flattened)?,
+    };
+
+    if let ([OscArgument::String(value) | OscArgument::Symbol(value)], DataType::String) =
+        (flattened.as_slice(), data_type)
+    {
+        return Some(DataHolder::String(value.clone()));
+    }
+
+    let numbers: Vec<f64> = match flattened.as_slice() {
+        // A bare address is a trigger
+        [] => vec![1.0],
+        [OscArgument::Midi(bytes)] => bytes.iter().map(|byte| *byte as f64).collect(),
+        [OscArgument::Blob(bytes)] => bytes.iter().map(|byte| *byte as f64).collect(),
+        _ => flattened
+            .iter()
+            .map(OscArgument::as_f64)
+            .collect::<Option<Vec<f64>>>()?,
+    };
+
+    let holder = match data_type {
+        DataType::Float => DataHolder::Float(*numbers.first()? as f32),
+        DataType::Float2 => DataHolder::Float2(to_floats(&numbers)?),
+        DataType::Float3 => DataHolder::Float3(to_floats(&numbers)?),
+        DataType::Float4 => DataHolder::Float4(to_floats(&numbers)?),
+        DataType::FloatArray => {
+            DataHolder::FloatArray(numbers.iter().map(|value| *value as f32).collect())
+        }
+
+        DataType::Int => DataHolder::Int(numbers.first()?.round() as i32),
+        DataType::Int2 => DataHolder::Int2(to_ints(&numbers)?),
+        DataType::Int3 => DataHolder::Int3(to_ints(&numbers)?),
+        DataType::Int4 => DataHolder::Int4(to_ints(&numbers)?),
+        DataType::IntArray => {
+            DataHolder::IntArray(numbers.iter().map(|value| value.round() as i32).collect())
+        }
+
+        DataType::Mat2 => DataHolder::Mat2(to_matrix(&numbers)?),
+        DataType::Mat3 => DataHolder::Mat3(to_matrix(&numbers)?),
+        DataType::Mat4 => DataHolder::Mat4(to_matrix(&numbers)?),
+
+        DataType::Bool => DataHolder::Bool(*numbers.first()? != 0.0),
+        DataType::BoolArray => {
+            DataHolder::BoolArray(numbers.iter().map(|value| *value != 0.0).collect())
+        }
+
+        DataType::ByteArray => DataHolder::ByteArray(
+            numbers
+                .iter()
+                .map(|value| value.clamp(0.0, 255.0) as u8)
+                .collect(),
+        ),
+
+        DataType::String => DataHolder::String(
+            numbers
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<String>>()
+                .join(" "),
+        ),
+
+        DataType::Texture | DataType::SrgbTexture => return None,
+    };
+
+    Some(holder)
+}
+
+fn get_local_name(address: &str) -> String {
+    let local_name: String = address
+        .trim_matches('/')
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if local_name.is_empty() {
+        "value".to_string()
+    } else {
+        local_name
+    }
+}
+
+pub struct OscProvider {
+    name: String,
+    socket: UdpSocket,
+    prefix: String,
+    discovered_types: BTreeMap<String, DataType>,
+    addresses: HashMap<String, String>,
+    pending: VecDeque<(SystemTime, OscMessage)>,
+    error: Option<InputError>,
+    uniforms: UniformStore,
+}
+
+impl OscProvider {
+    pub fn new(
+        address: &str,
+        port: u16,
+        prefix: &str,
+        uniform_types: HashMap<String, DataType>,
+    ) -> Result<Self> {
+        let socket = UdpSocket::bind((address, port))
+            .with_context(|| format!("Failed to bind OSC socket on {}:{}", address, port))?;
+        socket
+            .set_nonblocking(true)
+            .context("Failed to configure OSC socket")?;
+
+        // Configured types are seeded here, the first message fixes the type of the others
+        let mut discovered_types = BTreeMap::new();
+        let mut addresses: HashMap<String, String> = HashMap::new();
+        for (address, data_type) in uniform_types {
+            let local_name = get_local_name(&address);
+            let address = address.trim_matches('/').to_string();
+            if let Some(other_address) = addresses.get(&local_name) {
+                bail!(
+                    "OSC addresses /{} and /{} both map to {}",
+                    other_address,
+                    address,
+                    local_name
+                );
+            }
+
+            discovered_types.insert(local_name.clone(), data_type);
+            addresses.insert(local_name, address);
+        }
+
+        Ok(Self {
+            name: String::new(),
+            socket,
+            prefix: prefix.trim_end_matches('/').to_string(),
+            discovered_types,
+            addresses,
+            pending: VecDeque::new(),
+            error: None,
+            uniforms: UniformStore::default(),
+        })
+    }
+
+    pub fn get_local_address(&self) -> Result<SocketAddr> {
+        self.socket
+            .local_addr()
+            .context("Failed to get OSC socket address")
+    }
+
+    pub fn poll(&mut self) {
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+        loop {
+            let (size, sender) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    self.error = Some(InputError::DeviceLost(e.to_string()));
+                    break;
+                }
+            };
+
+            match OscPacket::decode(&buffer[..size]) {
+                Ok(packet) => {
+                    for (timetag, message) in packet.into_messages() {
+                        if timetag.is_immediate() {
+                            self.apply(&message);
+                        } else {
+                            self.schedule(timetag.to_system_time(), message);
+                        }
+                    }
+                }
+                // Anyone can reach the socket, a bad packet must not take the input down
+                Err(e) => log::warn!("Dropped malformed OSC packet from {}: {:#}", sender, e),
+            }
+        }
+
+        let now = SystemTime::now();
+        let (mut due, pending): (VecDeque<_>, VecDeque<_>) = self
+            .pending
+            .drain(..)
+            .partition(|(release_time, _)| *release_time <= now);
+        self.pending = pending;
+
+        // Due messages are applied in timetag order, those sharing a timetag in arrival order
+        due.make_contiguous()
+            .sort_by_key(|(release_time, _)| *release_time);
+        for (_, message) in due {
+            self.apply(&message);
+        }
+    }
+
+    // Any host that reaches the socket can send future bundles, the oldest ones make room
+    fn schedule(&mut self, release_time: SystemTime, message: OscMessage) {
+        if self.pending.len() >= MAX_PENDING_MESSAGES {
+            self.pending.pop_front();
+            self.error = Some(InputError::QueueFull(format!(
+                "OSC input {} dropped a scheduled message",
+                self.name
+            )));
+        }
+
+        self.pending.push_back((release_time, message));
+    }
+
+    fn apply(&mut self, message: &OscMessage) {
+        let address = match message.address.strip_prefix(&self.prefix) {
+            Some(address) if address.is_empty() || address.starts_with('/') => address,
+            _ => return,
+        };
+
+        let local_name = get_local_name(address);
+        let address = address.trim_matches('/');
+        match self.addresses.get(&local_name) {
+            Some(other_address) if other_address != address => {
+                self.error = Some(InputError::UniformCollision {
+                    name: format!("{}_{}", self.name, local_name),
+                    sources: vec![format!("/{}", other_address), format!("/{}", address)],
+                });
+                return;
+            }
+            Some(_) => (),
+            None => {
+                self.addresses
+                    .insert(local_name.clone(), address.to_string());
+            }
+        }
+
+        let data_type = self.discovered_types.get(&local_name).copied();
+
+        match osc_to_data_holder(&message.arguments, data_type) {
+            Some(value) => {
+                self.discovered_types
+                    .entry(local_name.clone())
+                    .or_insert_with(|| value.get_type());
+                self.uniforms.set(&local_name, value);
+            }
+            None => {
+                let found = infer_type(&message.arguments).unwrap_or(DataType::ByteArray);
+                self.error = Some(InputError::wrong_value_type(
+                    &format!("{}_{}", self.name, local_name),
+                    &data_type.into_iter().collect::<Vec<DataType>>(),
+                    found,
+                ));
+            }
+        }
+    }
+
+    fn get_local_name<'a>(&self, uniform_name: &'a str) -> InputResult<&'a str> {
+        match uniform_name
+            .strip_prefix(&self.name)
+            .and_then(|local_name| local_name.strip_prefix('_'))
+        {
+            Some(local_name) if self.discovered_types.contains_key(local_name) => Ok(local_name),
+            _ => Err(InputError::UnknownUniform(uniform_name.to_string())),
+        }
+    }
+}
+
+impl InputProvider for OscProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.discovered_types
+            .keys()
+            .map(|local_name| format!("{}_{}", self.name, local_name))
+            .collect()
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        self.discovered_types
+            .iter()
+            .map(|(local_name, data_type)| {
+                UniformDescription::new(
+                    &format!("{}_{}", self.name, local_name),
+                    *data_type,
+                    DataRange::None,
+                    UpdateFrequency::OnChange,
+                )
+            })
+            .collect()
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        let local_name = self.get_local_name(uniform_name)?;
+        Ok(self.uniforms.get_generation(local_name))
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        let local_name = self.get_local_name(uniform_name)?;
+        Ok(self.uniforms.get(local_name))
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()> {
+        match (property, value) {
+            ("prefix", DataHolder::String(prefix)) => {
+                self.prefix = prefix.trim_end_matches('/').to_string();
+                Ok(())
+            }
+            ("prefix", _) => Err(InputError::wrong_value_type(
+                property,
+                &[DataType::String],
+                value.get_type(),
+            )),
+            _ => Err(InputError::UnknownProperty(property.to_string())),
+        }
+    }
+
+    fn list_properties(&self) -> Vec<PropertyDescription> {
+        vec![PropertyDescription::new(
+            "prefix",
+            DataType::String,
+            DataRange::None,
+            "Address prefix stripped from incoming messages",
+        )]
+    }
+
+    fn get_property(&self, property: &str) -> InputResult<DataHolder> {
+        match property {
+            "prefix" => Ok(DataHolder::String(self.prefix.clone())),
+            _ => Err(InputError::UnknownProperty(property.to_string())),
+        }
+    }
+
+    fn set_beat(&mut self, _beat: f64, _sync: bool) {
+        self.poll();
+    }
+
+    fn set_time(&mut self, _time: f64, _sync: bool) {
+        self.poll();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::protocol::osc::{OscBundle, OscTimeTag};
+
+    fn create_provider(uniform_types: HashMap<String, DataType>) -> (OscProvider, UdpSocket) {
+        let mut provider = OscProvider::new("127.0.0.1", 0, "/wvr", uniform_types).unwrap();
+        provider.set_name("osc");
+
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = provider.get_local_address().unwrap().port();
+        sender.connect(("127.0.0.1", port)).unwrap();
+
+        (provider, sender)
+    }
+
+    fn send(sender: &UdpSocket, address: &str, arguments: Vec<OscArgument>) {
+        let packet = OscPacket::Message(OscMessage::new(address, arguments));
+        sender.send(&packet.encode()).unwrap();
+    }
+
+    fn wait_for_generation(provider: &mut OscProvider, uniform_name: &str, generation: u64) {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        loop {
+            provider.set_time(0.0, true);
+            if provider.get_generation(uniform_name).unwrap_or_default() > generation {
+                return;
+            }
+
+            assert!(
+                Instant::now() < deadline,
+                "No OSC update for {}",
+                uniform_name
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn receives_messages_over_udp() {
+        let (mut provider, sender) = create_provider(HashMap::new());
+
+        send(&sender, "/wvr/fader/1", vec![OscArgument::Float(0.25)]);
+        send(&sender, "/other/fader", vec![OscArgument::Float(1.0)]);
+        wait_for_generation(&mut provider, "osc_fader_1", 0);
+
+        assert_eq!(provider.provides(), vec!["osc_fader_1".to_string()]);
+        assert_eq!(
+            provider.get("osc_fader_1").unwrap(),
+            Some(DataHolder::Float(0.25))
+        );
+    }
+
+    #[test]
+    fn later_messages_keep_the_discovered_type() {
+        let (mut provider, sender) = create_provider(HashMap::new());
+
+        send(&sender, "/wvr/level", vec![OscArgument::Float(0.5)]);
+        wait_for_generation(&mut provider, "osc_level", 0);
+        let generation = provider.get_generation("osc_level").unwrap();
+
+        send(&sender, "/wvr/level", vec![OscArgument::Int(2)]);
+        wait_for_generation(&mut provider, "osc_level", generation);
+        assert_eq!(
+            provider.get("osc_level").unwrap(),
+            Some(DataHolder::Float(2.0))
+        );
+
+        let generation = provider.get_generation("osc_level").unwrap();
+        send(
+            &sender,
+            "/wvr/level",
+            vec![OscArgument::String("loud".to_string())],
+        );
+        send(&sender, "/wvr/marker", Vec::new());
+        wait_for_generation(&mut provider, "osc_marker", 0);
+
+        assert!(matches!(
+            provider.get("osc_level"),
+            Err(InputError::WrongValueType { .. })
+        ));
+        assert_eq!(provider.get_generation("osc_level").unwrap(), generation);
+        assert_eq!(
+            provider.get("osc_level").unwrap(),
+            Some(DataHolder::Float(2.0))
+        );
+    }
+
+    #[test]
+    fn configured_types_take_precedence() {
+        let uniform_types = HashMap::from([("/position".to_string(), DataType::Float2)]);
+        let (mut provider, sender) = create_provider(uniform_types);
+        assert_eq!(provider.provides(), vec!["osc_position".to_string()]);
+
+        send(
+            &sender,
+            "/wvr/position",
+            vec![OscArgument::Int(1), OscArgument::Int(2)],
+        );
+        wait_for_generation(&mut provider, "osc_position", 0);
+
+        assert_eq!(
+            provider.get("osc_position").unwrap(),
+            Some(DataHolder::Float2([1.0, 2.0]))
+        );
+    }
+
+    #[test]
+    fn malformed_packets_are_dropped() {
+        let (mut provider, sender) = create_provider(HashMap::new());
+
+        sender.send(b"garbage").unwrap();
+        sender.send(b"/wvr\0\0\0\0,[[[").unwrap();
+        send(&sender, "/wvr/after", vec![OscArgument::Bool(true)]);
+        wait_for_generation(&mut provider, "osc_after", 0);
+
+        assert_eq!(
+            provider.get("osc_after").unwrap(),
+            Some(DataHolder::Bool(true))
+        );
+    }
+
+    #[test]
+    fn colliding_addresses_are_reported() {
+        let (mut provider, sender) = create_provider(HashMap::new());
+
+        send(&sender, "/wvr/a/b", vec![OscArgument::Float(1.0)]);
+        send(&sender, "/wvr/a_b", vec![OscArgument::Float(2.0)]);
+        send(&sender, "/wvr/after", Vec::new());
+        wait_for_generation(&mut provider, "osc_after", 0);
+
+        assert_eq!(
+            provider.get("osc_a_b"),
+            Err(InputError::UniformCollision {
+                name: "osc_a_b".to_string(),
+                sources: vec!["/a/b".to_string(), "/a_b".to_string()],
+            })
+        );
+        assert_eq!(
+            provider.get("osc_a_b").unwrap(),
+            Some(DataHolder::Float(1.0))
+        );
+
+        let uniform_types = HashMap::from([
+            ("/x/y".to_string(), DataType::Float),
+            ("/x_y".to_string(), DataType::Int),
+        ]);
+        assert!(OscProvider::new("127.0.0.1", 0, "", uniform_types).is_err());
+    }
+
+    #[test]
+    fn scheduled_messages_are_capped() {
+        let (mut provider, _) = create_provider(HashMap::new());
+        let release_time = SystemTime::now() + Duration::from_secs(3600);
+
+        for index in 0..MAX_PENDING_MESSAGES + 1 {
+            let message = OscMessage::new("/wvr/late", vec![OscArgument::Int(index as i32)]);
+            provider.schedule(release_time, message);
+        }
+
+        assert_eq!(provider.pending.len(), MAX_PENDING_MESSAGES);
+        assert_eq!(
+            provider.pending.front().unwrap().1.arguments,
+            [OscArgument::Int(1)]
+        );
+        assert!(matches!(provider.error, Some(InputError::QueueFull(_))));
+        assert!(!provider.error.as_ref().unwrap().is_fatal());
+    }
+
+    #[test]
+    fn due_bundles_apply_in_timetag_order() {
+        let (mut provider, sender) = create_provider(HashMap::new());
+        let now = SystemTime::now();
+
+        // The later bundle arrives first
+        for (delay, value) in [(100, 2), (50, 1)] {
+            let packet = OscPacket::Bundle(OscBundle {
+                timetag: OscTimeTag::from_system_time(now + Duration::from_millis(delay)),
+                content: vec![OscPacket::Message(OscMessage::new(
+                    "/wvr/step",
+                    vec![OscArgument::Int(value)],
+                ))],
+            });
+            sender.send(&packet.encode()).unwrap();
+        }
+
+        thread::sleep(Duration::from_millis(150));
+        wait_for_generation(&mut provider, "osc_step", 0);
+        assert!(provider.pending.is_empty());
+        assert_eq!(
+            provider.get("osc_step").unwrap(),
+            Some(DataHolder::Int(2))
+        );
+    }
+}
@@ -0,0 +1,653 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::types::{
+    DataHolder, DataRange, DataType, InputError, InputEvent, InputProvider, InputResult, KeyEvent,
+    PointerEvent, UniformDescription, UniformStore, UpdateFrequency,
+};
+
+const KEY_TEXTURE_WIDTH: usize = 256;
+
+// Positions come in window pixels with a top-left origin and are exposed in render pixels
+// with a bottom-left origin, like Shadertoy
+#[derive(Clone, Debug, PartialEq)]
+pub struct PointerState {
+    width: f32,
+    height: f32,
+    window_size: (f32, f32),
+    position: [f32; 2],
+    hovering: bool,
+    buttons: u32,
+    click_origin: [f32; 2],
+    last_drag_position: [f32; 2],
+    clicked: bool,
+    wheel: [f32; 2],
+}
+
+impl PointerState {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width: width as f32,
+            height: height as f32,
+            window_size: (width as f32, height as f32),
+            position: [0.0; 2],
+            hovering: false,
+            buttons: 0,
+            click_origin: [0.0; 2],
+            last_drag_position: [0.0; 2],
+            clicked: false,
+            wheel: [0.0; 2],
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &PointerEvent) {
+        match *event {
+            PointerEvent::Resized { width, height } => {
+                self.window_size = (width.max(1.0), height.max(1.0))
+            }
+            PointerEvent::Moved { x, y } => {
+                self.position = [
+                    x / self.window_size.0 * self.width,
+                    (1.0 - y / self.window_size.1) * self.height,
+                ];
+                self.hovering = true;
+                if self.is_down() {
+                    self.last_drag_position = self.position;
+                }
+            }
+            PointerEvent::Pressed { button } => {
+                if !self.is_down() {
+                    self.click_origin = self.position;
+                    self.last_drag_position = self.position;
+                    self.clicked = true;
+                }
+                self.buttons |= 1 << button.min(31);
+            }
+            PointerEvent::Released { button } => self.buttons &= !(1 << button.min(31)),
+            PointerEvent::Scrolled { x, y } => {
+                self.wheel[0] += x;
+                self.wheel[1] += y;
+            }
+            PointerEvent::Left => {
+                self.hovering = false;
+                self.buttons = 0;
+            }
+        }
+    }
+
+    pub fn is_down(&self) -> bool {
+        self.buttons != 0
+    }
+
+    pub fn get_buttons(&self) -> u32 {
+        self.buttons
+    }
+
+    pub fn get_position(&self) -> [f32; 2] {
+        self.position
+    }
+
+    pub fn is_hovering(&self) -> bool {
+        self.hovering
+    }
+
+    pub fn get_wheel(&self) -> [f32; 2] {
+        self.wheel
+    }
+
+    // xy follows the pointer while a button is held, z is negated once released
+    // and w is only positive on the frame of the click
+    pub fn get_mouse(&self) -> [f32; 4] {
+        let [origin_x, origin_y] = self.click_origin;
+        let z = if self.is_down() { origin_x } else { -origin_x };
+        let w = if self.clicked { origin_y } else { -origin_y };
+
+        [self.last_drag_position[0], self.last_drag_position[1], z, w]
+    }
+
+    pub fn end_frame(&mut self) {
+        self.clicked = false;
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyboardState {
+    pressed: Vec<u32>,
+    just_pressed: Vec<u32>,
+    toggled: Vec<u32>,
+    last_key: Option<u32>,
+}
+
+impl KeyboardState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle_event(&mut self, event: &KeyEvent) {
+        match *event {
+            KeyEvent::Pressed(key) => {
+                // Auto-repeat sends presses for held keys, they are not new key presses
+                if self.pressed.contains(&key) {
+                    return;
+                }
+
+                self.pressed.push(key);
+                self.just_pressed.push(key);
+                self.last_key = Some(key);
+                match self
+                    .toggled
+                    .iter()
+                    .position(|toggled_key| *toggled_key == key)
+                {
+                    Some(index) => {
+                        self.toggled.remove(index);
+                    }
+                    None => self.toggled.push(key),
+                }
+            }
+            KeyEvent::Released(key) => self.pressed.retain(|pressed_key| *pressed_key != key),
+            KeyEvent::FocusLost => self.pressed.clear(),
+        }
+    }
+
+    pub fn is_pressed(&self, key: u32) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    pub fn get_pressed(&self) -> &[u32] {
+        &self.pressed
+    }
+
+    pub fn get_last_key(&self) -> Option<u32> {
+        self.last_key
+    }
+
+    // Shadertoy layout: held keys on the first row, keys pressed this frame on the second
+    // and toggled keys on the third
+    pub fn get_texture(&self) -> DataHolder {
+        let mut data = vec![0; KEY_TEXTURE_WIDTH * 3 * 3];
+        for (row, keys) in [&self.pressed, &self.just_pressed, &self.toggled]
+            .iter()
+            .enumerate()
+        {
+            for key in keys
+                .iter()
+                .filter(|key| (**key as usize) < KEY_TEXTURE_WIDTH)
+            {
+                let offset = (row * KEY_TEXTURE_WIDTH + *key as usize) * 3;
+                data[offset..offset + 3].copy_from_slice(&[255; 3]);
+            }
+        }
+
+        DataHolder::Texture(((KEY_TEXTURE_WIDTH as u32, 3), data))
+    }
+
+    pub fn end_frame(&mut self) {
+        self.just_pressed.clear();
+    }
+}
+
+pub struct PointerProvider {
+    name: String,
+    state: PointerState,
+    event_tx: Sender<PointerEvent>,
+    event_rx: Receiver<PointerEvent>,
+    uniforms: UniformStore,
+}
+
+impl PointerProvider {
+    pub fn new(width: usize, height: usize) -> Self {
+        let (event_tx, event_rx) = channel();
+        let mut provider = Self {
+            name: String::new(),
+            state: PointerState::new(width, height),
+            event_tx,
+            event_rx,
+            uniforms: UniformStore::default(),
+        };
+        provider.update();
+
+        provider
+    }
+
+    pub fn get_event_sender(&self) -> Sender<PointerEvent> {
+        self.event_tx.clone()
+    }
+
+    // Shares the queue with the event sender so that both keep their relative order
+    pub fn push_event(&mut self, event: &PointerEvent) {
+        // The receiver lives as long as self, sending cannot fail
+        let _ = self.event_tx.send(*event);
+    }
+
+    pub fn get_state(&self) -> &PointerState {
+        &self.state
+    }
+
+    fn update(&mut self) {
+        while let Ok(event) = self.event_rx.try_recv() {
+            self.state.handle_event(&event);
+        }
+
+        self.uniforms
+            .set_if_changed("mouse", DataHolder::Float4(self.state.get_mouse()));
+        self.uniforms
+            .set_if_changed("position", DataHolder::Float2(self.state.get_position()));
+        self.uniforms
+            .set_if_changed("buttons", DataHolder::Int(self.state.get_buttons() as i32));
+        self.uniforms
+            .set_if_changed("hover", DataHolder::Bool(self.state.is_hovering()));
+        self.uniforms
+            .set_if_changed("wheel", DataHolder::Float2(self.state.get_wheel()));
+        self.state.end_frame();
+    }
+
+    fn get_local_name<'a>(&self, uniform_name: &'a str) -> InputResult<&'a str> {
+        match uniform_name
+            .strip_prefix(&self.name)
+            .and_then(|local_name| local_name.strip_prefix('_'))
+        {
+            Some(local_name @ ("mouse" | "position" | "buttons" | "hover" | "wheel")) => {
+                Ok(local_name)
+            }
+            _ => Err(InputError::UnknownUniform(uniform_name.to_string())),
+        }
+    }
+}
+
+impl InputProvider for PointerProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        vec![
+            format!("{}_mouse", self.name),
+            format!("{}_position", self.name),
+            format!("{}_buttons", self.name),
+            format!("{}_hover", self.name),
+            format!("{}_wheel", self.name),
+        ]
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        vec![
+            UniformDescription::new(
+                &format!("{}_mouse", self.name),
+                DataType::Float4,
+                DataRange::None,
+                UpdateFrequency::OnChange,
+            ),
+            UniformDescription::new(
+                &format!("{}_position", self.name),
+                DataType::Float2,
+                DataRange::None,
+                UpdateFrequency::OnChange,
+            ),
+            UniformDescription::new(
+                &format!("{}_buttons", self.name),
+                DataType::Int,
+                DataRange::None,
+                UpdateFrequency::OnChange,
+            ),
+            UniformDescription::new(
+                &format!("{}_hover", self.name),
+                DataType::Bool,
+                DataRange::None,
+                UpdateFrequency::OnChange,
+            ),
+            UniformDescription::new(
+                &format!("{}_wheel", self.name),
+                DataType::Float2,
+                DataRange::None,
+                UpdateFrequency::OnChange,
+            ),
+        ]
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        let local_name = self.get_local_name(uniform_name)?;
+        Ok(self.uniforms.get_generation(local_name))
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        let local_name = self.get_local_name(uniform_name)?;
+        Ok(self.uniforms.get(local_name))
+    }
+
+    fn set_property(&mut self, property: &str, _value: &DataHolder) -> InputResult<()> {
+        Err(InputError::UnknownProperty(property.to_string()))
+    }
+
+    // Frames are delimited by the clock, events received in between belong to the next frame
+    fn set_time(&mut self, _time: f64, _sync: bool) {
+        self.update();
+    }
+
+    fn handle_event(&mut self, event: &InputEvent) {
+        if let InputEvent::Pointer(event) = event {
+            self.push_event(event);
+        }
+    }
+}
+
+pub struct KeyboardProvider {
+    name: String,
+    state: KeyboardState,
+    event_tx: Sender<KeyEvent>,
+    event_rx: Receiver<KeyEvent>,
+    uniforms: UniformStore,
+}
+
+impl Default for KeyboardProvider {
+    fn default() -> Self {
+        let (event_tx, event_rx) = channel();
+        let mut provider = Self {
+            name: String::new(),
+            state: KeyboardState::new(),
+            event_tx,
+            event_rx,
+            uniforms: UniformStore::default(),
+        };
+        provider.update();
+
+        provider
+    }
+}
+
+impl KeyboardProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_event_sender(&self) -> Sender<KeyEvent> {
+        self.event_tx.clone()
+    }
+
+    // Shares the queue with the event sender so that both keep their relative order
+    pub fn push_event(&mut self, event: &KeyEvent) {
+        // The receiver lives as long as self, sending cannot fail
+        let _ = self.event_tx.send(*event);
+    }
+
+    pub fn get_state(&self) -> &KeyboardState {
+        &self.state
+    }
+
+    fn update(&mut self) {
+        while let Ok(event) = self.event_rx.try_recv() {
+            self.state.handle_event(&event);
+        }
+
+        self.uniforms
+            .set_if_changed("keys", self.state.get_texture());
+        self.uniforms.set_if_changed(
+            "pressed",
+            DataHolder::IntArray(
+                self.state
+                    .get_pressed()
+                    .iter()
+                    .map(|key| *key as i32)
+                    .collect(),
+            ),
+        );
+        self.uniforms.set_if_changed(
+            "last_key",
+            DataHolder::Int(self.state.get_last_key().map_or(-1, |key| key as i32)),
+        );
+        self.state.end_frame();
+    }
+
+    fn get_local_name<'a>(&self, uniform_name: &'a str) -> InputResult<&'a str> {
+        match uniform_name
+            .strip_prefix(&self.name)
+            .and_then(|local_name| local_name.strip_prefix('_'))
+        {
+            Some(local_name @ ("keys" | "pressed" | "last_key")) => Ok(local_name),
+            _ => Err(InputError::UnknownUniform(uniform_name.to_string())),
+        }
+    }
+}
+
+impl InputProvider for KeyboardProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        vec![
+            format!("{}_keys", self.name),
+            format!("{}_pressed", self.name),
+            format!("{}_last_key", self.name),
+        ]
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        vec![
+            UniformDescription::new(
+                &format!("{}_keys", self.name),
+                DataType::Texture,
+                DataRange::None,
+                UpdateFrequency::OnChange,
+            ),
+            UniformDescription::new(
+                &format!("{}_pressed", self.name),
+                DataType::IntArray,
+                DataRange::None,
+                UpdateFrequency::OnChange,
+            ),
+            UniformDescription::new(
+                &format!("{}_last_key", self.name),
+                DataType::Int,
+                DataRange::None,
+                UpdateFrequency::OnChange,
+            ),
+        ]
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        let local_name = self.get_local_name(uniform_name)?;
+        Ok(self.uniforms.get_generation(local_name))
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        let local_name = self.get_local_name(uniform_name)?;
+        Ok(self.uniforms.get(local_name))
+    }
+
+    fn set_property(&mut self, property: &str, _value: &DataHolder) -> InputResult<()> {
+        Err(InputError::UnknownProperty(property.to_string()))
+    }
+
+    fn set_time(&mut self, _time: f64, _sync: bool) {
+        self.update();
+    }
+
+    fn handle_event(&mut self, event: &InputEvent) {
+        if let InputEvent::Key(event) = event {
+            self.push_event(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_key_row(texture: &DataHolder, row: usize, key: u32) -> u8 {
+        match texture {
+            DataHolder::Texture((_, data)) => data[(row * KEY_TEXTURE_WIDTH + key as usize) * 3],
+            _ => panic!("Keyboard texture is not a texture"),
+        }
+    }
+
+    #[test]
+    fn pointer_press_move_release() {
+        let mut state = PointerState::new(200, 100);
+        state.handle_event(&PointerEvent::Resized {
+            width: 400.0,
+            height: 200.0,
+        });
+
+        state.handle_event(&PointerEvent::Moved { x: 100.0, y: 50.0 });
+        assert_eq!(state.get_position(), [50.0, 75.0]);
+        assert!(state.is_hovering());
+        assert_eq!(state.get_mouse(), [0.0, 0.0, -0.0, -0.0]);
+
+        state.handle_event(&PointerEvent::Pressed { button: 0 });
+        assert_eq!(state.get_mouse(), [50.0, 75.0, 50.0, 75.0]);
+        state.end_frame();
+        assert_eq!(state.get_mouse(), [50.0, 75.0, 50.0, -75.0]);
+
+        state.handle_event(&PointerEvent::Moved { x: 200.0, y: 150.0 });
+        assert_eq!(state.get_mouse(), [100.0, 25.0, 50.0, -75.0]);
+
+        state.handle_event(&PointerEvent::Released { button: 0 });
+        state.handle_event(&PointerEvent::Moved { x: 0.0, y: 0.0 });
+        assert!(!state.is_down());
+        assert_eq!(state.get_position(), [0.0, 100.0]);
+        assert_eq!(state.get_mouse(), [100.0, 25.0, -50.0, -75.0]);
+    }
+
+    #[test]
+    fn pointer_buttons_and_leave() {
+        let mut state = PointerState::new(100, 100);
+        state.handle_event(&PointerEvent::Pressed { button: 0 });
+        state.handle_event(&PointerEvent::Pressed { button: 2 });
+        assert_eq!(state.get_buttons(), 0b101);
+
+        state.handle_event(&PointerEvent::Released { button: 0 });
+        assert!(state.is_down());
+
+        state.handle_event(&PointerEvent::Scrolled { x: 0.0, y: 1.0 });
+        state.handle_event(&PointerEvent::Scrolled { x: 0.0, y: 2.0 });
+        assert_eq!(state.get_wheel(), [0.0, 3.0]);
+
+        state.handle_event(&PointerEvent::Left);
+        assert!(!state.is_hovering());
+        assert_eq!(state.get_buttons(), 0);
+    }
+
+    #[test]
+    fn key_repeat_is_not_a_new_press() {
+        let mut state = KeyboardState::new();
+        state.handle_event(&KeyEvent::Pressed(65));
+        state.handle_event(&KeyEvent::Pressed(65));
+        state.handle_event(&KeyEvent::Pressed(65));
+
+        assert_eq!(state.get_pressed(), &[65]);
+        let texture = state.get_texture();
+        assert_eq!(get_key_row(&texture, 0, 65), 255);
+        assert_eq!(get_key_row(&texture, 1, 65), 255);
+        assert_eq!(get_key_row(&texture, 2, 65), 255);
+
+        state.end_frame();
+        state.handle_event(&KeyEvent::Pressed(65));
+        let texture = state.get_texture();
+        assert_eq!(get_key_row(&texture, 0, 65), 255);
+        assert_eq!(get_key_row(&texture, 1, 65), 0);
+        assert_eq!(get_key_row(&texture, 2, 65), 255);
+
+        state.handle_event(&KeyEvent::Released(65));
+        state.handle_event(&KeyEvent::Pressed(65));
+        let texture = state.get_texture();
+        assert_eq!(get_key_row(&texture, 1, 65), 255);
+        assert_eq!(get_key_row(&texture, 2, 65), 0);
+    }
+
+    #[test]
+    fn focus_loss_releases_keys() {
+        let mut state = KeyboardState::new();
+        state.handle_event(&KeyEvent::Pressed(16));
+        state.handle_event(&KeyEvent::Pressed(300));
+        state.handle_event(&KeyEvent::FocusLost);
+
+        assert!(state.get_pressed().is_empty());
+        assert_eq!(state.get_last_key(), Some(300));
+        // Keys outside the texture are still tracked
+        assert_eq!(get_key_row(&state.get_texture(), 2, 16), 255);
+    }
+
+    #[test]
+    fn providers_apply_events_per_frame() {
+        let mut pointer = PointerProvider::new(100, 100);
+        pointer.set_name("pointer");
+        let event_tx = pointer.get_event_sender();
+        event_tx
+            .send(PointerEvent::Moved { x: 10.0, y: 50.0 })
+            .unwrap();
+        pointer.handle_event(&InputEvent::Pointer(PointerEvent::Pressed { button: 1 }));
+        pointer.handle_event(&InputEvent::Key(KeyEvent::Pressed(32)));
+
+        pointer.set_time(0.0, true);
+        assert_eq!(
+            pointer.get("pointer_buttons").unwrap(),
+            Some(DataHolder::Int(2))
+        );
+        assert_eq!(
+            pointer.get("pointer_position").unwrap(),
+            Some(DataHolder::Float2([10.0, 50.0]))
+        );
+
+        let mut keyboard = KeyboardProvider::new();
+        keyboard.set_name("keyboard");
+        keyboard.handle_event(&InputEvent::Key(KeyEvent::Pressed(32)));
+        keyboard.handle_event(&InputEvent::Key(KeyEvent::Pressed(32)));
+        keyboard.set_time(0.0, true);
+        let generation = keyboard.get_generation("keyboard_keys").unwrap();
+
+        assert_eq!(
+            keyboard.get("keyboard_pressed").unwrap(),
+            Some(DataHolder::IntArray(vec![32]))
+        );
+        assert_eq!(
+            keyboard.get("keyboard_last_key").unwrap(),
+            Some(DataHolder::Int(32))
+        );
+
+        // The just pressed row clears on the next frame
+        keyboard.set_time(1.0, true);
+        assert!(keyboard.get_generation("keyboard_keys").unwrap() > generation);
+        let texture = keyboard.get("keyboard_keys").unwrap().unwrap();
+        assert_eq!(get_key_row(&texture, 1, 32), 0);
+    }
+
+    #[test]
+    fn sent_and_pushed_events_keep_their_order() {
+        let mut pointer = PointerProvider::new(100, 100);
+        pointer.set_name("pointer");
+        let event_tx = pointer.get_event_sender();
+
+        event_tx
+            .send(PointerEvent::Moved { x: 10.0, y: 50.0 })
+            .unwrap();
+        pointer.handle_event(&InputEvent::Pointer(PointerEvent::Pressed { button: 0 }));
+        event_tx
+            .send(PointerEvent::Moved { x: 20.0, y: 50.0 })
+            .unwrap();
+        assert_eq!(pointer.get_state().get_buttons(), 0);
+
+        pointer.set_time(0.0, true);
+        assert_eq!(
+            pointer.get("pointer_mouse").unwrap(),
+            Some(DataHolder::Float4([20.0, 50.0, 10.0, 50.0]))
+        );
+
+        let mut keyboard = KeyboardProvider::new();
+        keyboard.set_name("keyboard");
+        keyboard
+            .get_event_sender()
+            .send(KeyEvent::Pressed(65))
+            .unwrap();
+        keyboard.handle_event(&InputEvent::Key(KeyEvent::Released(65)));
+        keyboard.set_time(0.0, true);
+
+        assert_eq!(
+            keyboard.get("keyboard_pressed").unwrap(),
+            Some(DataHolder::IntArray(vec![]))
+        );
+        assert_eq!(
+            keyboard.get("keyboard_last_key").unwrap(),
+            Some(DataHolder::Int(65))
+        );
+    }
+}
@@ -0,0 +1,517 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::types::{
+    DataHolder, InputError, InputEvent, InputProvider, InputResult, PropertyDescription,
+    UniformDescription,
+};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum ReplayClock {
+    Time,
+    Beat,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum RecordEntry {
+    Provides(Vec<UniformDescription>),
+    Value {
+        time: f64,
+        beat: f64,
+        uniform: String,
+        value: DataHolder,
+    },
+}
+
+pub struct RecordingProvider {
+    provider: Box<dyn InputProvider + Send>,
+    writer: BufWriter<File>,
+    recorded_generations: HashMap<String, u64>,
+    time: f64,
+    beat: f64,
+}
+
+impl RecordingProvider {
+    pub fn new(provider: Box<dyn InputProvider + Send>, path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create input recording: {:?}", path))?;
+
+        let mut recording_provider = Self {
+            provider,
+            writer: BufWriter::new(file),
+            recorded_generations: HashMap::new(),
+            time: 0.0,
+            beat: 0.0,
+        };
+        recording_provider.write_description()?;
+
+        Ok(recording_provider)
+    }
+
+    pub fn into_inner(mut self) -> Result<Box<dyn InputProvider + Send>> {
+        self.writer
+            .flush()
+            .context("Failed to flush input recording")?;
+
+        Ok(self.provider)
+    }
+
+    fn write_description(&mut self) -> InputResult<()> {
+        write_entry(
+            &mut self.writer,
+            &RecordEntry::Provides(self.provider.describe()),
+        )
+    }
+}
+
+fn write_entry(writer: &mut BufWriter<File>, entry: &RecordEntry) -> InputResult<()> {
+    serde_json::to_writer(&mut *writer, entry)
+        .map_err(|e| InputError::Other(format!("Failed to record input: {}", e)))?;
+    writer.write_all(b"\n")?;
+
+    Ok(())
+}
+
+impl InputProvider for RecordingProvider {
+    fn set_name(&mut self, name: &str) {
+        self.provider.set_name(name);
+        let _ = self.write_description();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.provider.provides()
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        self.provider.describe()
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        self.provider.get_generation(uniform_name)
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        let generation = self.provider.get_generation(uniform_name)?;
+        let recorded_generation = self.recorded_generations.get(uniform_name).copied();
+        let value = self.provider.borrow(uniform_name)?;
+
+        if let Some(value) = value.filter(|_| recorded_generation != Some(generation)) {
+            let entry = RecordEntry::Value {
+                time: self.time,
+                beat: self.beat,
+                uniform: uniform_name.to_string(),
+                value: value.clone(),
+            };
+            write_entry(&mut self.writer, &entry)?;
+            self.recorded_generations
+                .insert(uniform_name.to_string(), generation);
+        }
+
+        Ok(value)
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()> {
+        self.provider.set_property(property, value)
+    }
+
+    fn list_properties(&self) -> Vec<PropertyDescription> {
+        self.provider.list_properties()
+    }
+
+    fn get_property(&self, property: &str) -> InputResult<DataHolder> {
+        self.provider.get_property(property)
+    }
+
+    fn set_beat(&mut self, beat: f64, sync: bool) {
+        self.beat = beat;
+        self.provider.set_beat(beat, sync);
+    }
+
+    fn set_time(&mut self, time: f64, sync: bool) {
+        self.time = time;
+        self.provider.set_time(time, sync);
+    }
+
+    fn handle_event(&mut self, event: &InputEvent) {
+        self.provider.handle_event(event);
+    }
+
+    fn is_seekable(&self) -> bool {
+        self.provider.is_seekable()
+    }
+
+    fn seek_time(&mut self, time: f64) -> InputResult<()> {
+        self.provider.seek_time(time)
+    }
+
+    fn seek_beat(&mut self, beat: f64) -> InputResult<()> {
+        self.provider.seek_beat(beat)
+    }
+
+    fn get_duration(&self) -> Option<f64> {
+        self.provider.get_duration()
+    }
+
+    fn get_position(&self) -> Option<f64> {
+        self.provider.get_position()
+    }
+
+    fn is_looping(&self) -> bool {
+        self.provider.is_looping()
+    }
+
+    fn set_looping(&mut self, looping: bool) -> InputResult<()> {
+        self.provider.set_looping(looping)
+    }
+
+    fn stop(&mut self) -> InputResult<()> {
+        self.writer.flush()?;
+        self.provider.stop()
+    }
+
+    fn pause(&mut self) -> InputResult<()> {
+        self.writer.flush()?;
+        self.provider.pause()
+    }
+
+    fn play(&mut self) -> InputResult<()> {
+        self.provider.play()
+    }
+}
+
+pub struct ReplayProvider {
+    clock: ReplayClock,
+    description: Vec<UniformDescription>,
+    values: HashMap<String, Vec<(f64, DataHolder)>>,
+    // Current value index and generation of each uniform, generations come from a single
+    // counter so that they keep increasing when a seek moves back
+    current: HashMap<String, (Option<usize>, u64)>,
+    last_generation: u64,
+    time: f64,
+    beat: f64,
+}
+
+impl ReplayProvider {
+    pub fn new(path: &Path, clock: ReplayClock) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open input recording: {:?}", path))?;
+
+        let mut description = Vec::new();
+        let mut values: HashMap<String, Vec<(f64, DataHolder)>> = HashMap::new();
+
+        for (line_index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.context("Failed to read input recording")?;
+            if line.is_empty() {
+                continue;
+            }
+
+            let entry: RecordEntry = serde_json::from_str(&line).with_context(|| {
+                format!("Invalid input recording entry at line {}", line_index + 1)
+            })?;
+
+            match entry {
+                RecordEntry::Provides(provides) => description = provides,
+                RecordEntry::Value {
+                    time,
+                    beat,
+                    uniform,
+                    value,
+                } => {
+                    let stamp = match clock {
+                        ReplayClock::Time => time,
+                        ReplayClock::Beat => beat,
+                    };
+                    values.entry(uniform).or_default().push((stamp, value));
+                }
+            }
+        }
+
+        for uniform_values in values.values_mut() {
+            // Stable sort so that values recorded on the same stamp replay in order
+            uniform_values.sort_by(|(left, _), (right, _)| left.total_cmp(right));
+        }
+
+        // Each Provides entry replaces the description, so a renamed provider is described by
+        // its last name, values recorded under earlier names are listed as unknown uniforms
+        let mut uniform_names = HashSet::new();
+        description
+            .retain(|uniform: &UniformDescription| uniform_names.insert(uniform.name.clone()));
+        let mut unlisted_names: Vec<&String> = values
+            .keys()
+            .filter(|uniform_name| !uniform_names.contains(*uniform_name))
+            .collect();
+        unlisted_names.sort_unstable();
+        description.extend(
+            unlisted_names
+                .into_iter()
+                .map(|uniform_name| UniformDescription::unknown(uniform_name)),
+        );
+
+        let mut provider = Self {
+            clock,
+            description,
+            values,
+            current: HashMap::new(),
+            last_generation: 0,
+            time: 0.0,
+            beat: 0.0,
+        };
+        provider.update_current();
+
+        Ok(provider)
+    }
+
+    fn get_stamp(&self) -> f64 {
+        match self.clock {
+            ReplayClock::Time => self.time,
+            ReplayClock::Beat => self.beat,
+        }
+    }
+
+    fn update_current(&mut self) {
+        let stamp = self.get_stamp();
+        for (uniform_name, uniform_values) in &self.values {
+            let value_count =
+                uniform_values.partition_point(|(value_stamp, _)| *value_stamp <= stamp);
+            let index = value_count.checked_sub(1);
+
+            let current = self
+                .current
+                .entry(uniform_name.clone())
+                .or_insert((None, 0));
+            if current.0 != index {
+                self.last_generation += 1;
+                *current = (index, self.last_generation);
+            }
+        }
+    }
+
+    fn get_current(&self, uniform_name: &str) -> InputResult<(Option<usize>, u64)> {
+        match self.current.get(uniform_name) {
+            Some(current) => Ok(*current),
+            None if self
+                .description
+                .iter()
+                .any(|description| description.name == uniform_name) =>
+            {
+                Ok((None, 0))
+            }
+            None => Err(InputError::UnknownUniform(uniform_name.to_string())),
+        }
+    }
+}
+
+impl InputProvider for ReplayProvider {
+    // Recorded uniforms keep the names they were captured with
+    fn set_name(&mut self, _name: &str) {}
+
+    fn provides(&self) -> Vec<String> {
+        self.description
+            .iter()
+            .map(|description| description.name.clone())
+            .collect()
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        self.description.clone()
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        Ok(self.get_current(uniform_name)?.1)
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        let index = match self.get_current(uniform_name)?.0 {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        Ok(self
+            .values
+            .get(uniform_name)
+            .map(|uniform_values| &uniform_values[index].1))
+    }
+
+    fn set_property(&mut self, property: &str, _value: &DataHolder) -> InputResult<()> {
+        Err(InputError::UnknownProperty(property.to_string()))
+    }
+
+    fn set_beat(&mut self, beat: f64, _sync: bool) {
+        self.beat = beat;
+        self.update_current();
+    }
+
+    fn set_time(&mut self, time: f64, _sync: bool) {
+        self.time = time;
+        self.update_current();
+    }
+
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn seek_time(&mut self, time: f64) -> InputResult<()> {
+        self.time = time;
+        self.update_current();
+        Ok(())
+    }
+
+    fn seek_beat(&mut self, beat: f64) -> InputResult<()> {
+        self.beat = beat;
+        self.update_current();
+        Ok(())
+    }
+
+    // Durations and positions are in the unit of the replay clock
+    fn get_duration(&self) -> Option<f64> {
+        self.values
+            .values()
+            .filter_map(|uniform_values| uniform_values.last().map(|(stamp, _)| *stamp))
+            .fold(None, |duration: Option<f64>, stamp| {
+                Some(duration.map_or(stamp, |duration| duration.max(stamp)))
+            })
+    }
+
+    fn get_position(&self) -> Option<f64> {
+        Some(self.get_stamp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DataRange, DataType, UniformStore, UpdateFrequency};
+
+    // Steps through a new value every half second
+    #[derive(Default)]
+    struct StepProvider {
+        name: String,
+        uniforms: UniformStore,
+    }
+
+    impl InputProvider for StepProvider {
+        fn set_name(&mut self, name: &str) {
+            self.name = name.to_string();
+        }
+
+        fn provides(&self) -> Vec<String> {
+            vec![format!("{}_step", self.name)]
+        }
+
+        fn describe(&self) -> Vec<UniformDescription> {
+            vec![UniformDescription::new(
+                &format!("{}_step", self.name),
+                DataType::Float,
+                DataRange::None,
+                UpdateFrequency::OnChange,
+            )]
+        }
+
+        fn get_generation(&self, _uniform_name: &str) -> InputResult<u64> {
+            Ok(self.uniforms.get_generation("step"))
+        }
+
+        fn borrow(&mut self, _uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+            Ok(self.uniforms.get("step"))
+        }
+
+        fn set_property(&mut self, property: &str, _value: &DataHolder) -> InputResult<()> {
+            Err(InputError::UnknownProperty(property.to_string()))
+        }
+
+        fn set_time(&mut self, time: f64, _sync: bool) {
+            self.uniforms
+                .set_if_changed("step", DataHolder::Float((time * 2.0).floor() as f32));
+        }
+    }
+
+    fn get_times() -> Vec<f64> {
+        (0..12).map(|index| index as f64 * 0.25).collect()
+    }
+
+    fn record(path: &Path) -> Vec<Option<DataHolder>> {
+        let mut recorder = RecordingProvider::new(Box::<StepProvider>::default(), path).unwrap();
+        recorder.set_name("steps");
+
+        let values = get_times()
+            .into_iter()
+            .map(|time| {
+                recorder.set_time(time, true);
+                recorder.set_beat(time * 2.0, true);
+                recorder.get("steps_step").unwrap()
+            })
+            .collect();
+        recorder.into_inner().unwrap();
+
+        values
+    }
+
+    #[test]
+    fn replay_matches_the_recording() {
+        let path = std::env::temp_dir().join(format!("wvr-record-{}.jsonl", std::process::id()));
+        let values = record(&path);
+
+        for clock in [ReplayClock::Time, ReplayClock::Beat] {
+            let mut replay = ReplayProvider::new(&path, clock).unwrap();
+            assert_eq!(replay.provides(), ["steps_step"]);
+            assert_eq!(replay.describe()[0].data_type, Some(DataType::Float));
+
+            for (time, value) in get_times().into_iter().zip(values.iter()) {
+                replay.set_time(time, true);
+                replay.set_beat(time * 2.0, true);
+                assert_eq!(
+                    &replay.get("steps_step").unwrap(),
+                    value,
+                    "{:?} at {}",
+                    clock,
+                    time
+                );
+            }
+            assert_eq!(
+                replay.get_duration(),
+                Some(match clock {
+                    ReplayClock::Time => 2.5,
+                    ReplayClock::Beat => 5.0,
+                })
+            );
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn generations_increase_across_seeks() {
+        let path = std::env::temp_dir().join(format!("wvr-seek-{}.jsonl", std::process::id()));
+        record(&path);
+
+        let mut replay = ReplayProvider::new(&path, ReplayClock::Time).unwrap();
+        let mut last_generation = replay.get_generation("steps_step").unwrap();
+        for time in [1.0, 1.2, 2.5, 0.0, 1.0, -1.0, 0.5] {
+            let value = replay.get("steps_step").unwrap();
+            replay.seek_time(time).unwrap();
+
+            let generation = replay.get_generation("steps_step").unwrap();
+            if replay.get("steps_step").unwrap() == value {
+                assert_eq!(generation, last_generation, "at {}", time);
+            } else {
+                assert!(generation > last_generation, "at {}", time);
+            }
+            last_generation = generation;
+        }
+
+        assert_eq!(
+            replay.get("steps_step").unwrap(),
+            Some(DataHolder::Float(1.0))
+        );
+        assert!(matches!(
+            replay.get_generation("steps_missing"),
+            Err(InputError::UnknownUniform(_))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
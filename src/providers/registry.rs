@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::input::InputConfig;
+use crate::types::{InputError, InputProvider, InputResult};
+
+#[cfg(any(unix, windows))]
+use super::IpcProvider;
+use super::{
+    find_midi_device, AudioAnalysisProvider, DataFileProvider, GeneratorProvider,
+    ImageSequenceProvider, KeyboardProvider, MidiProvider, OscProvider, PointerProvider,
+    ScriptProvider, TextProvider,
+};
+
+// Only reached when a constructor is registered under another kind or called directly
+fn get_mismatch_error(kind: &str, config: &InputConfig) -> InputError {
+    InputError::ConfigMismatch {
+        expected: kind.to_string(),
+        found: config.get_type_tag().to_string(),
+    }
+}
+
+pub type InputConstructor =
+    Box<dyn Fn(&InputConfig) -> InputResult<Box<dyn InputProvider + Send>> + Send + Sync>;
+
+#[derive(Default)]
+pub struct InputRegistry {
+    constructors: HashMap<String, InputConstructor>,
+    // Custom kinds live apart so that they can never shadow or reach a built-in type
+    custom_constructors: HashMap<String, InputConstructor>,
+}
+
+impl InputRegistry {
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::default();
+
+        registry.register("Audio", |config| match config {
+            InputConfig::Audio {
+                path,
+                fft_size,
+                band_count,
+                bpm,
+                looping,
+            } => {
+                let mut provider =
+                    AudioAnalysisProvider::from_file(Path::new(path), *fft_size, *band_count)?;
+                provider.set_beat_sync(*bpm);
+                provider.set_looping(*looping)?;
+                Ok(Box::new(provider))
+            }
+            _ => Err(get_mismatch_error("Audio", config)),
+        });
+
+        registry.register("Generator", |config| match config {
+            InputConfig::Generator {
+                width,
+                height,
+                pattern,
+                overlay,
+            } => Ok(Box::new(GeneratorProvider::new(
+                *width, *height, *pattern, *overlay,
+            )?)),
+            _ => Err(get_mismatch_error("Generator", config)),
+        });
+
+        registry.register("ImageSequence", |config| match config {
+            InputConfig::ImageSequence {
+                path,
+                width,
+                height,
+                speed,
+                playback,
+                preload,
+            } => Ok(Box::new(ImageSequenceProvider::new(
+                path,
+                *width,
+                *height,
+                *speed,
+                playback.clone(),
+                *preload,
+            )?)),
+            _ => Err(get_mismatch_error("ImageSequence", config)),
+        });
+
+        registry.register("Midi", |config| match config {
+            InputConfig::Midi { name, mappings } => {
+                let mut provider = MidiProvider::open(&find_midi_device(name)?)?;
+                provider.set_mappings(mappings.clone());
+                Ok(Box::new(provider))
+            }
+            _ => Err(get_mismatch_error("Midi", config)),
+        });
+
+        registry.register("Osc", |config| match config {
+            InputConfig::Osc {
+                address,
+                port,
+                prefix,
+                uniforms,
+            } => Ok(Box::new(OscProvider::new(
+                address,
+                *port,
+                prefix,
+                uniforms.clone(),
+            )?)),
+            _ => Err(get_mismatch_error("Osc", config)),
+        });
+
+        registry.register("Text", |config| match config {
+            InputConfig::Text {
+                width,
+                height,
+                text,
+                style,
+            } => Ok(Box::new(TextProvider::new(
+                *width,
+                *height,
+                text,
+                style.clone(),
+            )?)),
+            _ => Err(get_mismatch_error("Text", config)),
+        });
+
+        // The windowing layer feeds these through InputProvider::handle_event
+        registry.register("Pointer", |config| match config {
+            InputConfig::Pointer { width, height } => {
+                Ok(Box::new(PointerProvider::new(*width, *height)))
+            }
+            _ => Err(get_mismatch_error("Pointer", config)),
+        });
+
+        registry.register("Keyboard", |config| match config {
+            InputConfig::Keyboard {} => Ok(Box::new(KeyboardProvider::new())),
+            _ => Err(get_mismatch_error("Keyboard", config)),
+        });
+
+        #[cfg(any(unix, windows))]
+        registry.register("Ipc", |config| match config {
+            InputConfig::Ipc { path } => Ok(Box::new(IpcProvider::new(path)?)),
+            _ => Err(get_mismatch_error("Ipc", config)),
+        });
+
+        registry.register("Script", |config| match config {
+            InputConfig::Script {
+                path,
+                live_reload,
+                properties,
+                uniforms,
+            } => Ok(Box::new(ScriptProvider::new(
+                PathBuf::from(path),
+                *live_reload,
+                properties.clone(),
+                uniforms.clone(),
+            )?)),
+            _ => Err(get_mismatch_error("Script", config)),
+        });
+
+        registry.register("DataFile", |config| match config {
+            InputConfig::DataFile {
+                path,
+                time_column,
+                clock,
+                interpolation,
+                rate,
+                columns,
+                playback,
+            } => Ok(Box::new(DataFileProvider::from_file(
+                Path::new(path),
+                time_column,
+                columns,
+                *clock,
+                *interpolation,
+                *rate,
+                playback.clone(),
+            )?)),
+            _ => Err(get_mismatch_error("DataFile", config)),
+        });
+
+        registry
+    }
+
+    pub fn register<F>(&mut self, kind: &str, constructor: F) -> Option<InputConstructor>
+    where
+        F: Fn(&InputConfig) -> InputResult<Box<dyn InputProvider + Send>> + Send + Sync + 'static,
+    {
+        self.constructors
+            .insert(kind.to_string(), Box::new(constructor))
+    }
+
+    pub fn register_custom<F>(&mut self, kind: &str, constructor: F) -> Option<InputConstructor>
+    where
+        F: Fn(&InputConfig) -> InputResult<Box<dyn InputProvider + Send>> + Send + Sync + 'static,
+    {
+        self.custom_constructors
+            .insert(kind.to_string(), Box::new(constructor))
+    }
+
+    pub fn unregister(&mut self, kind: &str) -> Option<InputConstructor> {
+        self.constructors.remove(kind)
+    }
+
+    pub fn unregister_custom(&mut self, kind: &str) -> Option<InputConstructor> {
+        self.custom_constructors.remove(kind)
+    }
+
+    pub fn contains(&self, kind: &str) -> bool {
+        self.constructors.contains_key(kind)
+    }
+
+    pub fn contains_custom(&self, kind: &str) -> bool {
+        self.custom_constructors.contains_key(kind)
+    }
+
+    pub fn get_kinds(&self) -> Vec<&str> {
+        let mut kinds: Vec<&str> = self.constructors.keys().map(String::as_str).collect();
+        kinds.sort_unstable();
+
+        kinds
+    }
+
+    pub fn get_custom_kinds(&self) -> Vec<&str> {
+        let mut kinds: Vec<&str> = self
+            .custom_constructors
+            .keys()
+            .map(String::as_str)
+            .collect();
+        kinds.sort_unstable();
+
+        kinds
+    }
+
+    pub fn create(
+        &self,
+        name: &str,
+        config: &InputConfig,
+    ) -> InputResult<Box<dyn InputProvider + Send>> {
+        let kind = config.get_type_tag();
+        let constructors = if config.is_custom() {
+            &self.custom_constructors
+        } else {
+            &self.constructors
+        };
+        let constructor = constructors
+            .get(kind)
+            .ok_or_else(|| InputError::UnknownInputType(kind.to_string()))?;
+
+        let mut provider = constructor(config)?;
+        provider.set_name(name);
+
+        Ok(provider)
+    }
+
+    pub fn create_all(
+        &self,
+        configs: &HashMap<String, InputConfig>,
+    ) -> InputResult<HashMap<String, Box<dyn InputProvider + Send>>> {
+        configs
+            .iter()
+            .map(|(name, config)| Ok((name.clone(), self.create(name, config)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_error(result: InputResult<Box<dyn InputProvider + Send>>) -> InputError {
+        match result {
+            Ok(_) => panic!("Expected the constructor to fail"),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn constructors_only_accept_their_own_config() {
+        let mut registry = InputRegistry::with_defaults();
+        let provider = registry.create("keys", &InputConfig::Keyboard {}).unwrap();
+        assert!(!provider.provides().is_empty());
+
+        let pointer = registry.unregister("Pointer").unwrap();
+        assert_eq!(
+            get_error(pointer(&InputConfig::Keyboard {})),
+            InputError::ConfigMismatch {
+                expected: "Pointer".to_string(),
+                found: "Keyboard".to_string(),
+            }
+        );
+
+        registry.register("Keyboard", pointer);
+        assert!(matches!(
+            get_error(registry.create("keys", &InputConfig::Keyboard {})),
+            InputError::ConfigMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn custom_kinds_are_kept_apart() {
+        let mut registry = InputRegistry::with_defaults();
+        let config = InputConfig::Custom {
+            kind: "Keyboard".to_string(),
+            params: HashMap::new(),
+        };
+        assert_eq!(
+            get_error(registry.create("custom", &config)),
+            InputError::UnknownInputType("Keyboard".to_string())
+        );
+
+        registry.register_custom("Keyboard", |_| Ok(Box::new(KeyboardProvider::new())));
+        assert!(registry.create("custom", &config).is_ok());
+        assert_eq!(registry.get_custom_kinds(), ["Keyboard"]);
+    }
+}
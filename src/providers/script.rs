@@ -0,0 +1,710 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+
+use crate::types::{
+    DataHolder, DataRange, DataType, InputError, InputProvider, InputResult, PropertyDescription,
+    UniformDescription, UniformStore, UpdateFrequency,
+};
+
+// Keeps a runaway script from freezing the render loop
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+fn to_array<T: Clone + Into<Dynamic>>(values: &[T]) -> Dynamic {
+    values
+        .iter()
+        .cloned()
+        .map(Into::into)
+        .collect::<Array>()
+        .into()
+}
+
+pub fn data_holder_to_dynamic(value: &DataHolder) -> Dynamic {
+    let to_f64 = |values: &[f32]| {
+        values
+            .iter()
+            .map(|value| *value as f64)
+            .collect::<Vec<f64>>()
+    };
+    let to_i64 = |values: &[i32]| {
+        values
+            .iter()
+            .map(|value| *value as i64)
+            .collect::<Vec<i64>>()
+    };
+
+    match value {
+        DataHolder::Float(value) => (*value as f64).into(),
+        DataHolder::Float2(values) => to_array(&to_f64(values)),
+        DataHolder::Float3(values) => to_array(&to_f64(values)),
+        DataHolder::Float4(values) => to_array(&to_f64(values)),
+        DataHolder::FloatArray(values) => to_array(&to_f64(values)),
+
+        DataHolder::Int(value) => (*value as i64).into(),
+        DataHolder::Int2(values) => to_array(&to_i64(values)),
+        DataHolder::Int3(values) => to_array(&to_i64(values)),
+        DataHolder::Int4(values) => to_array(&to_i64(values)),
+        DataHolder::IntArray(values) => to_array(&to_i64(values)),
+
+        DataHolder::Mat2(rows) => rows
+            .iter()
+            .map(|row| to_array(&to_f64(row)))
+            .collect::<Array>()
+            .into(),
+        DataHolder::Mat3(rows) => rows
+            .iter()
+            .map(|row| to_array(&to_f64(row)))
+            .collect::<Array>()
+            .into(),
+        DataHolder::Mat4(rows) => rows
+            .iter()
+            .map(|row| to_array(&to_f64(row)))
+            .collect::<Array>()
+            .into(),
+
+        DataHolder::Bool(value) => (*value).into(),
+        DataHolder::BoolArray(values) => to_array(values),
+
+        DataHolder::ByteArray(values) => Dynamic::from_blob(values.clone()),
+
+        DataHolder::String(value) => value.clone().into(),
+
+        DataHolder::Texture(_) | DataHolder::SrgbTexture(_) => Dynamic::UNIT,
+    }
+}
+
+fn as_number(value: &Dynamic) -> Option<f64> {
+    value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|value| value as f64))
+}
+
+// Rhai integers are inferred as floats so that a script returning `0` on its first frame
+// does not lock the uniform to Int, integer uniforms have to be declared
+fn infer_type(value: &Dynamic) -> Option<DataType> {
+    if value.is_bool() {
+        return Some(DataType::Bool);
+    } else if as_number(value).is_some() {
+        return Some(DataType::Float);
+    } else if value.is_string() {
+        return Some(DataType::String);
+    } else if value.is_blob() {
+        return Some(DataType::ByteArray);
+    }
+
+    let values = value.read_lock::<Array>()?;
+    if !values.is_empty() && values.iter().all(Dynamic::is_array) {
+        return match values.len() {
+            2 => Some(DataType::Mat2),
+            3 => Some(DataType::Mat3),
+            4 => Some(DataType::Mat4),
+            _ => None,
+        };
+    }
+
+    if !values.is_empty() && values.iter().all(Dynamic::is_bool) {
+        Some(DataType::BoolArray)
+    } else if values.iter().all(|value| as_number(value).is_some()) {
+        Some(match values.len() {
+            2 => DataType::Float2,
+            3 => DataType::Float3,
+            4 => DataType::Float4,
+            _ => DataType::FloatArray,
+        })
+    } else {
+        None
+    }
+}
+
+fn flatten_numbers(value: &Dynamic, numbers: &mut Vec<f64>) -> Option<()> {
+    match value.read_lock::<Array>() {
+        Some(values) => {
+            for value in values.iter() {
+                flatten_numbers(value, numbers)?;
+            }
+        }
+        None => numbers.push(as_number(value)?),
+    }
+
+    Some(())
+}
+
+fn to_matrix<const N: usize>(numbers: &[f64]) -> Option<[[f32; N]; N]> {
+    if numbers.len() != N * N {
+        return None;
+    }
+
+    let mut matrix = [[0.0; N]; N];
+    for (index, value) in numbers.iter().enumerate() {
+        matrix[index / N][index % N] = *value as f32;
+    }
+
+    Some(matrix)
+}
+
+pub fn dynamic_to_data_holder(value: &Dynamic, data_type: Option<DataType>) -> Option<DataHolder> {
+    let data_type = match data_type {
+        Some(data_type) => data_type,
+        None => infer_type(value)?,
+    };
+
+    match data_type {
+        DataType::Bool => return value.as_bool().ok().map(DataHolder::Bool),
+        DataType::BoolArray => {
+            return value
+                .read_lock::<Array>()?
+                .iter()
+                .map(|value| value.as_bool().ok())
+                .collect::<Option<Vec<bool>>>()
+                .map(DataHolder::BoolArray)
+        }
+        DataType::String => return value.clone().into_string().ok().map(DataHolder::String),
+        DataType::ByteArray if value.is_blob() => {
+            return value.clone().into_blob().ok().map(DataHolder::ByteArray)
+        }
+        DataType::Texture | DataType::SrgbTexture => return None,
+        _ => (),
+    }
+
+    let mut numbers = Vec::new();
+    flatten_numbers(value, &mut numbers)?;
+    let floats: Vec<f32> = numbers.iter().map(|value| *value as f32).collect();
+    let ints: Vec<i32> = numbers.iter().map(|value| value.round() as i32).collect();
+
+    let holder = match data_type {
+        DataType::Float if floats.len() == 1 => DataHolder::Float(floats[0]),
+        DataType::Float2 => DataHolder::Float2(floats.try_into().ok()?),
+        DataType::Float3 => DataHolder::Float3(floats.try_into().ok()?),
+        DataType::Float4 => DataHolder::Float4(floats.try_into().ok()?),
+        DataType::FloatArray => DataHolder::FloatArray(floats),
+
+        DataType::Int if ints.len() == 1 => DataHolder::Int(ints[0]),
+        DataType::Int2 => DataHolder::Int2(ints.try_into().ok()?),
+        DataType::Int3 => DataHolder::Int3(ints.try_into().ok()?),
+        DataType::Int4 => DataHolder::Int4(ints.try_into().ok()?),
+        DataType::IntArray => DataHolder::IntArray(ints),
+
+        DataType::Mat2 => DataHolder::Mat2(to_matrix(&numbers)?),
+        DataType::Mat3 => DataHolder::Mat3(to_matrix(&numbers)?),
+        DataType::Mat4 => DataHolder::Mat4(to_matrix(&numbers)?),
+
+        DataType::ByteArray => DataHolder::ByteArray(
+            numbers
+                .iter()
+                .map(|value| value.clamp(0.0, 255.0) as u8)
+                .collect(),
+        ),
+
+        _ => return None,
+    };
+
+    Some(holder)
+}
+
+fn read_script(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Failed to read script: {:?}", path))
+}
+
+// The script is evaluated on the first read after `time`, `beat` or `properties`, all in
+// scope, change and must evaluate to a map of uniform values, `state` is a map kept between
+// evaluations. Script errors are reported without stopping the input, the last good values
+// are kept
+pub struct ScriptProvider {
+    name: String,
+    path: PathBuf,
+    engine: Engine,
+    live_reload: bool,
+    _file_watcher: RecommendedWatcher,
+    file_change_rx: Receiver<DebouncedEvent>,
+    time: f64,
+    beat: f64,
+    properties: HashMap<String, DataHolder>,
+    // Generations are polled through a shared reference, which must run a pending evaluation
+    evaluation: RefCell<ScriptEvaluation>,
+}
+
+struct ScriptEvaluation {
+    ast: AST,
+    scope: Scope<'static>,
+    uniform_types: BTreeMap<String, DataType>,
+    dirty: bool,
+    error: Option<InputError>,
+    uniforms: UniformStore,
+}
+
+impl ScriptProvider {
+    pub fn new(
+        path: PathBuf,
+        live_reload: bool,
+        properties: HashMap<String, DataHolder>,
+        uniforms: HashMap<String, DataType>,
+    ) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        let ast = engine
+            .compile(read_script(&path)?)
+            .map_err(|e| anyhow::anyhow!("Failed to compile script {:?}: {}", path, e))?;
+
+        // Editors that save by renaming a new file over the script replace the watched
+        // inode, so the parent directory is watched instead
+        let watch_path = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let (tx, file_change_rx) = channel();
+        let mut file_watcher: RecommendedWatcher = Watcher::new(tx, Duration::from_millis(1))?;
+        file_watcher.watch(&watch_path, RecursiveMode::NonRecursive)?;
+
+        let mut scope = Scope::new();
+        scope.push("state", Map::new());
+
+        Ok(Self {
+            name: String::new(),
+            path,
+            engine,
+            live_reload,
+            _file_watcher: file_watcher,
+            file_change_rx,
+            time: 0.0,
+            beat: 0.0,
+            properties,
+            evaluation: RefCell::new(ScriptEvaluation {
+                ast,
+                scope,
+                uniform_types: uniforms.into_iter().collect(),
+                dirty: true,
+                error: None,
+                uniforms: UniformStore::default(),
+            }),
+        })
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn check_changes(&self) -> Result<bool> {
+        if !self.live_reload {
+            return Ok(false);
+        }
+
+        let mut changed = false;
+        while let Ok(event) = self.file_change_rx.try_recv() {
+            changed |= match event {
+                DebouncedEvent::Write(path)
+                | DebouncedEvent::Create(path)
+                | DebouncedEvent::Rename(_, path) => path.file_name() == self.path.file_name(),
+                _ => false,
+            };
+        }
+
+        if !changed {
+            return Ok(false);
+        }
+
+        // A broken edit keeps the previous script running
+        let ast = self
+            .engine
+            .compile(read_script(&self.path)?)
+            .map_err(|e| anyhow::anyhow!("Failed to compile script {:?}: {}", self.path, e))?;
+        let mut evaluation = self.evaluation.borrow_mut();
+        evaluation.ast = ast;
+        evaluation.dirty = true;
+
+        Ok(true)
+    }
+
+    fn set_dirty(&mut self) {
+        self.evaluation.get_mut().dirty = true;
+    }
+
+    fn update(&self) {
+        let reload_result = self.check_changes();
+        let mut evaluation = self.evaluation.borrow_mut();
+        let evaluation = &mut *evaluation;
+
+        if let Err(e) = reload_result {
+            evaluation.error = Some(InputError::Script(e.to_string()));
+        }
+
+        if !evaluation.dirty {
+            return;
+        }
+        evaluation.dirty = false;
+
+        let properties: Map = self
+            .properties
+            .iter()
+            .map(|(name, value)| (name.into(), data_holder_to_dynamic(value)))
+            .collect();
+
+        let scope_length = evaluation.scope.len();
+        evaluation.scope.push_constant("time", self.time);
+        evaluation.scope.push_constant("beat", self.beat);
+        evaluation.scope.push_constant("properties", properties);
+        let result = self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut evaluation.scope, &evaluation.ast);
+        evaluation.scope.rewind(scope_length);
+
+        let values = match result {
+            Ok(values) => match values.try_cast::<Map>() {
+                Some(values) => values,
+                None => {
+                    evaluation.error = Some(InputError::Script(format!(
+                        "Script {:?} did not return a map of uniforms",
+                        self.path
+                    )));
+                    return;
+                }
+            },
+            Err(e) => {
+                evaluation.error = Some(InputError::Script(format!(
+                    "Script {:?} failed: {}",
+                    self.path, e
+                )));
+                return;
+            }
+        };
+
+        for (local_name, value) in values {
+            let local_name = local_name.to_string();
+            let data_type = evaluation.uniform_types.get(&local_name).copied();
+            match dynamic_to_data_holder(&value, data_type) {
+                Some(value) => {
+                    evaluation
+                        .uniform_types
+                        .entry(local_name.clone())
+                        .or_insert_with(|| value.get_type());
+                    evaluation.uniforms.set_if_changed(&local_name, value);
+                }
+                None => {
+                    evaluation.error = Some(InputError::Script(format!(
+                        "Script uniform {}_{} has an unsupported value: {}",
+                        self.name, local_name, value
+                    )))
+                }
+            }
+        }
+    }
+
+    fn get_local_name<'a>(&self, uniform_name: &'a str) -> InputResult<&'a str> {
+        match uniform_name
+            .strip_prefix(&self.name)
+            .and_then(|local_name| local_name.strip_prefix('_'))
+        {
+            Some(local_name)
+                if self
+                    .evaluation
+                    .borrow()
+                    .uniform_types
+                    .contains_key(local_name) =>
+            {
+                Ok(local_name)
+            }
+            _ => Err(InputError::UnknownUniform(uniform_name.to_string())),
+        }
+    }
+}
+
+impl InputProvider for ScriptProvider {
+    // Uniforms are only known once the script has run, which waits for the name so that
+    // errors from the first run name the uniforms they concern
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+        self.update();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.evaluation
+            .borrow()
+            .uniform_types
+            .keys()
+            .map(|local_name| format!("{}_{}", self.name, local_name))
+            .collect()
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        self.evaluation
+            .borrow()
+            .uniform_types
+            .iter()
+            .map(|(local_name, data_type)| {
+                UniformDescription::new(
+                    &format!("{}_{}", self.name, local_name),
+                    *data_type,
+                    DataRange::None,
+                    UpdateFrequency::OnChange,
+                )
+            })
+            .collect()
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        let local_name = self.get_local_name(uniform_name)?;
+        self.update();
+
+        Ok(self.evaluation.borrow().uniforms.get_generation(local_name))
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        let local_name = self.get_local_name(uniform_name)?;
+        self.update();
+
+        let evaluation = self.evaluation.get_mut();
+        if let Some(error) = evaluation.error.take() {
+            return Err(error);
+        }
+
+        Ok(evaluation.uniforms.get(local_name))
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()> {
+        match self.properties.get_mut(property) {
+            Some(current_value) if current_value.get_type() == value.get_type() => {
+                *current_value = value.clone();
+                self.set_dirty();
+                Ok(())
+            }
+            Some(current_value) => Err(InputError::wrong_value_type(
+                property,
+                &[current_value.get_type()],
+                value.get_type(),
+            )),
+            None => Err(InputError::UnknownProperty(property.to_string())),
+        }
+    }
+
+    fn list_properties(&self) -> Vec<PropertyDescription> {
+        let mut properties: Vec<PropertyDescription> = self
+            .properties
+            .iter()
+            .map(|(name, value)| {
+                PropertyDescription::new(name, value.get_type(), DataRange::None, "Script property")
+            })
+            .collect();
+        properties.sort_by(|a, b| a.name.cmp(&b.name));
+
+        properties
+    }
+
+    fn get_property(&self, property: &str) -> InputResult<DataHolder> {
+        self.properties
+            .get(property)
+            .cloned()
+            .ok_or_else(|| InputError::UnknownProperty(property.to_string()))
+    }
+
+    // Clocks set both the time and the beat every frame, the script runs once on the next read
+    fn set_beat(&mut self, beat: f64, _sync: bool) {
+        self.beat = beat;
+        self.set_dirty();
+    }
+
+    fn set_time(&mut self, time: f64, _sync: bool) {
+        self.time = time;
+        self.set_dirty();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::timing::clock::FrameClock;
+
+    fn write_script(name: &str, source: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("wvr-script-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("script.rhai");
+        fs::write(&path, source).unwrap();
+
+        path
+    }
+
+    fn create_provider(
+        name: &str,
+        source: &str,
+        uniforms: HashMap<String, DataType>,
+    ) -> ScriptProvider {
+        let mut provider =
+            ScriptProvider::new(write_script(name, source), true, HashMap::new(), uniforms)
+                .unwrap();
+        provider.set_name("script");
+
+        provider
+    }
+
+    #[test]
+    fn integers_are_inferred_as_floats() {
+        let mut provider = create_provider(
+            "infer",
+            "#{ level: if time > 0.5 { time } else { 0 }, offset: [0, 1] }",
+            HashMap::new(),
+        );
+
+        assert_eq!(
+            provider.borrow("script_level").unwrap(),
+            Some(&DataHolder::Float(0.0))
+        );
+        assert_eq!(
+            provider.borrow("script_offset").unwrap(),
+            Some(&DataHolder::Float2([0.0, 1.0]))
+        );
+
+        provider.set_time(1.5, false);
+        assert_eq!(
+            provider.borrow("script_level").unwrap(),
+            Some(&DataHolder::Float(1.5))
+        );
+    }
+
+    #[test]
+    fn declared_types_are_kept() {
+        let mut uniforms = HashMap::new();
+        uniforms.insert("count".to_string(), DataType::Int);
+        let mut provider = create_provider("declared", "#{ count: time * 2.0 }", uniforms);
+
+        provider.set_time(1.2, false);
+        assert_eq!(
+            provider.borrow("script_count").unwrap(),
+            Some(&DataHolder::Int(2))
+        );
+        assert_eq!(provider.describe()[0].data_type, Some(DataType::Int));
+    }
+
+    #[test]
+    fn script_errors_are_not_fatal() {
+        let mut provider = create_provider(
+            "errors",
+            r#"if time > 1.0 { throw "broken frame" } #{ level: time }"#,
+            HashMap::new(),
+        );
+
+        provider.set_time(0.25, false);
+        assert!(provider.borrow("script_level").is_ok());
+
+        provider.set_time(2.0, false);
+        let error = provider.borrow("script_level").unwrap_err();
+        assert!(matches!(error, InputError::Script(_)));
+        assert!(!error.is_fatal());
+        assert_eq!(
+            provider.borrow("script_level").unwrap(),
+            Some(&DataHolder::Float(0.25))
+        );
+
+        provider.set_time(0.5, false);
+        assert_eq!(
+            provider.borrow("script_level").unwrap(),
+            Some(&DataHolder::Float(0.5))
+        );
+    }
+
+    #[test]
+    fn reloads_scripts_saved_by_rename() {
+        let mut provider = create_provider("reload", "#{ level: 1.0 }", HashMap::new());
+        let path = provider.get_path().to_path_buf();
+
+        for value in [2.0, 3.0] {
+            let temporary_path = path.with_extension("tmp");
+            fs::write(&temporary_path, format!("#{{ level: {:.1} }}", value)).unwrap();
+            fs::rename(&temporary_path, &path).unwrap();
+
+            let start = Instant::now();
+            while !provider.check_changes().unwrap() {
+                assert!(start.elapsed() < Duration::from_secs(5), "Reload timed out");
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            assert_eq!(
+                provider.borrow("script_level").unwrap(),
+                Some(&DataHolder::Float(value))
+            );
+        }
+    }
+
+    #[test]
+    fn generations_only_move_forward() {
+        let mut provider = create_provider(
+            "generations",
+            "#{ level: if time > 1.0 { 1.0 } else { 0.0 } }",
+            HashMap::new(),
+        );
+
+        let generation = provider.get_generation("script_level").unwrap();
+        provider.set_time(0.5, false);
+        assert_eq!(provider.get_generation("script_level").unwrap(), generation);
+        provider.borrow("script_level").unwrap();
+        assert_eq!(provider.get_generation("script_level").unwrap(), generation);
+
+        provider.set_time(2.0, false);
+        let changed_generation = provider.get_generation("script_level").unwrap();
+        assert!(changed_generation > generation);
+        provider.borrow("script_level").unwrap();
+        assert_eq!(
+            provider.get_generation("script_level").unwrap(),
+            changed_generation
+        );
+    }
+
+    #[test]
+    fn first_run_errors_name_the_input() {
+        let mut provider =
+            create_provider("first-run", "#{ level: \"a\", bad: () }", HashMap::new());
+
+        match provider.borrow("script_level") {
+            Err(InputError::Script(reason)) => assert!(reason.contains("script_bad"), "{}", reason),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        assert_eq!(
+            provider.borrow("script_level").unwrap(),
+            Some(&DataHolder::String("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn scripts_run_once_per_frame() {
+        let mut provider = create_provider(
+            "frames",
+            "state.frames = if state.frames == () { 0 } else { state.frames + 1 };
+            #{ frames: state.frames, beat_per_second: if time > 0.0 { beat / time } else { 2.0 } }",
+            HashMap::new(),
+        );
+        let mut clock = FrameClock::new(10.0, 120.0).unwrap();
+
+        for frame_index in 1..=5 {
+            clock.advance();
+            clock.drive_input(&mut provider);
+
+            provider.get_generation("script_frames").unwrap();
+            assert_eq!(
+                provider.get("script_frames").unwrap(),
+                Some(DataHolder::Float(frame_index as f32))
+            );
+            // The script never sees the time of one frame with the beat of another
+            assert_eq!(
+                provider.get("script_beat_per_second").unwrap(),
+                Some(DataHolder::Float(2.0))
+            );
+        }
+
+        // Nothing changed, so reading again does not run the script
+        assert_eq!(
+            provider.get("script_frames").unwrap(),
+            Some(DataHolder::Float(5.0))
+        );
+    }
+}
@@ -0,0 +1,577 @@
+use std::fs;
+
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use anyhow::{Context, Result};
+
+use crate::config::text::{TextAlignment, TextOutline, TextStyle, TextVerticalAlignment};
+use crate::types::{
+    DataHolder, DataRange, DataType, InputError, InputProvider, InputResult, PropertyDescription,
+    UniformDescription, UniformStore, UpdateFrequency,
+};
+
+pub fn load_font(path: &str) -> Result<FontVec> {
+    let data = fs::read(path).with_context(|| format!("Failed to read font: {}", path))?;
+    FontVec::try_from_vec(data).with_context(|| format!("Invalid font file: {}", path))
+}
+
+fn get_line_width<F: Font>(font: &F, scale: PxScale, line: &str) -> f32 {
+    let scaled_font = font.as_scaled(scale);
+    let mut previous = None;
+
+    line.chars()
+        .map(|character| {
+            let glyph_id = scaled_font.glyph_id(character);
+            let kerning = previous
+                .map(|previous| scaled_font.kern(previous, glyph_id))
+                .unwrap_or_default();
+            previous = Some(glyph_id);
+
+            kerning + scaled_font.h_advance(glyph_id)
+        })
+        .sum()
+}
+
+fn rasterize_coverage<F: Font>(
+    font: &F,
+    text: &str,
+    style: &TextStyle,
+    width: usize,
+    height: usize,
+) -> Vec<f32> {
+    let mut coverage: Vec<f32> = vec![0.0; width * height];
+    let scale = PxScale::from(style.size.max(1.0));
+    let scaled_font = font.as_scaled(scale);
+    let margin = style
+        .outline
+        .map(|outline| outline.width)
+        .unwrap_or_default();
+
+    let lines: Vec<&str> = text.split('\n').collect();
+    let line_height = scaled_font.height() * style.line_spacing;
+    let text_height = line_height * (lines.len() - 1) as f32 + scaled_font.height();
+
+    let top = match style.vertical_alignment {
+        TextVerticalAlignment::Top => margin,
+        TextVerticalAlignment::Middle => (height as f32 - text_height) / 2.0,
+        TextVerticalAlignment::Bottom => height as f32 - text_height - margin,
+    };
+
+    for (line_index, line) in lines.iter().enumerate() {
+        let line_width = get_line_width(font, scale, line);
+        let mut x = match style.alignment {
+            TextAlignment::Left => margin,
+            TextAlignment::Center => (width as f32 - line_width) / 2.0,
+            TextAlignment::Right => width as f32 - line_width - margin,
+        };
+        let baseline = top + line_index as f32 * line_height + scaled_font.ascent();
+
+        let mut previous = None;
+        for character in line.chars() {
+            let glyph_id = scaled_font.glyph_id(character);
+            if let Some(previous) = previous {
+                x += scaled_font.kern(previous, glyph_id);
+            }
+            previous = Some(glyph_id);
+
+            let glyph = glyph_id.with_scale_and_position(scale, point(x, baseline));
+            x += scaled_font.h_advance(glyph_id);
+
+            if let Some(outlined_glyph) = font.outline_glyph(glyph) {
+                let bounds = outlined_glyph.px_bounds();
+                outlined_glyph.draw(|glyph_x, glyph_y, glyph_coverage| {
+                    let pixel_x = bounds.min.x as i64 + glyph_x as i64;
+                    let pixel_y = bounds.min.y as i64 + glyph_y as i64;
+                    if pixel_x < 0
+                        || pixel_y < 0
+                        || pixel_x >= width as i64
+                        || pixel_y >= height as i64
+                    {
+                        return;
+                    }
+
+                    let pixel = &mut coverage[pixel_y as usize * width + pixel_x as usize];
+                    *pixel = pixel.max(glyph_coverage);
+                });
+            }
+        }
+    }
+
+    coverage
+}
+
+// Separable max filter with a one pixel soft edge, linear in the outline width
+fn dilate_coverage(coverage: &[f32], width: usize, height: usize, radius: f32) -> Vec<f32> {
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+
+    let extent = radius.ceil() as i64 + 1;
+    let kernel: Vec<(i64, f32)> = (-extent..=extent)
+        .map(|offset| (offset, (radius + 1.0 - offset.abs() as f32).clamp(0.0, 1.0)))
+        .filter(|(_, weight)| *weight > 0.0)
+        .collect();
+
+    let dilate_pass = |source: &[f32], step: usize, length: usize, line_step: usize| {
+        let mut dilated = vec![0.0; width * height];
+        let line_count = width * height / length;
+        for line in 0..line_count {
+            let line_start = line * line_step;
+            for index in 0..length as i64 {
+                let mut value: f32 = 0.0;
+                for (offset, weight) in kernel.iter() {
+                    let sample = index + offset;
+                    if sample >= 0 && sample < length as i64 {
+                        value = value.max(source[line_start + sample as usize * step] * weight);
+                    }
+                }
+                dilated[line_start + index as usize * step] = value;
+            }
+        }
+
+        dilated
+    };
+
+    let horizontal = dilate_pass(coverage, 1, width, width);
+    dilate_pass(&horizontal, width, height, 1)
+}
+
+fn mix(from: [f32; 3], to: [f32; 3], amount: f32) -> [f32; 3] {
+    [
+        from[0] + (to[0] - from[0]) * amount,
+        from[1] + (to[1] - from[1]) * amount,
+        from[2] + (to[2] - from[2]) * amount,
+    ]
+}
+
+pub fn render_text<F: Font>(
+    font: &F,
+    text: &str,
+    style: &TextStyle,
+    width: usize,
+    height: usize,
+) -> Vec<u8> {
+    let coverage = rasterize_coverage(font, text, style, width, height);
+    let outline_coverage = match style.outline {
+        Some(TextOutline {
+            width: outline_width,
+            ..
+        }) if outline_width > 0.0 => Some(dilate_coverage(&coverage, width, height, outline_width)),
+        _ => None,
+    };
+
+    let mut data = Vec::with_capacity(width * height * 3);
+    for (index, fill) in coverage.iter().enumerate() {
+        let mut color = style.background;
+        if let (Some(outline), Some(outline_coverage)) = (style.outline, &outline_coverage) {
+            color = mix(color, outline.color, outline_coverage[index]);
+        }
+        color = mix(color, style.color, *fill);
+
+        data.extend(
+            color
+                .iter()
+                .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8),
+        );
+    }
+
+    data
+}
+
+fn get_alignment(name: &str) -> InputResult<TextAlignment> {
+    TextAlignment::from_name(name)
+        .ok_or_else(|| InputError::invalid_value("alignment", name, &["Left", "Center", "Right"]))
+}
+
+fn get_vertical_alignment(name: &str) -> InputResult<TextVerticalAlignment> {
+    TextVerticalAlignment::from_name(name).ok_or_else(|| {
+        InputError::invalid_value("vertical_alignment", name, &["Top", "Middle", "Bottom"])
+    })
+}
+
+fn color_property(name: &str, description: &str) -> PropertyDescription {
+    PropertyDescription::new(name, DataType::Float3, DataRange::ColorRange, description)
+}
+
+pub struct TextProvider {
+    name: String,
+    width: usize,
+    height: usize,
+    text: String,
+    style: TextStyle,
+    font: FontVec,
+
+    dirty: bool,
+    uniforms: UniformStore,
+}
+
+impl TextProvider {
+    pub fn new(width: usize, height: usize, text: &str, style: TextStyle) -> Result<Self> {
+        let provider = Self {
+            name: String::new(),
+            width,
+            height,
+            text: text.to_string(),
+            font: load_font(&style.font)?,
+            style,
+
+            dirty: true,
+            uniforms: UniformStore::default(),
+        };
+
+        // Configured values go through the same checks as the properties that change them
+        for description in provider.list_properties() {
+            description.check(&provider.get_property(&description.name)?)?;
+        }
+
+        Ok(provider)
+    }
+
+    fn update(&mut self) {
+        if !self.dirty {
+            return;
+        }
+
+        let data = render_text(&self.font, &self.text, &self.style, self.width, self.height);
+        self.uniforms.set(
+            "texture",
+            DataHolder::Texture(((self.width as u32, self.height as u32), data)),
+        );
+        self.dirty = false;
+    }
+
+    fn check_uniform(&self, uniform_name: &str) -> InputResult<()> {
+        if uniform_name != self.name {
+            return Err(InputError::UnknownUniform(uniform_name.to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+impl InputProvider for TextProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        vec![self.name.clone()]
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        vec![UniformDescription::new(
+            &self.name,
+            DataType::Texture,
+            DataRange::None,
+            UpdateFrequency::OnChange,
+        )]
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        self.check_uniform(uniform_name)?;
+        Ok(self.uniforms.get_generation("texture") + self.dirty as u64)
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        self.check_uniform(uniform_name)?;
+        self.update();
+
+        Ok(self.uniforms.get("texture"))
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()> {
+        match self
+            .list_properties()
+            .iter()
+            .find(|description| description.name == property)
+        {
+            Some(description) => description.check(value)?,
+            None => return Err(InputError::UnknownProperty(property.to_string())),
+        }
+
+        match (property, value) {
+            ("text", DataHolder::String(text)) => self.text = text.clone(),
+            // A font that fails to load leaves the current one in place
+            ("font", DataHolder::String(font)) => {
+                self.font = load_font(font).map_err(|_| {
+                    InputError::invalid_value(property, font, &["a TrueType or OpenType font file"])
+                })?;
+                self.style.font = font.clone();
+            }
+            ("size", DataHolder::Float(size)) => self.style.size = *size,
+            ("line_spacing", DataHolder::Float(line_spacing)) => {
+                self.style.line_spacing = *line_spacing
+            }
+            ("color", DataHolder::Float3(color)) => self.style.color = *color,
+            ("background", DataHolder::Float3(background)) => self.style.background = *background,
+            ("outline_width", DataHolder::Float(outline_width)) => {
+                let color = self
+                    .style
+                    .outline
+                    .map(|outline| outline.color)
+                    .unwrap_or_default();
+                self.style.outline = Some(TextOutline {
+                    width: *outline_width,
+                    color,
+                });
+            }
+            ("outline_color", DataHolder::Float3(outline_color)) => {
+                let width = self
+                    .style
+                    .outline
+                    .map(|outline| outline.width)
+                    .unwrap_or_default();
+                self.style.outline = Some(TextOutline {
+                    width,
+                    color: *outline_color,
+                });
+            }
+            ("alignment", DataHolder::String(alignment)) => {
+                self.style.alignment = get_alignment(alignment)?
+            }
+            ("vertical_alignment", DataHolder::String(vertical_alignment)) => {
+                self.style.vertical_alignment = get_vertical_alignment(vertical_alignment)?
+            }
+            _ => return Err(InputError::UnknownProperty(property.to_string())),
+        }
+
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn list_properties(&self) -> Vec<PropertyDescription> {
+        vec![
+            PropertyDescription::new("text", DataType::String, DataRange::None, "Displayed text"),
+            PropertyDescription::new("font", DataType::String, DataRange::None, "Font file path"),
+            PropertyDescription::new(
+                "size",
+                DataType::Float,
+                DataRange::FloatRange(1.0, 512.0, 1.0),
+                "Font size, in pixels",
+            ),
+            PropertyDescription::new(
+                "line_spacing",
+                DataType::Float,
+                DataRange::FloatRange(0.5, 4.0, 0.05),
+                "Distance between lines, relative to the font height",
+            ),
+            color_property("color", "Text color"),
+            color_property("background", "Background color"),
+            PropertyDescription::new(
+                "outline_width",
+                DataType::Float,
+                DataRange::FloatRange(0.0, 32.0, 0.5),
+                "Outline width, in pixels",
+            ),
+            color_property("outline_color", "Outline color"),
+            PropertyDescription::new(
+                "alignment",
+                DataType::String,
+                DataRange::None,
+                "Horizontal alignment: Left, Center or Right",
+            ),
+            PropertyDescription::new(
+                "vertical_alignment",
+                DataType::String,
+                DataRange::None,
+                "Vertical alignment: Top, Middle or Bottom",
+            ),
+        ]
+    }
+
+    fn get_property(&self, property: &str) -> InputResult<DataHolder> {
+        let outline = self.style.outline.unwrap_or(TextOutline {
+            width: 0.0,
+            color: [0.0; 3],
+        });
+
+        match property {
+            "text" => Ok(DataHolder::String(self.text.clone())),
+            "font" => Ok(DataHolder::String(self.style.font.clone())),
+            "size" => Ok(DataHolder::Float(self.style.size)),
+            "line_spacing" => Ok(DataHolder::Float(self.style.line_spacing)),
+            "color" => Ok(DataHolder::Float3(self.style.color)),
+            "background" => Ok(DataHolder::Float3(self.style.background)),
+            "outline_width" => Ok(DataHolder::Float(outline.width)),
+            "outline_color" => Ok(DataHolder::Float3(outline.color)),
+            "alignment" => Ok(DataHolder::String(self.style.alignment.name().to_string())),
+            "vertical_alignment" => Ok(DataHolder::String(
+                self.style.vertical_alignment.name().to_string(),
+            )),
+            _ => Err(InputError::UnknownProperty(property.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dilation_spreads_a_pixel_with_a_soft_edge() {
+        let mut coverage = vec![0.0; 25];
+        coverage[12] = 1.0;
+
+        let dilated = dilate_coverage(&coverage, 5, 5, 1.0);
+        for y in 0..5 {
+            for x in 0..5 {
+                let distance = (x as i32 - 2).abs().max((y as i32 - 2).abs());
+                let expected = if distance <= 1 { 1.0 } else { 0.0 };
+                assert_eq!(dilated[y * 5 + x], expected, "at {}, {}", x, y);
+            }
+        }
+
+        let dilated = dilate_coverage(&coverage, 5, 5, 0.5);
+        assert_eq!(dilated[12], 1.0);
+        assert_eq!(dilated[11], 0.5);
+        assert_eq!(dilated[6], 0.25);
+        assert_eq!(dilated[10], 0.0);
+    }
+
+    #[test]
+    fn dilation_with_zero_radius_keeps_the_coverage() {
+        let coverage: Vec<f32> = (0..12).map(|index| index as f32 / 12.0).collect();
+        assert_eq!(dilate_coverage(&coverage, 4, 3, 0.0), coverage);
+        assert!(dilate_coverage(&[], 0, 3, 1.0).is_empty());
+    }
+
+    #[test]
+    fn unknown_alignments_list_the_valid_names() {
+        assert_eq!(get_alignment("Right"), Ok(TextAlignment::Right));
+        assert_eq!(
+            get_alignment("Justify"),
+            Err(InputError::invalid_value(
+                "alignment",
+                "Justify",
+                &["Left", "Center", "Right"]
+            ))
+        );
+        assert_eq!(
+            get_vertical_alignment("Middle"),
+            Ok(TextVerticalAlignment::Middle)
+        );
+        assert_eq!(
+            get_vertical_alignment("Center"),
+            Err(InputError::invalid_value(
+                "vertical_alignment",
+                "Center",
+                &["Top", "Middle", "Bottom"]
+            ))
+        );
+    }
+
+    // Drawing needs a real font, these tests are skipped where none of them is installed
+    const TEST_FONTS: [&str; 3] = [
+        "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+        "/System/Library/Fonts/Supplemental/Arial.ttf",
+        "C:\\Windows\\Fonts\\arial.ttf",
+    ];
+
+    fn get_style() -> Option<TextStyle> {
+        let font = TEST_FONTS
+            .iter()
+            .find(|font| std::path::Path::new(font).exists())?;
+
+        Some(TextStyle {
+            font: font.to_string(),
+            size: 24.0,
+            alignment: TextAlignment::Center,
+            vertical_alignment: TextVerticalAlignment::Middle,
+            color: [1.0; 3],
+            background: [0.0; 3],
+            outline: None,
+            line_spacing: 1.2,
+        })
+    }
+
+    fn get_texture(provider: &mut TextProvider) -> Vec<u8> {
+        match provider.borrow("text").unwrap() {
+            Some(DataHolder::Texture((size, data))) => {
+                assert_eq!(*size, (64, 32));
+                data.clone()
+            }
+            value => panic!("Unexpected texture: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn live_text_renders_a_new_texture() {
+        let style = match get_style() {
+            Some(style) => style,
+            None => return,
+        };
+        let mut provider = TextProvider::new(64, 32, "A", style).unwrap();
+        provider.set_name("text");
+
+        let generation = provider.get_generation("text").unwrap();
+        let texture = get_texture(&mut provider);
+        assert!(texture.iter().any(|channel| *channel > 0));
+        assert_eq!(provider.get_generation("text").unwrap(), generation);
+
+        provider
+            .set_property("text", &DataHolder::String("B".to_string()))
+            .unwrap();
+        assert!(provider.get_generation("text").unwrap() > generation);
+        let next_texture = get_texture(&mut provider);
+        assert_ne!(next_texture, texture);
+
+        provider
+            .set_property("text", &DataHolder::String(String::new()))
+            .unwrap();
+        assert!(get_texture(&mut provider)
+            .iter()
+            .all(|channel| *channel == 0));
+    }
+
+    #[test]
+    fn unreadable_fonts_keep_the_current_one() {
+        let style = match get_style() {
+            Some(style) => style,
+            None => return,
+        };
+        let font = style.font.clone();
+        let mut provider = TextProvider::new(64, 32, "A", style).unwrap();
+        provider.set_name("text");
+        let texture = get_texture(&mut provider);
+
+        let error = provider
+            .set_property("font", &DataHolder::String("missing.ttf".to_string()))
+            .unwrap_err();
+        assert!(matches!(error, InputError::InvalidValue { .. }));
+        assert!(!error.is_fatal());
+        assert_eq!(provider.get_property("font"), Ok(DataHolder::String(font)));
+        assert_eq!(get_texture(&mut provider), texture);
+    }
+
+    #[test]
+    fn configured_styles_are_range_checked() {
+        let style = match get_style() {
+            Some(style) => style,
+            None => return,
+        };
+
+        for invalid_style in [
+            TextStyle {
+                size: 0.0,
+                ..style.clone()
+            },
+            TextStyle {
+                color: [2.0, 0.0, 0.0],
+                ..style.clone()
+            },
+            TextStyle {
+                outline: Some(TextOutline {
+                    width: f32::NAN,
+                    color: [1.0; 3],
+                }),
+                ..style.clone()
+            },
+        ] {
+            assert!(
+                TextProvider::new(64, 32, "A", invalid_style.clone()).is_err(),
+                "{:?}",
+                invalid_style
+            );
+        }
+        assert!(TextProvider::new(64, 32, "A", style).is_ok());
+    }
+}
@@ -0,0 +1,644 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::types::{
+    DataHolder, InputError, InputEvent, InputProvider, InputResult, PropertyDescription,
+    UniformDescription,
+};
+
+const MAX_QUEUED_ERRORS: usize = 64;
+
+enum Command {
+    SetName(String, Sender<()>),
+    SetProperty(String, DataHolder),
+    SetBeat(f64, bool),
+    SetTime(f64, bool),
+    HandleEvent(InputEvent),
+    SeekTime(f64),
+    SeekBeat(f64),
+    SetLooping(bool),
+    Play,
+    Pause,
+    Stop(Sender<InputResult<()>>),
+}
+
+// Provider state that the render thread reads without a round trip to the worker
+#[derive(Default)]
+struct TransportState {
+    seekable: bool,
+    duration: Option<f64>,
+    position: Option<f64>,
+    looping: bool,
+}
+
+impl TransportState {
+    fn from_provider(provider: &dyn InputProvider) -> Self {
+        Self {
+            seekable: provider.is_seekable(),
+            duration: provider.get_duration(),
+            position: provider.get_position(),
+            looping: provider.is_looping(),
+        }
+    }
+}
+
+fn get_properties(provider: &dyn InputProvider) -> Vec<(PropertyDescription, Option<DataHolder>)> {
+    provider
+        .list_properties()
+        .into_iter()
+        .map(|description| {
+            let value = provider.get_property(&description.name).ok();
+            (description, value)
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Mailbox {
+    values: HashMap<String, (u64, DataHolder)>,
+    description: Vec<UniformDescription>,
+    properties: Vec<(PropertyDescription, Option<DataHolder>)>,
+    transport: TransportState,
+    uniform_errors: HashMap<String, InputError>,
+    errors: VecDeque<(String, InputError)>,
+}
+
+impl Mailbox {
+    fn is_known(&self, uniform_name: &str) -> bool {
+        self.description
+            .iter()
+            .any(|description| description.name == uniform_name)
+    }
+
+    fn push_error(&mut self, operation: String, error: InputError) {
+        if self.errors.len() >= MAX_QUEUED_ERRORS {
+            self.errors.pop_front();
+        }
+        self.errors.push_back((operation, error));
+    }
+}
+
+pub struct ThreadedProvider {
+    command_tx: Sender<Command>,
+    mailbox: Arc<Mutex<Mailbox>>,
+    values: HashMap<String, (u64, DataHolder)>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl ThreadedProvider {
+    pub fn new(provider: Box<dyn InputProvider + Send>, poll_interval: Duration) -> Self {
+        let mailbox = Arc::new(Mutex::new(Mailbox {
+            description: provider.describe(),
+            properties: get_properties(provider.as_ref()),
+            transport: TransportState::from_provider(provider.as_ref()),
+            ..Default::default()
+        }));
+
+        let (command_tx, command_rx) = channel();
+        let worker_mailbox = mailbox.clone();
+        let worker =
+            thread::spawn(move || run_worker(provider, command_rx, worker_mailbox, poll_interval));
+
+        Self {
+            command_tx,
+            mailbox,
+            values: HashMap::new(),
+            worker: Some(worker),
+        }
+    }
+
+    fn lock_mailbox(&self) -> MutexGuard<'_, Mailbox> {
+        lock_mailbox(&self.mailbox)
+    }
+
+    fn send(&self, command: Command) -> InputResult<()> {
+        self.command_tx
+            .send(command)
+            .map_err(|_| InputError::DeviceLost("Input worker thread has stopped".to_string()))
+    }
+
+    // Calls that only queue a command return before the worker runs it, errors raised by the
+    // wrapped provider then are kept here, tagged with the operation that raised them
+    pub fn take_errors(&self) -> Vec<(String, InputError)> {
+        self.lock_mailbox().errors.drain(..).collect()
+    }
+
+    fn join(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+// A poisoned mailbox only means the worker panicked mid-update, the data is still usable
+fn lock_mailbox(mailbox: &Mutex<Mailbox>) -> MutexGuard<'_, Mailbox> {
+    mailbox
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn run_worker(
+    mut provider: Box<dyn InputProvider + Send>,
+    command_rx: Receiver<Command>,
+    mailbox: Arc<Mutex<Mailbox>>,
+    poll_interval: Duration,
+) {
+    let mut playing = true;
+    let mut generations: HashMap<String, u64> = HashMap::new();
+
+    loop {
+        let mut command = match command_rx.recv_timeout(poll_interval) {
+            Ok(command) => Some(command),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => {
+                let _ = provider.stop();
+                return;
+            }
+        };
+
+        let mut errors = Vec::new();
+        let mut properties_changed = false;
+        while let Some(current_command) = command {
+            let (operation, result) = match current_command {
+                Command::SetName(name, done_tx) => {
+                    provider.set_name(&name);
+                    generations.clear();
+                    let description = provider.describe();
+                    {
+                        let mut mailbox = lock_mailbox(&mailbox);
+                        mailbox.values.clear();
+                        mailbox.uniform_errors.clear();
+                        mailbox.description = description;
+                    }
+                    let _ = done_tx.send(());
+                    ("set_name".to_string(), Ok(()))
+                }
+                Command::SetProperty(property, value) => {
+                    properties_changed = true;
+                    let result = provider.set_property(&property, &value);
+                    (format!("set_property({})", property), result)
+                }
+                Command::SetBeat(beat, sync) => {
+                    provider.set_beat(beat, sync);
+                    ("set_beat".to_string(), Ok(()))
+                }
+                Command::SetTime(time, sync) => {
+                    provider.set_time(time, sync);
+                    ("set_time".to_string(), Ok(()))
+                }
+                Command::HandleEvent(event) => {
+                    provider.handle_event(&event);
+                    ("handle_event".to_string(), Ok(()))
+                }
+                Command::SeekTime(time) => ("seek_time".to_string(), provider.seek_time(time)),
+                Command::SeekBeat(beat) => ("seek_beat".to_string(), provider.seek_beat(beat)),
+                Command::SetLooping(looping) => {
+                    properties_changed = true;
+                    ("set_looping".to_string(), provider.set_looping(looping))
+                }
+                Command::Play => {
+                    playing = true;
+                    ("play".to_string(), provider.play())
+                }
+                Command::Pause => {
+                    playing = false;
+                    ("pause".to_string(), provider.pause())
+                }
+                Command::Stop(result_tx) => {
+                    let _ = result_tx.send(provider.stop());
+                    return;
+                }
+            };
+
+            if let Err(error) = result {
+                errors.push((operation, error));
+            }
+
+            command = command_rx.try_recv().ok();
+        }
+
+        let mut values = Vec::new();
+        let mut uniform_errors = Vec::new();
+        if playing {
+            for uniform_name in provider.provides() {
+                let generation = match provider.get_generation(&uniform_name) {
+                    Ok(generation) => generation,
+                    Err(error) => {
+                        uniform_errors.push((uniform_name, error));
+                        continue;
+                    }
+                };
+                if generations.get(&uniform_name) == Some(&generation) {
+                    continue;
+                }
+
+                match provider.get(&uniform_name) {
+                    Ok(Some(value)) => {
+                        generations.insert(uniform_name.clone(), generation);
+                        values.push((uniform_name, generation, value));
+                    }
+                    Ok(None) => (),
+                    Err(error) => uniform_errors.push((uniform_name, error)),
+                }
+            }
+        }
+
+        // Everything is read from the provider first so that the lock is only held for the swap
+        let transport = TransportState::from_provider(provider.as_ref());
+        let properties = if properties_changed {
+            Some(get_properties(provider.as_ref()))
+        } else {
+            None
+        };
+
+        let mut mailbox = lock_mailbox(&mailbox);
+        for (uniform_name, generation, value) in values {
+            mailbox.values.insert(uniform_name, (generation, value));
+        }
+        for (uniform_name, error) in uniform_errors {
+            mailbox.uniform_errors.insert(uniform_name, error);
+        }
+        for (operation, error) in errors {
+            mailbox.push_error(operation, error);
+        }
+        mailbox.transport = transport;
+        if let Some(properties) = properties {
+            mailbox.properties = properties;
+        }
+    }
+}
+
+impl InputProvider for ThreadedProvider {
+    // Waits for the worker so that the uniform names are current once this returns
+    fn set_name(&mut self, name: &str) {
+        let (done_tx, done_rx) = channel();
+        if self
+            .send(Command::SetName(name.to_string(), done_tx))
+            .is_ok()
+        {
+            let _ = done_rx.recv();
+        }
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.lock_mailbox()
+            .description
+            .iter()
+            .map(|description| description.name.clone())
+            .collect()
+    }
+
+    fn describe(&self) -> Vec<UniformDescription> {
+        self.lock_mailbox().description.clone()
+    }
+
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64> {
+        let mailbox = self.lock_mailbox();
+        match mailbox.values.get(uniform_name) {
+            Some((generation, _)) => Ok(*generation),
+            None if mailbox.is_known(uniform_name) => Ok(0),
+            None => Err(InputError::UnknownUniform(uniform_name.to_string())),
+        }
+    }
+
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+        {
+            let mut mailbox = lock_mailbox(&self.mailbox);
+            if let Some(error) = mailbox.uniform_errors.remove(uniform_name) {
+                return Err(error);
+            }
+
+            match mailbox.values.get(uniform_name) {
+                Some((generation, value)) => {
+                    let local_generation = self.values.get(uniform_name).map(|(local, _)| *local);
+                    // Only clone out of the mailbox when the worker produced something new
+                    if local_generation != Some(*generation) {
+                        self.values
+                            .insert(uniform_name.to_string(), (*generation, value.clone()));
+                    }
+                }
+                None if mailbox.is_known(uniform_name) => return Ok(None),
+                None => return Err(InputError::UnknownUniform(uniform_name.to_string())),
+            }
+        }
+
+        Ok(self.values.get(uniform_name).map(|(_, value)| value))
+    }
+
+    // Listed properties are checked before the value is queued, unlisted ones are passed on
+    // as is, errors the wrapped provider raises while applying them are only reported by
+    // take_errors
+    fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()> {
+        if let Some((description, _)) = self
+            .lock_mailbox()
+            .properties
+            .iter()
+            .find(|(description, _)| description.name == property)
+        {
+            description.check(value)?;
+        }
+
+        self.send(Command::SetProperty(property.to_string(), value.clone()))
+    }
+
+    fn list_properties(&self) -> Vec<PropertyDescription> {
+        self.lock_mailbox()
+            .properties
+            .iter()
+            .map(|(description, _)| description.clone())
+            .collect()
+    }
+
+    fn get_property(&self, property: &str) -> InputResult<DataHolder> {
+        self.lock_mailbox()
+            .properties
+            .iter()
+            .find(|(description, _)| description.name == property)
+            .and_then(|(_, value)| value.clone())
+            .ok_or_else(|| InputError::UnknownProperty(property.to_string()))
+    }
+
+    fn set_beat(&mut self, beat: f64, sync: bool) {
+        let _ = self.send(Command::SetBeat(beat, sync));
+    }
+
+    fn set_time(&mut self, time: f64, sync: bool) {
+        let _ = self.send(Command::SetTime(time, sync));
+    }
+
+    fn handle_event(&mut self, event: &InputEvent) {
+        let _ = self.send(Command::HandleEvent(*event));
+    }
+
+    fn is_seekable(&self) -> bool {
+        self.lock_mailbox().transport.seekable
+    }
+
+    fn seek_time(&mut self, time: f64) -> InputResult<()> {
+        self.send(Command::SeekTime(time))
+    }
+
+    fn seek_beat(&mut self, beat: f64) -> InputResult<()> {
+        self.send(Command::SeekBeat(beat))
+    }
+
+    fn get_duration(&self) -> Option<f64> {
+        self.lock_mailbox().transport.duration
+    }
+
+    fn get_position(&self) -> Option<f64> {
+        self.lock_mailbox().transport.position
+    }
+
+    fn is_looping(&self) -> bool {
+        self.lock_mailbox().transport.looping
+    }
+
+    fn set_looping(&mut self, looping: bool) -> InputResult<()> {
+        self.send(Command::SetLooping(looping))
+    }
+
+    fn stop(&mut self) -> InputResult<()> {
+        if self.worker.is_none() {
+            return Ok(());
+        }
+
+        // A worker that already exited, or panicked, is still joined
+        let (result_tx, result_rx) = channel();
+        let result = self.send(Command::Stop(result_tx)).and_then(|_| {
+            result_rx.recv().unwrap_or_else(|_| {
+                Err(InputError::DeviceLost(
+                    "Input worker thread has stopped".to_string(),
+                ))
+            })
+        });
+        self.join();
+
+        result
+    }
+
+    fn pause(&mut self) -> InputResult<()> {
+        self.send(Command::Pause)
+    }
+
+    fn play(&mut self) -> InputResult<()> {
+        self.send(Command::Play)
+    }
+}
+
+impl Drop for ThreadedProvider {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::types::{DataRange, DataType, UniformStore, UpdateFrequency};
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+    // Counts the time updates it received, `broken` fails on every read and a negative time
+    // makes the worker panic
+    #[derive(Default)]
+    struct CounterProvider {
+        name: String,
+        step: i32,
+        stopped: Arc<Mutex<bool>>,
+        uniforms: UniformStore,
+    }
+
+    impl InputProvider for CounterProvider {
+        fn set_name(&mut self, name: &str) {
+            self.name = name.to_string();
+        }
+
+        fn provides(&self) -> Vec<String> {
+            vec![
+                format!("{}_count", self.name),
+                format!("{}_broken", self.name),
+            ]
+        }
+
+        fn describe(&self) -> Vec<UniformDescription> {
+            self.provides()
+                .iter()
+                .map(|name| {
+                    UniformDescription::new(
+                        name,
+                        DataType::Int,
+                        DataRange::None,
+                        UpdateFrequency::OnChange,
+                    )
+                })
+                .collect()
+        }
+
+        fn get_generation(&self, _uniform_name: &str) -> InputResult<u64> {
+            Ok(self.uniforms.get_generation("count"))
+        }
+
+        fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+            if uniform_name.ends_with("_broken") {
+                return Err(InputError::Unsupported("broken".to_string()));
+            }
+
+            Ok(self.uniforms.get("count"))
+        }
+
+        fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()> {
+            match (property, value) {
+                ("step", DataHolder::Int(step)) => self.step = *step,
+                ("rejected", _) => return Err(InputError::Unsupported(property.to_string())),
+                _ => return Err(InputError::UnknownProperty(property.to_string())),
+            }
+
+            Ok(())
+        }
+
+        fn list_properties(&self) -> Vec<PropertyDescription> {
+            vec![PropertyDescription::new(
+                "step",
+                DataType::Int,
+                DataRange::IntRange(1, 10, 1),
+                "Count increment",
+            )]
+        }
+
+        fn get_property(&self, property: &str) -> InputResult<DataHolder> {
+            match property {
+                "step" => Ok(DataHolder::Int(self.step)),
+                _ => Err(InputError::UnknownProperty(property.to_string())),
+            }
+        }
+
+        fn set_time(&mut self, time: f64, _sync: bool) {
+            assert!(time >= 0.0, "Negative time");
+
+            let count = match self.uniforms.get("count") {
+                Some(DataHolder::Int(count)) => *count,
+                _ => 0,
+            };
+            self.uniforms
+                .set("count", DataHolder::Int(count + self.step));
+        }
+
+        fn stop(&mut self) -> InputResult<()> {
+            *self.stopped.lock().unwrap() = true;
+            Ok(())
+        }
+    }
+
+    fn create_provider() -> (ThreadedProvider, Arc<Mutex<bool>>) {
+        let stopped = Arc::new(Mutex::new(false));
+        let counter = CounterProvider {
+            step: 1,
+            stopped: stopped.clone(),
+            ..CounterProvider::default()
+        };
+
+        let mut provider = ThreadedProvider::new(Box::new(counter), POLL_INTERVAL);
+        provider.set_name("counter");
+
+        (provider, stopped)
+    }
+
+    fn wait_for<F: FnMut() -> bool>(mut condition: F) {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !condition() {
+            assert!(
+                Instant::now() < deadline,
+                "Timed out waiting for the worker"
+            );
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    #[test]
+    fn names_apply_before_set_name_returns() {
+        let (provider, _) = create_provider();
+        assert_eq!(provider.provides(), ["counter_count", "counter_broken"]);
+        assert_eq!(provider.describe()[0].data_type, Some(DataType::Int));
+    }
+
+    #[test]
+    fn values_come_back_from_the_worker() {
+        let (mut provider, _) = create_provider();
+        provider.set_time(0.0, true);
+        provider.set_time(1.0, true);
+
+        wait_for(|| provider.get("counter_count").unwrap() == Some(DataHolder::Int(2)));
+        assert!(provider.get_generation("counter_count").unwrap() > 0);
+        assert!(matches!(
+            provider.get("counter_missing"),
+            Err(InputError::UnknownUniform(_))
+        ));
+    }
+
+    #[test]
+    fn errors_stay_with_their_uniform_or_call() {
+        let (mut provider, _) = create_provider();
+        provider.set_time(0.0, true);
+        wait_for(|| {
+            matches!(
+                provider.get("counter_broken"),
+                Err(InputError::Unsupported(_))
+            )
+        });
+
+        provider
+            .set_property("rejected", &DataHolder::Int(1))
+            .unwrap();
+        provider.set_time(1.0, true);
+        wait_for(|| provider.get("counter_count").unwrap() == Some(DataHolder::Int(2)));
+
+        let errors = provider.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, "set_property(rejected)");
+        assert!(provider.take_errors().is_empty());
+    }
+
+    #[test]
+    fn listed_properties_are_checked_synchronously() {
+        let (mut provider, _) = create_provider();
+
+        assert!(matches!(
+            provider.set_property("step", &DataHolder::Int(20)),
+            Err(InputError::OutOfRange { .. })
+        ));
+        assert!(matches!(
+            provider.set_property("step", &DataHolder::Float(2.0)),
+            Err(InputError::WrongValueType { .. })
+        ));
+
+        provider.set_property("step", &DataHolder::Int(5)).unwrap();
+        wait_for(|| provider.get_property("step") == Ok(DataHolder::Int(5)));
+    }
+
+    #[test]
+    fn stop_shuts_the_worker_down() {
+        let (mut provider, stopped) = create_provider();
+
+        provider.stop().unwrap();
+        assert!(*stopped.lock().unwrap());
+        assert!(provider.worker.is_none());
+        assert!(provider.stop().is_ok());
+        assert!(provider.pause().is_err());
+    }
+
+    #[test]
+    fn stop_joins_a_worker_that_panicked() {
+        let (mut provider, stopped) = create_provider();
+
+        provider.set_time(-1.0, true);
+        assert!(matches!(provider.stop(), Err(InputError::DeviceLost(_))));
+        assert!(provider.worker.is_none());
+        assert!(!*stopped.lock().unwrap());
+    }
+}
@@ -1,9 +1,9 @@
+use std::fs::File;
 use std::io::Error;
 use std::io::Read;
 use std::path::PathBuf;
 use std::sync::mpsc::Receiver;
 use std::time::Duration;
-use std::{fs::File, io::ErrorKind};
 
 use anyhow::{Context, Result};
 
@@ -61,10 +61,10 @@ pub struct FileShader {
 impl FileShader {
     pub fn new(file_path: PathBuf, live_reload: bool) -> Result<Self> {
         let mut file = File::open(&file_path).map_err(|e| {
-            Error::new(
-                ErrorKind::Other,
-                format!("Failed to open shader file: {:?} ({:?})", &file_path, &e),
-            )
+            Error::other(format!(
+                "Failed to open shader file: {:?} ({:?})",
+                &file_path, &e
+            ))
         })?;
 
         let (tx, file_change_rx) = channel();
@@ -76,13 +76,10 @@ impl FileShader {
 
         let mut text = String::new();
         file.read_to_string(&mut text).map_err(|e| {
-            Error::new(
-                ErrorKind::Other,
-                format!(
-                    "Failed to retrieve shader content: {:?} ({:?})",
-                    &file_path, &e
-                ),
-            )
+            Error::other(format!(
+                "Failed to retrieve shader content: {:?} ({:?})",
+                &file_path, &e
+            ))
         })?;
 
         let text_shader = TextShader::new(text);
@@ -131,20 +128,12 @@ impl Shader for FileShader {
     }
 }
 
+#[derive(Default)]
 pub struct ShaderComposer {
     components: Vec<Box<dyn Shader>>,
     text: String,
 }
 
-impl Default for ShaderComposer {
-    fn default() -> Self {
-        Self {
-            components: Vec::new(),
-            text: String::new(),
-        }
-    }
-}
-
 impl ShaderComposer {
     pub fn insert(&mut self, index: usize, shader: Box<dyn Shader>) {
         let index = index.min(self.components.len());
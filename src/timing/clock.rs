@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+
+use crate::config::project::ProjectConfig;
+use crate::types::{Automation, DataHolder, InputProvider};
+
+pub trait Clock {
+    fn get_time(&self) -> f64;
+    fn get_beat(&self) -> f64;
+    fn get_bpm(&self) -> f64;
+}
+
+fn validate_bpm(bpm: f64) -> Result<()> {
+    if !bpm.is_finite() || bpm <= 0.0 {
+        bail!("Invalid frame clock tempo: {} bpm", bpm);
+    }
+
+    Ok(())
+}
+
+// Each tempo change starts a segment at the frame and beat where it happened
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TempoSegment {
+    frame_index: u64,
+    beat: f64,
+    bpm: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameClock {
+    fps: f64,
+    frame_index: u64,
+    tempo_segments: Vec<TempoSegment>,
+}
+
+impl FrameClock {
+    pub fn new(fps: f64, bpm: f64) -> Result<Self> {
+        if !fps.is_finite() || fps <= 0.0 {
+            bail!("Invalid frame clock rate: {} fps", fps);
+        }
+        validate_bpm(bpm)?;
+
+        Ok(Self {
+            fps,
+            frame_index: 0,
+            tempo_segments: vec![TempoSegment {
+                frame_index: 0,
+                beat: 0.0,
+                bpm,
+            }],
+        })
+    }
+
+    pub fn from_project(project: &ProjectConfig) -> Result<Self> {
+        Self::new(project.view.target_fps as f64, project.bpm as f64)
+    }
+
+    pub fn get_fps(&self) -> f64 {
+        self.fps
+    }
+
+    pub fn get_frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    pub fn set_frame_index(&mut self, frame_index: u64) {
+        self.frame_index = frame_index;
+    }
+
+    pub fn advance(&mut self) {
+        self.frame_index += 1;
+    }
+
+    // Segments after the current frame belonged to a timeline the new tempo replaces
+    pub fn set_bpm(&mut self, bpm: f64) -> Result<()> {
+        validate_bpm(bpm)?;
+
+        let beat = self.get_beat_at(self.frame_index);
+        let frame_index = self.frame_index;
+        self.tempo_segments
+            .retain(|segment| segment.frame_index < frame_index);
+        self.tempo_segments.push(TempoSegment {
+            frame_index,
+            beat,
+            bpm,
+        });
+
+        Ok(())
+    }
+
+    fn get_segment_at(&self, frame_index: u64) -> &TempoSegment {
+        let segment_count = self
+            .tempo_segments
+            .partition_point(|segment| segment.frame_index <= frame_index);
+
+        &self.tempo_segments[segment_count.saturating_sub(1)]
+    }
+
+    pub fn get_time_at(&self, frame_index: u64) -> f64 {
+        frame_index as f64 / self.fps
+    }
+
+    pub fn get_beat_at(&self, frame_index: u64) -> f64 {
+        let segment = self.get_segment_at(frame_index);
+        let frame_delta = frame_index as f64 - segment.frame_index as f64;
+
+        segment.beat + frame_delta * segment.bpm / (60.0 * self.fps)
+    }
+
+    pub fn get_bpm_at(&self, frame_index: u64) -> f64 {
+        self.get_segment_at(frame_index).bpm
+    }
+
+    pub fn get_frame_count(&self, duration: f64) -> u64 {
+        (duration * self.fps).round().max(0.0) as u64
+    }
+
+    pub fn drive_input(&self, input: &mut dyn InputProvider) {
+        input.set_time(self.get_time(), true);
+        input.set_beat(self.get_beat(), true);
+    }
+
+    pub fn automate(&self, value: &DataHolder, automation: &Automation) -> DataHolder {
+        automation
+            .apply(value, self.get_beat())
+            .unwrap_or_else(|| value.clone())
+    }
+
+    pub fn automate_variables(
+        &self,
+        variables: &HashMap<String, (DataHolder, Automation)>,
+    ) -> HashMap<String, DataHolder> {
+        variables
+            .iter()
+            .map(|(name, (value, automation))| (name.clone(), self.automate(value, automation)))
+            .collect()
+    }
+}
+
+impl Iterator for FrameClock {
+    type Item = (u64, f64, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = (self.frame_index, self.get_time(), self.get_beat());
+        self.advance();
+
+        Some(frame)
+    }
+}
+
+impl Clock for FrameClock {
+    fn get_time(&self) -> f64 {
+        self.get_time_at(self.frame_index)
+    }
+
+    fn get_beat(&self) -> f64 {
+        self.get_beat_at(self.frame_index)
+    }
+
+    fn get_bpm(&self) -> f64 {
+        self.get_bpm_at(self.frame_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{InputResult, UniformStore};
+
+    #[derive(Default)]
+    struct ClockRecorder {
+        time: f64,
+        beat: f64,
+        uniforms: UniformStore,
+    }
+
+    impl InputProvider for ClockRecorder {
+        fn set_name(&mut self, _name: &str) {}
+
+        fn provides(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        fn get_generation(&self, _uniform_name: &str) -> InputResult<u64> {
+            Ok(0)
+        }
+
+        fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>> {
+            Ok(self.uniforms.get(uniform_name))
+        }
+
+        fn set_property(&mut self, _property: &str, _value: &DataHolder) -> InputResult<()> {
+            Ok(())
+        }
+
+        fn set_beat(&mut self, beat: f64, _sync: bool) {
+            self.beat = beat;
+        }
+
+        fn set_time(&mut self, time: f64, _sync: bool) {
+            self.time = time;
+        }
+    }
+
+    #[test]
+    fn frames_map_to_exact_times_and_beats() {
+        let mut clock = FrameClock::new(60.0, 120.0).unwrap();
+
+        assert_eq!(clock.get_time_at(0), 0.0);
+        assert_eq!(clock.get_time_at(90), 1.5);
+        assert_eq!(clock.get_beat_at(90), 3.0);
+        assert_eq!(clock.get_frame_count(2.0), 120);
+
+        // Positions are derived from the frame index, not accumulated
+        clock.set_frame_index(3_600_000);
+        assert_eq!(clock.get_time(), 60_000.0);
+        assert_eq!(clock.get_beat(), 120_000.0);
+
+        let mut clock = FrameClock::new(30.0, 90.0).unwrap();
+        let frames: Vec<(u64, f64, f64)> = clock.by_ref().take(3).collect();
+        assert_eq!(
+            frames,
+            [(0, 0.0, 0.0), (1, 1.0 / 30.0, 0.05), (2, 2.0 / 30.0, 0.1)]
+        );
+        assert_eq!(clock.get_frame_index(), 3);
+    }
+
+    #[test]
+    fn tempo_changes_keep_the_beat_continuous() {
+        let mut clock = FrameClock::new(60.0, 120.0).unwrap();
+        clock.set_frame_index(60);
+        clock.set_bpm(60.0).unwrap();
+
+        assert_eq!(clock.get_beat(), 2.0);
+        clock.set_frame_index(120);
+        assert_eq!(clock.get_beat(), 3.0);
+        assert_eq!(clock.get_time(), 2.0);
+    }
+
+    #[test]
+    fn seeking_back_uses_the_tempo_of_that_time() {
+        let mut clock = FrameClock::new(60.0, 120.0).unwrap();
+        clock.set_frame_index(60);
+        clock.set_bpm(60.0).unwrap();
+        clock.set_frame_index(120);
+        clock.set_bpm(240.0).unwrap();
+
+        clock.set_frame_index(0);
+        assert_eq!(clock.get_beat(), 0.0);
+        assert_eq!(clock.get_bpm(), 120.0);
+        assert_eq!(clock.get_beat_at(30), 1.0);
+        assert_eq!(clock.get_beat_at(90), 2.5);
+        assert_eq!(clock.get_beat_at(150), 5.0);
+
+        // A tempo change in the past replaces the changes that came after it
+        clock.set_frame_index(90);
+        clock.set_bpm(120.0).unwrap();
+        assert_eq!(clock.get_beat_at(150), 4.5);
+        assert_eq!(clock.get_beat_at(60), 2.0);
+        assert_eq!(clock.get_bpm_at(150), 120.0);
+    }
+
+    #[test]
+    fn invalid_rates_are_rejected() {
+        for fps in [0.0, -30.0, f64::NAN, f64::INFINITY] {
+            assert!(FrameClock::new(fps, 120.0).is_err(), "{} fps", fps);
+        }
+        for bpm in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(FrameClock::new(60.0, bpm).is_err(), "{} bpm", bpm);
+        }
+
+        let mut clock = FrameClock::new(60.0, 120.0).unwrap();
+        assert!(clock.set_bpm(0.0).is_err());
+        assert_eq!(clock.get_bpm(), 120.0);
+    }
+
+    #[test]
+    fn inputs_receive_time_and_beat() {
+        let mut clock = FrameClock::new(50.0, 150.0).unwrap();
+        clock.set_frame_index(100);
+
+        let mut input = ClockRecorder::default();
+        clock.drive_input(&mut input);
+
+        assert_eq!(input.time, 2.0);
+        assert_eq!(input.beat, 5.0);
+    }
+}
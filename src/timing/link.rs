@@ -0,0 +1,1103 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::hash::{BuildHasher, Hasher};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+
+use super::Clock;
+
+pub const LINK_MULTICAST_ADDRESS: Ipv4Addr = Ipv4Addr::new(224, 76, 78, 75);
+pub const LINK_MULTICAST_PORT: u16 = 20808;
+
+const PROTOCOL_HEADER: [u8; 8] = [b'_', b'a', b's', b'd', b'p', b'_', b'v', 1];
+const MESSAGE_HEADER_SIZE: usize = PROTOCOL_HEADER.len() + 12;
+const MAX_MESSAGE_SIZE: usize = 512;
+
+const MEASUREMENT_HEADER: [u8; 8] = [b'_', b'l', b'i', b'n', b'k', b'_', b'v', 1];
+const MEASUREMENT_HEADER_SIZE: usize = MEASUREMENT_HEADER.len() + 1;
+
+const TIMELINE_KEY: u32 = u32::from_be_bytes(*b"tmln");
+const SESSION_MEMBERSHIP_KEY: u32 = u32::from_be_bytes(*b"sess");
+const START_STOP_STATE_KEY: u32 = u32::from_be_bytes(*b"stst");
+const MEASUREMENT_ENDPOINT_KEY: u32 = u32::from_be_bytes(*b"mep4");
+const HOST_TIME_KEY: u32 = u32::from_be_bytes(*b"__ht");
+const GHOST_TIME_KEY: u32 = u32::from_be_bytes(*b"__gt");
+const PREV_GHOST_TIME_KEY: u32 = u32::from_be_bytes(*b"_pgt");
+
+const DEFAULT_TTL: u8 = 5;
+const BROADCAST_INTERVAL: Duration = Duration::from_millis(250);
+
+const MEASUREMENT_SAMPLE_COUNT: usize = 100;
+const MEASUREMENT_TIMEOUT: Duration = Duration::from_millis(50);
+const MAX_MEASUREMENT_RETRIES: u32 = 5;
+const REMEASUREMENT_INTERVAL: Duration = Duration::from_secs(30);
+// Sessions whose ghost times are closer than this are considered equally old
+const SESSION_EPSILON: i64 = 500_000;
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NodeId(pub [u8; 8]);
+
+impl NodeId {
+    pub fn random() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_nanos())
+                .unwrap_or_default(),
+        );
+
+        Self(hasher.finish().to_be_bytes())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timeline {
+    pub bpm: f64,
+    pub beat_origin: f64,
+    pub time_origin: i64,
+}
+
+impl Timeline {
+    pub fn get_beat_at(&self, time: i64) -> f64 {
+        self.beat_origin + (time - self.time_origin) as f64 / 1_000_000.0 * self.bpm / 60.0
+    }
+
+    pub fn get_time_at(&self, beat: f64) -> i64 {
+        self.time_origin + ((beat - self.beat_origin) * 60.0 / self.bpm * 1_000_000.0) as i64
+    }
+
+    pub fn with_bpm(&self, bpm: f64, time: i64) -> Self {
+        Self {
+            bpm,
+            beat_origin: self.get_beat_at(time),
+            time_origin: time,
+        }
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        let micros_per_beat = (60_000_000.0 / self.bpm).round() as i64;
+        let micro_beats = (self.beat_origin * 1_000_000.0).round() as i64;
+
+        buffer.extend_from_slice(&micros_per_beat.to_be_bytes());
+        buffer.extend_from_slice(&micro_beats.to_be_bytes());
+        buffer.extend_from_slice(&self.time_origin.to_be_bytes());
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < 24 {
+            bail!("Truncated Link timeline payload");
+        }
+
+        let micros_per_beat = read_i64(&data[0..8]);
+        if micros_per_beat <= 0 {
+            bail!("Invalid Link tempo: {} micros per beat", micros_per_beat);
+        }
+
+        Ok(Self {
+            bpm: 60_000_000.0 / micros_per_beat as f64,
+            beat_origin: read_i64(&data[8..16]) as f64 / 1_000_000.0,
+            time_origin: read_i64(&data[16..24]),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StartStopState {
+    pub playing: bool,
+    pub beat: f64,
+    pub timestamp: i64,
+}
+
+impl StartStopState {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.playing as u8);
+        buffer.extend_from_slice(&((self.beat * 1_000_000.0).round() as i64).to_be_bytes());
+        buffer.extend_from_slice(&self.timestamp.to_be_bytes());
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < 17 {
+            bail!("Truncated Link start/stop payload");
+        }
+
+        Ok(Self {
+            playing: data[0] != 0,
+            beat: read_i64(&data[1..9]) as f64 / 1_000_000.0,
+            timestamp: read_i64(&data[9..17]),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageType {
+    Alive,
+    Response,
+    ByeBye,
+}
+
+impl MessageType {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Alive => 1,
+            Self::Response => 2,
+            Self::ByeBye => 3,
+        }
+    }
+
+    fn from_byte(value: u8) -> Result<Self> {
+        match value {
+            1 => Ok(Self::Alive),
+            2 => Ok(Self::Response),
+            3 => Ok(Self::ByeBye),
+            _ => bail!("Unknown Link message type: {}", value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeerMessage {
+    pub message_type: MessageType,
+    pub ttl: u8,
+    pub node_id: NodeId,
+    pub session_id: Option<NodeId>,
+    pub timeline: Option<Timeline>,
+    pub start_stop_state: Option<StartStopState>,
+    pub measurement_endpoint: Option<SocketAddrV4>,
+}
+
+impl PeerMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(MAX_MESSAGE_SIZE);
+        buffer.extend_from_slice(&PROTOCOL_HEADER);
+        buffer.push(self.message_type.to_byte());
+        buffer.push(self.ttl);
+        buffer.extend_from_slice(&0u16.to_be_bytes());
+        buffer.extend_from_slice(&self.node_id.0);
+
+        if let Some(timeline) = self.timeline {
+            write_entry(&mut buffer, TIMELINE_KEY, |buffer| timeline.encode(buffer));
+        }
+
+        if let Some(session_id) = self.session_id {
+            write_entry(&mut buffer, SESSION_MEMBERSHIP_KEY, |buffer| {
+                buffer.extend_from_slice(&session_id.0)
+            });
+        }
+
+        if let Some(start_stop_state) = self.start_stop_state {
+            write_entry(&mut buffer, START_STOP_STATE_KEY, |buffer| {
+                start_stop_state.encode(buffer)
+            });
+        }
+
+        if let Some(endpoint) = self.measurement_endpoint {
+            write_entry(&mut buffer, MEASUREMENT_ENDPOINT_KEY, |buffer| {
+                buffer.extend_from_slice(&endpoint.ip().octets());
+                buffer.extend_from_slice(&endpoint.port().to_be_bytes());
+            });
+        }
+
+        buffer
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < MESSAGE_HEADER_SIZE || data[..PROTOCOL_HEADER.len()] != PROTOCOL_HEADER {
+            bail!("Not a Link discovery message");
+        }
+
+        let header = &data[PROTOCOL_HEADER.len()..MESSAGE_HEADER_SIZE];
+        let mut message = Self {
+            message_type: MessageType::from_byte(header[0])?,
+            ttl: header[1],
+            node_id: NodeId(header[4..12].try_into()?),
+            session_id: None,
+            timeline: None,
+            start_stop_state: None,
+            measurement_endpoint: None,
+        };
+
+        let mut payload = &data[MESSAGE_HEADER_SIZE..];
+        while !payload.is_empty() {
+            if payload.len() < 8 {
+                bail!("Truncated Link payload entry header");
+            }
+
+            let key = u32::from_be_bytes(payload[0..4].try_into()?);
+            let size = u32::from_be_bytes(payload[4..8].try_into()?) as usize;
+            let value = payload
+                .get(8..8 + size)
+                .context("Truncated Link payload entry")?;
+
+            match key {
+                TIMELINE_KEY => message.timeline = Some(Timeline::decode(value)?),
+                SESSION_MEMBERSHIP_KEY => {
+                    if value.len() < 8 {
+                        bail!("Truncated Link session membership payload");
+                    }
+                    message.session_id = Some(NodeId(value[0..8].try_into()?));
+                }
+                START_STOP_STATE_KEY => {
+                    message.start_stop_state = Some(StartStopState::decode(value)?)
+                }
+                MEASUREMENT_ENDPOINT_KEY => {
+                    if value.len() < 6 {
+                        bail!("Truncated Link measurement endpoint payload");
+                    }
+                    let address: [u8; 4] = value[0..4].try_into()?;
+                    message.measurement_endpoint = Some(SocketAddrV4::new(
+                        address.into(),
+                        u16::from_be_bytes(value[4..6].try_into()?),
+                    ));
+                }
+                _ => (),
+            }
+
+            payload = &payload[8 + size..];
+        }
+
+        Ok(message)
+    }
+}
+
+fn write_entry<F: FnOnce(&mut Vec<u8>)>(buffer: &mut Vec<u8>, key: u32, write_value: F) {
+    buffer.extend_from_slice(&key.to_be_bytes());
+    let size_offset = buffer.len();
+    buffer.extend_from_slice(&0u32.to_be_bytes());
+
+    write_value(buffer);
+
+    let size = (buffer.len() - size_offset - 4) as u32;
+    buffer[size_offset..size_offset + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+fn read_i64(data: &[u8]) -> i64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[..8]);
+    i64::from_be_bytes(bytes)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeasurementType {
+    Ping,
+    Pong,
+}
+
+impl MeasurementType {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Ping => 1,
+            Self::Pong => 2,
+        }
+    }
+
+    fn from_byte(value: u8) -> Result<Self> {
+        match value {
+            1 => Ok(Self::Ping),
+            2 => Ok(Self::Pong),
+            _ => bail!("Unknown Link measurement message type: {}", value),
+        }
+    }
+}
+
+// Pings carry the sender host time, pongs echo it along with the responder ghost time
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeasurementMessage {
+    pub message_type: MeasurementType,
+    pub session_id: Option<NodeId>,
+    pub host_time: Option<i64>,
+    pub ghost_time: Option<i64>,
+    pub prev_ghost_time: Option<i64>,
+}
+
+impl MeasurementMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(MAX_MESSAGE_SIZE);
+        buffer.extend_from_slice(&MEASUREMENT_HEADER);
+        buffer.push(self.message_type.to_byte());
+
+        if let Some(session_id) = self.session_id {
+            write_entry(&mut buffer, SESSION_MEMBERSHIP_KEY, |buffer| {
+                buffer.extend_from_slice(&session_id.0)
+            });
+        }
+
+        for (key, time) in [
+            (GHOST_TIME_KEY, self.ghost_time),
+            (PREV_GHOST_TIME_KEY, self.prev_ghost_time),
+            (HOST_TIME_KEY, self.host_time),
+        ] {
+            if let Some(time) = time {
+                write_entry(&mut buffer, key, |buffer| {
+                    buffer.extend_from_slice(&time.to_be_bytes())
+                });
+            }
+        }
+
+        buffer
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < MEASUREMENT_HEADER_SIZE
+            || data[..MEASUREMENT_HEADER.len()] != MEASUREMENT_HEADER
+        {
+            bail!("Not a Link measurement message");
+        }
+
+        let mut message = Self {
+            message_type: MeasurementType::from_byte(data[MEASUREMENT_HEADER.len()])?,
+            session_id: None,
+            host_time: None,
+            ghost_time: None,
+            prev_ghost_time: None,
+        };
+
+        let mut payload = &data[MEASUREMENT_HEADER_SIZE..];
+        while !payload.is_empty() {
+            if payload.len() < 8 {
+                bail!("Truncated Link payload entry header");
+            }
+
+            let key = u32::from_be_bytes(payload[0..4].try_into()?);
+            let size = u32::from_be_bytes(payload[4..8].try_into()?) as usize;
+            let value = payload
+                .get(8..8 + size)
+                .context("Truncated Link payload entry")?;
+
+            let read_time = || -> Result<i64> {
+                if value.len() < 8 {
+                    bail!("Truncated Link time payload");
+                }
+                Ok(read_i64(value))
+            };
+
+            match key {
+                SESSION_MEMBERSHIP_KEY => {
+                    if value.len() < 8 {
+                        bail!("Truncated Link session membership payload");
+                    }
+                    message.session_id = Some(NodeId(value[0..8].try_into()?));
+                }
+                HOST_TIME_KEY => message.host_time = Some(read_time()?),
+                GHOST_TIME_KEY => message.ghost_time = Some(read_time()?),
+                PREV_GHOST_TIME_KEY => message.prev_ghost_time = Some(read_time()?),
+                _ => (),
+            }
+
+            payload = &payload[8 + size..];
+        }
+
+        Ok(message)
+    }
+}
+
+// Ghost time is the session-wide time base, each peer measures the offset between its own
+// host clock and the ghost time of the session it joins
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GhostXForm {
+    pub intercept: i64,
+}
+
+impl GhostXForm {
+    pub fn host_to_ghost(&self, host_time: i64) -> i64 {
+        host_time + self.intercept
+    }
+
+    pub fn ghost_to_host(&self, ghost_time: i64) -> i64 {
+        ghost_time - self.intercept
+    }
+}
+
+fn get_median(mut samples: Vec<f64>) -> f64 {
+    samples.sort_by(f64::total_cmp);
+    let count = samples.len();
+    (samples[(count - 1) / 2] + samples[count / 2]) / 2.0
+}
+
+fn validate_bpm(bpm: f64) -> Result<()> {
+    if !bpm.is_finite() || bpm <= 0.0 {
+        bail!("Invalid Link tempo: {} bpm", bpm);
+    }
+
+    Ok(())
+}
+
+// Multicast has no local address to advertise, the route towards the group picks one
+fn get_local_ip(target: SocketAddr) -> Ipv4Addr {
+    UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            socket.connect(target)?;
+            socket.local_addr()
+        })
+        .ok()
+        .and_then(|address| match address.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .unwrap_or(Ipv4Addr::LOCALHOST)
+}
+
+struct Peer {
+    session_id: NodeId,
+    address: SocketAddr,
+    measurement_endpoint: Option<SocketAddr>,
+    expiration: Instant,
+}
+
+struct Measurement {
+    session_id: NodeId,
+    timeline: Timeline,
+    start_stop_state: Option<StartStopState>,
+    endpoint: SocketAddr,
+    samples: Vec<f64>,
+    last_ping: Instant,
+    retries: u32,
+}
+
+pub struct LinkSession {
+    socket: UdpSocket,
+    measurement_socket: UdpSocket,
+    targets: Vec<SocketAddr>,
+    node_id: NodeId,
+    session_id: NodeId,
+    // Host time counts from the session creation and never jumps, unlike the beat timeline
+    start: Instant,
+    ghost_xform: GhostXForm,
+    timeline: Timeline,
+    start_stop_state: StartStopState,
+    peers: HashMap<NodeId, Peer>,
+    measurement: Option<Measurement>,
+    last_measurement: Option<Instant>,
+    last_broadcast: Option<Instant>,
+}
+
+impl LinkSession {
+    pub fn new(socket: UdpSocket, targets: Vec<SocketAddr>, bpm: f64) -> Result<Self> {
+        validate_bpm(bpm)?;
+        socket
+            .set_nonblocking(true)
+            .context("Failed to configure Link socket")?;
+
+        let local_ip = match socket.local_addr()?.ip() {
+            IpAddr::V4(ip) if !ip.is_unspecified() => ip,
+            _ => get_local_ip(SocketAddr::V4(SocketAddrV4::new(
+                LINK_MULTICAST_ADDRESS,
+                LINK_MULTICAST_PORT,
+            ))),
+        };
+        let measurement_socket = UdpSocket::bind(SocketAddrV4::new(local_ip, 0))
+            .context("Failed to bind Link measurement socket")?;
+        measurement_socket
+            .set_nonblocking(true)
+            .context("Failed to configure Link measurement socket")?;
+
+        let node_id = NodeId::random();
+
+        Ok(Self {
+            socket,
+            measurement_socket,
+            targets,
+            node_id,
+            session_id: node_id,
+            start: Instant::now(),
+            ghost_xform: GhostXForm::default(),
+            timeline: Timeline {
+                bpm,
+                beat_origin: 0.0,
+                time_origin: 0,
+            },
+            start_stop_state: StartStopState {
+                playing: false,
+                beat: 0.0,
+                timestamp: 0,
+            },
+            peers: HashMap::new(),
+            measurement: None,
+            last_measurement: None,
+            last_broadcast: None,
+        })
+    }
+
+    pub fn multicast(bpm: f64) -> Result<Self> {
+        let socket = UdpSocket::bind(SocketAddrV4::new(
+            Ipv4Addr::UNSPECIFIED,
+            LINK_MULTICAST_PORT,
+        ))
+        .context("Failed to bind Link discovery socket")?;
+        socket
+            .join_multicast_v4(&LINK_MULTICAST_ADDRESS, &Ipv4Addr::UNSPECIFIED)
+            .context("Failed to join Link multicast group")?;
+        socket.set_multicast_loop_v4(true)?;
+
+        Self::new(
+            socket,
+            vec![SocketAddr::V4(SocketAddrV4::new(
+                LINK_MULTICAST_ADDRESS,
+                LINK_MULTICAST_PORT,
+            ))],
+            bpm,
+        )
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    pub fn get_measurement_address(&self) -> Result<SocketAddr> {
+        Ok(self.measurement_socket.local_addr()?)
+    }
+
+    pub fn add_target(&mut self, target: SocketAddr) {
+        if !self.targets.contains(&target) {
+            self.targets.push(target);
+        }
+    }
+
+    pub fn get_node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    pub fn get_session_id(&self) -> NodeId {
+        self.session_id
+    }
+
+    pub fn get_peer_count(&self) -> usize {
+        self.peers
+            .values()
+            .filter(|peer| peer.session_id == self.session_id)
+            .count()
+    }
+
+    pub fn get_timeline(&self) -> Timeline {
+        self.timeline
+    }
+
+    pub fn get_ghost_xform(&self) -> GhostXForm {
+        self.ghost_xform
+    }
+
+    pub fn get_host_time(&self) -> i64 {
+        self.start.elapsed().as_micros() as i64
+    }
+
+    pub fn get_ghost_time(&self) -> i64 {
+        self.ghost_xform.host_to_ghost(self.get_host_time())
+    }
+
+    pub fn is_measuring(&self) -> bool {
+        self.measurement.is_some()
+    }
+
+    pub fn get_phase(&self, quantum: f64) -> f64 {
+        if quantum <= 0.0 {
+            return 0.0;
+        }
+
+        self.get_beat().rem_euclid(quantum)
+    }
+
+    pub fn set_bpm(&mut self, bpm: f64) -> Result<()> {
+        validate_bpm(bpm)?;
+        self.timeline = self.timeline.with_bpm(bpm, self.get_ghost_time());
+        self.broadcast(MessageType::Alive)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.start_stop_state.playing
+    }
+
+    pub fn set_playing(&mut self, playing: bool) -> Result<()> {
+        let now = self.get_ghost_time();
+        self.start_stop_state = StartStopState {
+            playing,
+            beat: self.timeline.get_beat_at(now),
+            timestamp: now,
+        };
+        self.broadcast(MessageType::Alive)
+    }
+
+    pub fn update(&mut self) -> Result<()> {
+        let mut buffer = [0; MAX_MESSAGE_SIZE];
+
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((size, address)) => {
+                    if let Ok(message) = PeerMessage::decode(&buffer[..size]) {
+                        self.handle_message(message, address)?;
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error).context("Failed to receive Link message"),
+            }
+        }
+
+        loop {
+            match self.measurement_socket.recv_from(&mut buffer) {
+                Ok((size, address)) => {
+                    if let Ok(message) = MeasurementMessage::decode(&buffer[..size]) {
+                        self.handle_measurement(message, address)?;
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    return Err(error).context("Failed to receive Link measurement message")
+                }
+            }
+        }
+
+        let now = Instant::now();
+        self.peers.retain(|_, peer| peer.expiration > now);
+
+        self.update_measurement()?;
+
+        let broadcast_due = self
+            .last_broadcast
+            .map(|last_broadcast| now.duration_since(last_broadcast) >= BROADCAST_INTERVAL)
+            .unwrap_or(true);
+        if broadcast_due {
+            self.broadcast(MessageType::Alive)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn leave(&mut self) -> Result<()> {
+        self.broadcast(MessageType::ByeBye)
+    }
+
+    fn create_message(&self, message_type: MessageType) -> PeerMessage {
+        PeerMessage {
+            message_type,
+            ttl: DEFAULT_TTL,
+            node_id: self.node_id,
+            session_id: Some(self.session_id),
+            timeline: Some(self.timeline),
+            start_stop_state: Some(self.start_stop_state),
+            measurement_endpoint: match self.measurement_socket.local_addr() {
+                Ok(SocketAddr::V4(address)) => Some(address),
+                _ => None,
+            },
+        }
+    }
+
+    fn broadcast(&mut self, message_type: MessageType) -> Result<()> {
+        let message = self.create_message(message_type).encode();
+        for target in self.targets.iter() {
+            self.socket
+                .send_to(&message, target)
+                .with_context(|| format!("Failed to send Link message to {}", target))?;
+        }
+        self.last_broadcast = Some(Instant::now());
+
+        Ok(())
+    }
+
+    fn handle_message(&mut self, message: PeerMessage, address: SocketAddr) -> Result<()> {
+        if message.node_id == self.node_id {
+            return Ok(());
+        }
+
+        if message.message_type == MessageType::ByeBye {
+            self.peers.remove(&message.node_id);
+            return Ok(());
+        }
+
+        let (session_id, timeline) = match (message.session_id, message.timeline) {
+            (Some(session_id), Some(timeline)) => (session_id, timeline),
+            _ => return Ok(()),
+        };
+
+        let measurement_endpoint = message.measurement_endpoint.map(|endpoint| {
+            if endpoint.ip().is_unspecified() {
+                SocketAddr::new(address.ip(), endpoint.port())
+            } else {
+                SocketAddr::V4(endpoint)
+            }
+        });
+
+        let is_new_peer = !self.peers.contains_key(&message.node_id);
+        self.peers.insert(
+            message.node_id,
+            Peer {
+                session_id,
+                address,
+                measurement_endpoint,
+                expiration: Instant::now() + Duration::from_secs(message.ttl as u64),
+            },
+        );
+
+        // Timelines of the same session share the ghost time base, the latest change wins
+        if session_id == self.session_id {
+            if timeline.time_origin > self.timeline.time_origin {
+                self.timeline = timeline;
+            }
+
+            if let Some(start_stop_state) = message.start_stop_state {
+                if start_stop_state.timestamp > self.start_stop_state.timestamp {
+                    self.start_stop_state = start_stop_state;
+                }
+            }
+        } else if self.measurement.is_none() {
+            if let Some(endpoint) = measurement_endpoint {
+                self.start_measurement(session_id, timeline, message.start_stop_state, endpoint)?;
+            }
+        } else if let Some(measurement) = &mut self.measurement {
+            if measurement.session_id == session_id {
+                measurement.timeline = timeline;
+                measurement.start_stop_state = message.start_stop_state;
+            }
+        }
+
+        if message.message_type == MessageType::Alive && is_new_peer {
+            let response = self.create_message(MessageType::Response).encode();
+            let peer_address = self.peers[&message.node_id].address;
+            self.socket
+                .send_to(&response, peer_address)
+                .with_context(|| format!("Failed to answer Link peer at {}", peer_address))?;
+        }
+
+        Ok(())
+    }
+
+    fn start_measurement(
+        &mut self,
+        session_id: NodeId,
+        timeline: Timeline,
+        start_stop_state: Option<StartStopState>,
+        endpoint: SocketAddr,
+    ) -> Result<()> {
+        self.measurement = Some(Measurement {
+            session_id,
+            timeline,
+            start_stop_state,
+            endpoint,
+            samples: Vec::with_capacity(MEASUREMENT_SAMPLE_COUNT + 1),
+            last_ping: Instant::now(),
+            retries: 0,
+        });
+
+        self.send_ping(None)
+    }
+
+    fn send_ping(&mut self, prev_ghost_time: Option<i64>) -> Result<()> {
+        let host_time = self.get_host_time();
+        let measurement = match &mut self.measurement {
+            Some(measurement) => measurement,
+            None => return Ok(()),
+        };
+
+        let ping = MeasurementMessage {
+            message_type: MeasurementType::Ping,
+            session_id: None,
+            host_time: Some(host_time),
+            ghost_time: None,
+            prev_ghost_time,
+        };
+        measurement.last_ping = Instant::now();
+        self.measurement_socket
+            .send_to(&ping.encode(), measurement.endpoint)
+            .with_context(|| format!("Failed to ping Link peer at {}", measurement.endpoint))?;
+
+        Ok(())
+    }
+
+    fn update_measurement(&mut self) -> Result<()> {
+        let timed_out = match &mut self.measurement {
+            Some(measurement) if measurement.last_ping.elapsed() >= MEASUREMENT_TIMEOUT => {
+                measurement.retries += 1;
+                measurement.samples.clear();
+                measurement.retries > MAX_MEASUREMENT_RETRIES
+            }
+            Some(_) => return Ok(()),
+            None => {
+                // Host clocks drift, so the current session gets measured again periodically
+                let remeasurement_due = self.last_measurement.is_some_and(|last_measurement| {
+                    last_measurement.elapsed() >= REMEASUREMENT_INTERVAL
+                });
+                let session_peer = self
+                    .peers
+                    .values()
+                    .filter(|peer| peer.session_id == self.session_id)
+                    .find_map(|peer| peer.measurement_endpoint);
+
+                if let (true, Some(endpoint)) = (remeasurement_due, session_peer) {
+                    self.start_measurement(self.session_id, self.timeline, None, endpoint)?;
+                }
+                return Ok(());
+            }
+        };
+
+        if timed_out {
+            self.measurement = None;
+            self.last_measurement = Some(Instant::now());
+            Ok(())
+        } else {
+            self.send_ping(None)
+        }
+    }
+
+    fn handle_measurement(
+        &mut self,
+        message: MeasurementMessage,
+        address: SocketAddr,
+    ) -> Result<()> {
+        match message.message_type {
+            MeasurementType::Ping => {
+                let pong = MeasurementMessage {
+                    message_type: MeasurementType::Pong,
+                    session_id: Some(self.session_id),
+                    host_time: message.host_time,
+                    ghost_time: Some(self.get_ghost_time()),
+                    prev_ghost_time: message.prev_ghost_time,
+                };
+                self.measurement_socket
+                    .send_to(&pong.encode(), address)
+                    .with_context(|| format!("Failed to answer Link ping from {}", address))?;
+
+                Ok(())
+            }
+            MeasurementType::Pong => self.handle_pong(message),
+        }
+    }
+
+    fn handle_pong(&mut self, message: MeasurementMessage) -> Result<()> {
+        let host_time = self.get_host_time();
+        let measurement = match &mut self.measurement {
+            Some(measurement) if message.session_id == Some(measurement.session_id) => measurement,
+            _ => return Ok(()),
+        };
+
+        let (ghost_time, prev_host_time) = match (message.ghost_time, message.host_time) {
+            (Some(ghost_time), Some(prev_host_time)) => (ghost_time, prev_host_time),
+            _ => return Ok(()),
+        };
+
+        // The responder ghost time is matched against the middle of the round trip, and the
+        // previous ghost time against the host time of the ping that follows it
+        measurement
+            .samples
+            .push(ghost_time as f64 - (host_time + prev_host_time) as f64 / 2.0);
+        if let Some(prev_ghost_time) = message.prev_ghost_time {
+            measurement
+                .samples
+                .push((ghost_time + prev_ghost_time) as f64 / 2.0 - prev_host_time as f64);
+        }
+
+        if measurement.samples.len() < MEASUREMENT_SAMPLE_COUNT {
+            return self.send_ping(Some(ghost_time));
+        }
+
+        let measurement = self.measurement.take().unwrap();
+        self.last_measurement = Some(Instant::now());
+        self.finish_measurement(measurement);
+
+        Ok(())
+    }
+
+    fn finish_measurement(&mut self, measurement: Measurement) {
+        let ghost_xform = GhostXForm {
+            intercept: get_median(measurement.samples).round() as i64,
+        };
+
+        if measurement.session_id == self.session_id {
+            self.ghost_xform = ghost_xform;
+            return;
+        }
+
+        // The session whose ghost time runs ahead has been alive longer and wins, close
+        // calls are settled by the lowest session id as Link does
+        let ghost_difference = ghost_xform.intercept - self.ghost_xform.intercept;
+        let joins_session = ghost_difference > SESSION_EPSILON
+            || (ghost_difference.abs() <= SESSION_EPSILON
+                && measurement.session_id < self.session_id);
+
+        if joins_session {
+            self.session_id = measurement.session_id;
+            self.ghost_xform = ghost_xform;
+            self.timeline = measurement.timeline;
+            if let Some(start_stop_state) = measurement.start_stop_state {
+                self.start_stop_state = start_stop_state;
+            }
+        }
+    }
+}
+
+impl Drop for LinkSession {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
+
+impl Clock for LinkSession {
+    fn get_time(&self) -> f64 {
+        self.get_host_time() as f64 / 1_000_000.0
+    }
+
+    fn get_beat(&self) -> f64 {
+        self.timeline.get_beat_at(self.get_ghost_time())
+    }
+
+    fn get_bpm(&self) -> f64 {
+        self.timeline.bpm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_sessions(count: usize, bpm: f64) -> Vec<LinkSession> {
+        let mut sessions: Vec<LinkSession> = (0..count)
+            .map(|_| {
+                let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+                LinkSession::new(socket, Vec::new(), bpm).unwrap()
+            })
+            .collect();
+
+        let addresses: Vec<SocketAddr> = sessions
+            .iter()
+            .map(|session| session.local_addr().unwrap())
+            .collect();
+        for session in sessions.iter_mut() {
+            for address in addresses.iter() {
+                if *address != session.local_addr().unwrap() {
+                    session.add_target(*address);
+                }
+            }
+        }
+
+        sessions
+    }
+
+    fn update_until<F: Fn(&[LinkSession]) -> bool>(sessions: &mut [LinkSession], done: F) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done(sessions) {
+            assert!(Instant::now() < deadline, "Link sessions did not settle");
+            for session in sessions.iter_mut() {
+                session.update().unwrap();
+            }
+            std::thread::sleep(Duration::from_micros(200));
+        }
+    }
+
+    fn is_converged(sessions: &[LinkSession]) -> bool {
+        sessions.iter().all(|session| {
+            session.get_session_id() == sessions[0].get_session_id()
+                && session.get_peer_count() == sessions.len() - 1
+                && !session.is_measuring()
+        })
+    }
+
+    #[test]
+    fn peer_message_roundtrip() {
+        let message = PeerMessage {
+            message_type: MessageType::Alive,
+            ttl: DEFAULT_TTL,
+            node_id: NodeId([1, 2, 3, 4, 5, 6, 7, 8]),
+            session_id: Some(NodeId([8, 7, 6, 5, 4, 3, 2, 1])),
+            timeline: Some(Timeline {
+                bpm: 120.0,
+                beat_origin: 4.5,
+                time_origin: 1_234_567,
+            }),
+            start_stop_state: Some(StartStopState {
+                playing: true,
+                beat: 2.25,
+                timestamp: -42,
+            }),
+            measurement_endpoint: Some(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 4321)),
+        };
+
+        assert_eq!(PeerMessage::decode(&message.encode()).unwrap(), message);
+    }
+
+    #[test]
+    fn measurement_message_roundtrip() {
+        let message = MeasurementMessage {
+            message_type: MeasurementType::Pong,
+            session_id: Some(NodeId([9; 8])),
+            host_time: Some(1_000),
+            ghost_time: Some(-5_000),
+            prev_ghost_time: Some(7),
+        };
+        let encoded = message.encode();
+
+        assert_eq!(MeasurementMessage::decode(&encoded).unwrap(), message);
+        assert!(PeerMessage::decode(&encoded).is_err());
+        assert!(MeasurementMessage::decode(&encoded[..encoded.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_tempo() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert!(LinkSession::new(socket, Vec::new(), 0.0).is_err());
+
+        let mut session = create_sessions(1, 120.0).remove(0);
+        assert!(session.set_bpm(0.0).is_err());
+        assert!(session.set_bpm(-60.0).is_err());
+        assert!(session.set_bpm(f64::NAN).is_err());
+        assert_eq!(session.get_bpm(), 120.0);
+    }
+
+    #[test]
+    fn time_does_not_jump_on_tempo_change() {
+        let mut session = create_sessions(1, 120.0).remove(0);
+        std::thread::sleep(Duration::from_millis(20));
+
+        let time = session.get_time();
+        session.set_bpm(90.0).unwrap();
+        let elapsed = session.get_time() - time;
+
+        assert!(time >= 0.02);
+        assert!((0.0..0.01).contains(&elapsed));
+    }
+
+    #[test]
+    fn peers_converge_to_one_session() {
+        let mut sessions = create_sessions(3, 120.0);
+        sessions[1].set_bpm(100.0).unwrap();
+        sessions[2].set_bpm(140.0).unwrap();
+        let lowest_session_id = sessions
+            .iter()
+            .map(|session| session.get_session_id())
+            .min()
+            .unwrap();
+
+        update_until(&mut sessions, is_converged);
+
+        assert_eq!(sessions[0].get_session_id(), lowest_session_id);
+        let bpm = sessions[0].get_bpm();
+        // Tempos travel as whole microseconds per beat
+        for session in sessions.iter() {
+            assert!((session.get_bpm() - bpm).abs() < 0.001);
+        }
+
+        // Beats are sampled back to back, a few milliseconds of slack covers the gap
+        let beats: Vec<f64> = sessions.iter().map(|session| session.get_beat()).collect();
+        for beat in beats.iter() {
+            assert!((beat - beats[0]).abs() < 0.01, "{:?}", beats);
+        }
+    }
+
+    #[test]
+    fn tempo_changes_propagate() {
+        let mut sessions = create_sessions(3, 120.0);
+        update_until(&mut sessions, is_converged);
+
+        sessions[2].set_bpm(150.0).unwrap();
+        update_until(&mut sessions, |sessions| {
+            sessions
+                .iter()
+                .all(|session| (session.get_bpm() - 150.0).abs() < 0.001)
+        });
+
+        let beats: Vec<f64> = sessions.iter().map(|session| session.get_beat()).collect();
+        for beat in beats.iter() {
+            assert!((beat - beats[0]).abs() < 0.01, "{:?}", beats);
+        }
+    }
+}
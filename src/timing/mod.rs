@@ -0,0 +1,7 @@
+pub mod clock;
+pub mod link;
+pub mod scheduler;
+
+pub use clock::*;
+pub use link::*;
+pub use scheduler::*;
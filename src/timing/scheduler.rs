@@ -0,0 +1,431 @@
+use anyhow::{bail, Result};
+
+use crate::config::filter::FilterMode;
+use crate::config::project::ProjectConfig;
+use crate::config::rendering::RenderStageConfig;
+use crate::types::{Automation, BufferPrecision, DataHolder, InputSampler};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum Quantization {
+    None,
+    Beat,
+    Bar,
+    Bars(u32),
+}
+
+impl Quantization {
+    pub fn get_length(&self, beats_per_bar: f64) -> f64 {
+        match *self {
+            Self::None => 0.0,
+            Self::Beat => 1.0,
+            Self::Bar => beats_per_bar,
+            Self::Bars(bar_count) => beats_per_bar * bar_count as f64,
+        }
+    }
+
+    pub fn get_next_boundary(&self, beat: f64, beats_per_bar: f64) -> f64 {
+        let length = self.get_length(beats_per_bar);
+        if length <= 0.0 {
+            return beat;
+        }
+
+        let boundary = (beat / length).ceil() * length;
+        if boundary - beat < 1e-9 {
+            beat
+        } else {
+            boundary
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParameterChange {
+    ProjectVariable {
+        name: String,
+        value: DataHolder,
+    },
+    ProjectVariableAutomation {
+        name: String,
+        automation: Automation,
+    },
+    StageVariable {
+        stage: String,
+        name: String,
+        value: DataHolder,
+    },
+    StageVariableAutomation {
+        stage: String,
+        name: String,
+        automation: Automation,
+    },
+    StageFilter {
+        stage: String,
+        filter: String,
+        filter_mode_params: Option<FilterMode>,
+    },
+    StageInput {
+        stage: String,
+        name: String,
+        sampler: InputSampler,
+    },
+    StagePrecision {
+        stage: String,
+        precision: BufferPrecision,
+    },
+}
+
+impl ParameterChange {
+    pub fn apply(&self, project: &mut ProjectConfig) -> Result<()> {
+        match self {
+            Self::ProjectVariable { name, value } => {
+                if let Some(variable) = project.variables.get_mut(name) {
+                    variable.0 = value.clone();
+                } else {
+                    bail!("Unknown project variable: {}", name);
+                }
+            }
+            Self::ProjectVariableAutomation { name, automation } => {
+                if let Some(variable) = project.variables.get_mut(name) {
+                    variable.1 = *automation;
+                } else {
+                    bail!("Unknown project variable: {}", name);
+                }
+            }
+            Self::StageVariable { stage, name, value } => {
+                let stage_config = get_stage_mut(project, stage)?;
+                if let Some(variable) = stage_config.variables.get_mut(name) {
+                    variable.0 = value.clone();
+                } else {
+                    bail!("Unknown variable {} in stage {}", name, stage);
+                }
+            }
+            Self::StageVariableAutomation {
+                stage,
+                name,
+                automation,
+            } => {
+                let stage_config = get_stage_mut(project, stage)?;
+                if let Some(variable) = stage_config.variables.get_mut(name) {
+                    variable.1 = *automation;
+                } else {
+                    bail!("Unknown variable {} in stage {}", name, stage);
+                }
+            }
+            Self::StageFilter {
+                stage,
+                filter,
+                filter_mode_params,
+            } => {
+                let stage_config = get_stage_mut(project, stage)?;
+                stage_config.filter = filter.clone();
+                if let Some(filter_mode_params) = filter_mode_params {
+                    stage_config.filter_mode_params = *filter_mode_params;
+                }
+            }
+            Self::StageInput {
+                stage,
+                name,
+                sampler,
+            } => {
+                if let Some(stage_index) = project.get_stage_index(stage) {
+                    project.resolve_input(stage_index, sampler.get_reference())?;
+                }
+
+                let stage_config = get_stage_mut(project, stage)?;
+                stage_config.inputs.insert(name.clone(), sampler.clone());
+            }
+            Self::StagePrecision { stage, precision } => {
+                get_stage_mut(project, stage)?.precision = *precision;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn get_stage_mut<'a>(
+    project: &'a mut ProjectConfig,
+    stage: &str,
+) -> Result<&'a mut RenderStageConfig> {
+    if project.final_stage.name == stage {
+        return Ok(&mut project.final_stage);
+    }
+
+    match project
+        .render_chain
+        .iter_mut()
+        .find(|stage_config| stage_config.name == stage)
+    {
+        Some(stage_config) => Ok(stage_config),
+        None => bail!("Unknown render stage: {}", stage),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ChangeId(u64);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduledChange {
+    pub id: ChangeId,
+    pub beat: f64,
+    pub change: ParameterChange,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangeScheduler {
+    beats_per_bar: f64,
+    next_id: u64,
+    pending: Vec<ScheduledChange>,
+}
+
+impl Default for ChangeScheduler {
+    fn default() -> Self {
+        Self::new(4.0)
+    }
+}
+
+impl ChangeScheduler {
+    pub fn new(beats_per_bar: f64) -> Self {
+        Self {
+            beats_per_bar,
+            next_id: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn set_beats_per_bar(&mut self, beats_per_bar: f64) {
+        self.beats_per_bar = beats_per_bar;
+    }
+
+    pub fn schedule(
+        &mut self,
+        change: ParameterChange,
+        quantization: Quantization,
+        beat: f64,
+    ) -> ChangeId {
+        let release_beat = quantization.get_next_boundary(beat, self.beats_per_bar);
+        self.schedule_at(change, release_beat)
+    }
+
+    pub fn schedule_at(&mut self, change: ParameterChange, beat: f64) -> ChangeId {
+        let id = ChangeId(self.next_id);
+        self.next_id += 1;
+
+        // Keep the queue sorted by release beat, changes on the same beat keep their order
+        let index = self
+            .pending
+            .iter()
+            .position(|scheduled| scheduled.beat > beat)
+            .unwrap_or(self.pending.len());
+        self.pending
+            .insert(index, ScheduledChange { id, beat, change });
+
+        id
+    }
+
+    pub fn cancel(&mut self, id: ChangeId) -> bool {
+        let pending_count = self.pending.len();
+        self.pending.retain(|scheduled| scheduled.id != id);
+
+        self.pending.len() != pending_count
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.pending.clear();
+    }
+
+    pub fn get_pending(&self) -> &[ScheduledChange] {
+        &self.pending
+    }
+
+    pub fn get_next_release(&self) -> Option<f64> {
+        self.pending.first().map(|scheduled| scheduled.beat)
+    }
+
+    pub fn release(&mut self, beat: f64) -> Vec<ParameterChange> {
+        let due_count = self
+            .pending
+            .iter()
+            .position(|scheduled| scheduled.beat > beat)
+            .unwrap_or(self.pending.len());
+
+        self.pending
+            .drain(..due_count)
+            .map(|scheduled| scheduled.change)
+            .collect()
+    }
+
+    pub fn apply(&mut self, beat: f64, project: &mut ProjectConfig) -> Result<usize> {
+        // A failing change must not take the rest of the released batch down with it
+        let changes = self.release(beat);
+        let errors: Vec<String> = changes
+            .iter()
+            .filter_map(|change| change.apply(project).err())
+            .map(|error| format!("{:#}", error))
+            .collect();
+
+        if !errors.is_empty() {
+            bail!(
+                "Failed to apply {} of {} scheduled changes: {}",
+                errors.len(),
+                changes.len(),
+                errors.join("; ")
+            );
+        }
+
+        Ok(changes.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::config::project::ViewConfig;
+    use crate::config::server::ServerConfig;
+
+    fn get_project() -> ProjectConfig {
+        let stage = RenderStageConfig {
+            name: "main".to_string(),
+            filter: "copy".to_string(),
+            filter_mode_params: FilterMode::Rectangle(0.0, 0.0, 1.0, 1.0),
+            inputs: HashMap::new(),
+            variables: HashMap::new(),
+            precision: BufferPrecision::default(),
+        };
+        let mut variables = HashMap::new();
+        variables.insert(
+            "level".to_string(),
+            (DataHolder::Float(0.0), Automation::None),
+        );
+
+        ProjectConfig {
+            bpm: 120.0,
+            view: ViewConfig {
+                width: 640,
+                height: 480,
+                fullscreen: false,
+                target_fps: 60.0,
+                dynamic: false,
+                vsync: true,
+                screenshot_path: PathBuf::new(),
+                screenshot: false,
+                screenshot_frame_count: 0,
+                locked_speed: false,
+            },
+            server: ServerConfig {
+                ip: "127.0.0.1".to_string(),
+                port: 3000,
+                enable: false,
+            },
+            variables,
+            inputs: HashMap::new(),
+            render_chain: Vec::new(),
+            final_stage: stage,
+        }
+    }
+
+    fn set_level(value: f32) -> ParameterChange {
+        ParameterChange::ProjectVariable {
+            name: "level".to_string(),
+            value: DataHolder::Float(value),
+        }
+    }
+
+    #[test]
+    fn boundaries_round_up_to_the_quantization() {
+        let cases = [
+            (Quantization::None, 1.3, 1.3),
+            (Quantization::Beat, 1.0, 1.0),
+            (Quantization::Beat, 1.0 - 1e-12, 1.0 - 1e-12),
+            (Quantization::Beat, 1.001, 2.0),
+            (Quantization::Beat, 0.0, 0.0),
+            (Quantization::Bar, 4.0, 4.0),
+            (Quantization::Bar, 0.5, 4.0),
+            (Quantization::Bar, 4.01, 8.0),
+            (Quantization::Bars(2), 3.0, 8.0),
+            (Quantization::Bars(2), 8.0, 8.0),
+            (Quantization::Bars(2), 8.5, 16.0),
+            (Quantization::Bars(0), 2.5, 2.5),
+        ];
+
+        for (quantization, beat, boundary) in cases {
+            assert_eq!(
+                quantization.get_next_boundary(beat, 4.0),
+                boundary,
+                "{:?} at {}",
+                quantization,
+                beat
+            );
+        }
+
+        assert_eq!(Quantization::Bar.get_next_boundary(1.0, 3.0), 3.0);
+        assert_eq!(Quantization::Bars(4).get_length(3.0), 12.0);
+    }
+
+    #[test]
+    fn changes_are_released_on_their_boundary_in_order() {
+        let mut scheduler = ChangeScheduler::new(4.0);
+        let bar = scheduler.schedule(set_level(1.0), Quantization::Bar, 0.5);
+        let beat = scheduler.schedule(set_level(2.0), Quantization::Beat, 0.5);
+        let same_bar = scheduler.schedule(set_level(3.0), Quantization::Bar, 1.5);
+        let cancelled = scheduler.schedule(set_level(4.0), Quantization::Bars(2), 1.5);
+
+        assert_eq!(scheduler.get_next_release(), Some(1.0));
+        assert!(scheduler.cancel(cancelled));
+        assert!(!scheduler.cancel(cancelled));
+        assert_eq!(
+            scheduler
+                .get_pending()
+                .iter()
+                .map(|scheduled| scheduled.id)
+                .collect::<Vec<ChangeId>>(),
+            [beat, bar, same_bar]
+        );
+
+        assert!(scheduler.release(0.999).is_empty());
+        assert_eq!(scheduler.release(1.0), [set_level(2.0)]);
+        assert!(scheduler.release(3.999).is_empty());
+        assert_eq!(scheduler.release(4.0), [set_level(1.0), set_level(3.0)]);
+        assert_eq!(scheduler.get_next_release(), None);
+    }
+
+    #[test]
+    fn apply_collects_every_error() {
+        let mut project = get_project();
+        let mut scheduler = ChangeScheduler::default();
+        scheduler.schedule_at(
+            ParameterChange::ProjectVariable {
+                name: "missing".to_string(),
+                value: DataHolder::Float(1.0),
+            },
+            4.0,
+        );
+        scheduler.schedule_at(set_level(0.5), 4.0);
+        scheduler.schedule_at(
+            ParameterChange::StagePrecision {
+                stage: "missing".to_string(),
+                precision: BufferPrecision::default(),
+            },
+            4.0,
+        );
+        scheduler.schedule_at(set_level(0.75), 8.0);
+
+        let error = scheduler.apply(4.0, &mut project).unwrap_err().to_string();
+        assert!(error.contains("2 of 3"), "{}", error);
+        assert!(
+            error.contains("Unknown project variable: missing"),
+            "{}",
+            error
+        );
+        assert!(error.contains("Unknown render stage: missing"), "{}", error);
+
+        // The valid change of the batch still applies, later ones stay queued
+        assert_eq!(project.variables["level"].0, DataHolder::Float(0.5));
+        assert_eq!(scheduler.get_pending().len(), 1);
+        assert_eq!(scheduler.apply(8.0, &mut project).unwrap(), 1);
+        assert_eq!(project.variables["level"].0, DataHolder::Float(0.75));
+    }
+}
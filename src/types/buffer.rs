@@ -3,15 +3,10 @@ pub struct Buffer {
     pub data: Option<Vec<u8>>,
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
 pub enum BufferPrecision {
+    #[default]
     U8,
     F16,
     F32,
 }
-
-impl Default for BufferPrecision {
-    fn default() -> Self {
-        Self::U8
-    }
-}
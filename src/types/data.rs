@@ -37,7 +37,74 @@ pub enum DataHolder {
     SrgbTexture(((u32, u32), Vec<u8>)),
 }
 
-impl<'a> Mul for &'a DataHolder {
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum DataType {
+    Float,
+    Float2,
+    Float3,
+    Float4,
+    FloatArray,
+
+    Int,
+    Int2,
+    Int3,
+    Int4,
+    IntArray,
+
+    Mat2,
+    Mat3,
+    Mat4,
+
+    Bool,
+    BoolArray,
+
+    ByteArray,
+
+    String,
+
+    Texture,
+    SrgbTexture,
+}
+
+impl DataType {
+    pub fn is_texture(&self) -> bool {
+        matches!(self, Self::Texture | Self::SrgbTexture)
+    }
+}
+
+impl DataHolder {
+    pub fn get_type(&self) -> DataType {
+        match self {
+            Self::Float(_) => DataType::Float,
+            Self::Float2(_) => DataType::Float2,
+            Self::Float3(_) => DataType::Float3,
+            Self::Float4(_) => DataType::Float4,
+            Self::FloatArray(_) => DataType::FloatArray,
+
+            Self::Int(_) => DataType::Int,
+            Self::Int2(_) => DataType::Int2,
+            Self::Int3(_) => DataType::Int3,
+            Self::Int4(_) => DataType::Int4,
+            Self::IntArray(_) => DataType::IntArray,
+
+            Self::Mat2(_) => DataType::Mat2,
+            Self::Mat3(_) => DataType::Mat3,
+            Self::Mat4(_) => DataType::Mat4,
+
+            Self::Bool(_) => DataType::Bool,
+            Self::BoolArray(_) => DataType::BoolArray,
+
+            Self::ByteArray(_) => DataType::ByteArray,
+
+            Self::String(_) => DataType::String,
+
+            Self::Texture(_) => DataType::Texture,
+            Self::SrgbTexture(_) => DataType::SrgbTexture,
+        }
+    }
+}
+
+impl Mul for &DataHolder {
     type Output = DataHolder;
 
     fn mul(self, other: Self) -> DataHolder {
@@ -94,7 +161,7 @@ impl<'a> Mul for &'a DataHolder {
     }
 }
 
-impl<'a> Add for &'a DataHolder {
+impl Add for &DataHolder {
     type Output = DataHolder;
 
     fn add(self, other: Self) -> DataHolder {
@@ -151,7 +218,7 @@ impl<'a> Add for &'a DataHolder {
     }
 }
 
-impl<'a> Sub for &'a DataHolder {
+impl Sub for &DataHolder {
     type Output = DataHolder;
 
     fn sub(self, other: Self) -> DataHolder {
@@ -208,7 +275,7 @@ impl<'a> Sub for &'a DataHolder {
     }
 }
 
-impl<'a> Div for &'a DataHolder {
+impl Div for &DataHolder {
     type Output = DataHolder;
 
     fn div(self, other: Self) -> DataHolder {
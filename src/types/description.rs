@@ -0,0 +1,185 @@
+use super::{DataHolder, DataRange, DataType, InputError, InputResult};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum UpdateFrequency {
+    Static,
+    PerFrame,
+    OnChange,
+    Rate(f32),
+    Unknown,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct UniformDescription {
+    pub name: String,
+    pub data_type: Option<DataType>,
+    pub range: DataRange,
+    pub texture: bool,
+    pub update_frequency: UpdateFrequency,
+}
+
+impl UniformDescription {
+    pub fn new(
+        name: &str,
+        data_type: DataType,
+        range: DataRange,
+        update_frequency: UpdateFrequency,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            data_type: Some(data_type),
+            range,
+            texture: data_type.is_texture(),
+            update_frequency,
+        }
+    }
+
+    pub fn unknown(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            data_type: None,
+            range: DataRange::None,
+            texture: false,
+            update_frequency: UpdateFrequency::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PropertyDescription {
+    pub name: String,
+    pub data_type: DataType,
+    pub range: DataRange,
+    pub description: String,
+}
+
+impl PropertyDescription {
+    pub fn new(name: &str, data_type: DataType, range: DataRange, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            data_type,
+            range,
+            description: description.to_string(),
+        }
+    }
+
+    pub fn check(&self, value: &DataHolder) -> InputResult<()> {
+        if value.get_type() != self.data_type {
+            return Err(InputError::wrong_value_type(
+                &self.name,
+                &[self.data_type],
+                value.get_type(),
+            ));
+        }
+
+        let (min, max, in_range) = match (self.range, value) {
+            (DataRange::FloatRange(min, max, _), DataHolder::Float(value)) => {
+                (min, max, (min..=max).contains(&(*value as f64)))
+            }
+            (DataRange::IntRange(min, max, _), DataHolder::Int(value)) => (
+                min as f64,
+                max as f64,
+                (min..=max).contains(&(*value as i64)),
+            ),
+            (DataRange::ColorRange, DataHolder::Float3(color)) => (
+                0.0,
+                1.0,
+                color.iter().all(|channel| (0.0..=1.0).contains(channel)),
+            ),
+            _ => return Ok(()),
+        };
+        if !in_range {
+            return Err(InputError::OutOfRange {
+                name: self.name.clone(),
+                min,
+                max,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_rejects_other_types() {
+        let description =
+            PropertyDescription::new("speed", DataType::Float, DataRange::None, "Speed");
+        assert!(description.check(&DataHolder::Float(100.0)).is_ok());
+        assert_eq!(
+            description.check(&DataHolder::Int(1)),
+            Err(InputError::wrong_value_type(
+                "speed",
+                &[DataType::Float],
+                DataType::Int
+            ))
+        );
+    }
+
+    #[test]
+    fn check_includes_both_bounds() {
+        let description = PropertyDescription::new(
+            "speed",
+            DataType::Float,
+            DataRange::FloatRange(-1.0, 2.0, 0.1),
+            "Speed",
+        );
+        for value in [-1.0, 0.0, 2.0] {
+            assert!(
+                description.check(&DataHolder::Float(value)).is_ok(),
+                "{}",
+                value
+            );
+        }
+
+        let error = InputError::OutOfRange {
+            name: "speed".to_string(),
+            min: -1.0,
+            max: 2.0,
+        };
+        for value in [-1.5, 2.01, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                description.check(&DataHolder::Float(value)),
+                Err(error.clone()),
+                "{}",
+                value
+            );
+        }
+
+        let description = PropertyDescription::new(
+            "count",
+            DataType::Int,
+            DataRange::IntRange(1, 8, 1),
+            "Count",
+        );
+        assert!(description.check(&DataHolder::Int(1)).is_ok());
+        assert!(description.check(&DataHolder::Int(8)).is_ok());
+        assert!(matches!(
+            description.check(&DataHolder::Int(0)),
+            Err(InputError::OutOfRange { .. })
+        ));
+        assert!(matches!(
+            description.check(&DataHolder::Int(9)),
+            Err(InputError::OutOfRange { .. })
+        ));
+
+        let description =
+            PropertyDescription::new("color", DataType::Float3, DataRange::ColorRange, "Color");
+        assert!(description
+            .check(&DataHolder::Float3([0.0, 0.5, 1.0]))
+            .is_ok());
+        for color in [[1.5, 0.0, 0.0], [0.0, -0.1, 0.0], [0.0, 0.0, f32::NAN]] {
+            assert!(
+                matches!(
+                    description.check(&DataHolder::Float3(color)),
+                    Err(InputError::OutOfRange { .. })
+                ),
+                "{:?}",
+                color
+            );
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::fmt;
+
+use super::DataType;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputError {
+    UnknownInputType(String),
+    ConfigMismatch {
+        expected: String,
+        found: String,
+    },
+    UnknownUniform(String),
+    UnknownProperty(String),
+    WrongValueType {
+        name: String,
+        expected: Vec<DataType>,
+        found: DataType,
+    },
+    OutOfRange {
+        name: String,
+        min: f64,
+        max: f64,
+    },
+    InvalidValue {
+        name: String,
+        value: String,
+        expected: Vec<String>,
+    },
+    UniformCollision {
+        name: String,
+        sources: Vec<String>,
+    },
+    QueueFull(String),
+    Unsupported(String),
+    Script(String),
+    DeviceLost(String),
+    EndOfStream,
+    Other(String),
+}
+
+pub type InputResult<T> = std::result::Result<T, InputError>;
+
+impl InputError {
+    pub fn wrong_value_type(name: &str, expected: &[DataType], found: DataType) -> Self {
+        Self::WrongValueType {
+            name: name.to_string(),
+            expected: expected.to_vec(),
+            found,
+        }
+    }
+
+    pub fn invalid_value(name: &str, value: &str, expected: &[&str]) -> Self {
+        Self::InvalidValue {
+            name: name.to_string(),
+            value: value.to_string(),
+            expected: expected.iter().map(|value| value.to_string()).collect(),
+        }
+    }
+
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Self::DeviceLost(_) | Self::Other(_))
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownInputType(kind) => write!(f, "Unknown input type: {}", kind),
+            Self::ConfigMismatch { expected, found } => write!(
+                f,
+                "Input constructor for {} received a {} config",
+                expected, found
+            ),
+            Self::UnknownUniform(name) => write!(f, "Unknown uniform: {}", name),
+            Self::UnknownProperty(name) => write!(f, "Unknown property: {}", name),
+            Self::WrongValueType {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Wrong value type for {}: expected one of {:?}, found {:?}",
+                name, expected, found
+            ),
+            Self::OutOfRange { name, min, max } => {
+                write!(f, "Value for {} is out of range {}..{}", name, min, max)
+            }
+            Self::InvalidValue {
+                name,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid value for {}: {}, expected one of {}",
+                name,
+                value,
+                expected.join(", ")
+            ),
+            Self::UniformCollision { name, sources } => write!(
+                f,
+                "Uniform {} is produced by several sources: {}",
+                name,
+                sources.join(", ")
+            ),
+            Self::QueueFull(reason) => write!(f, "Input queue full: {}", reason),
+            Self::Unsupported(operation) => write!(f, "Unsupported input operation: {}", operation),
+            Self::Script(reason) => write!(f, "Script error: {}", reason),
+            Self::DeviceLost(reason) => write!(f, "Input device lost: {}", reason),
+            Self::EndOfStream => write!(f, "End of stream"),
+            Self::Other(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl Error for InputError {}
+
+impl From<anyhow::Error> for InputError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::Other(format!("{:#}", error))
+    }
+}
+
+impl From<std::io::Error> for InputError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Other(error.to_string())
+    }
+}
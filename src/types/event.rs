@@ -0,0 +1,23 @@
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum PointerEvent {
+    Resized { width: f32, height: f32 },
+    Moved { x: f32, y: f32 },
+    Pressed { button: u8 },
+    Released { button: u8 },
+    Scrolled { x: f32, y: f32 },
+    Left,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum KeyEvent {
+    Pressed(u32),
+    Released(u32),
+    FocusLost,
+}
+
+// Window events are broadcast to every input, providers ignore the ones they have no use for
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum InputEvent {
+    Pointer(PointerEvent),
+    Key(KeyEvent),
+}
@@ -1,6 +1,48 @@
+// Serialized name of InputSource::ProjectOutput, inputs and stages cannot use it
+pub const PROJECT_OUTPUT_NAME: &str = "ProjectOutput";
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum InputSource {
+    Input(String),
+    Stage(String),
+    PreviousStage(String),
+    ProjectOutput,
+}
+
+impl InputSource {
+    pub fn is_feedback(&self) -> bool {
+        matches!(self, Self::PreviousStage(_) | Self::ProjectOutput)
+    }
+}
+
+// Plain names are kept for older projects and resolve to the input or stage of that name,
+// a name shared by both is ambiguous and must use an explicit source
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum InputReference {
+    Source(InputSource),
+    Named(String),
+}
+
+impl From<InputSource> for InputReference {
+    fn from(source: InputSource) -> Self {
+        Self::Source(source)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum InputSampler {
-    Nearest(String),
-    Linear(String),
-    Mipmaps(String),
+    Nearest(InputReference),
+    Linear(InputReference),
+    Mipmaps(InputReference),
+}
+
+impl InputSampler {
+    pub fn get_reference(&self) -> &InputReference {
+        match self {
+            Self::Nearest(reference) | Self::Linear(reference) | Self::Mipmaps(reference) => {
+                reference
+            }
+        }
+    }
 }
@@ -1,30 +1,80 @@
-use anyhow::Result;
-
 pub mod automation;
 pub mod buffer;
 pub mod data;
+pub mod description;
+pub mod error;
+pub mod event;
 pub mod input;
+pub mod playback;
+pub mod store;
 
 pub use automation::*;
 pub use buffer::*;
 pub use data::*;
+pub use description::*;
+pub use error::*;
+pub use event::*;
 pub use input::*;
+pub use playback::*;
+pub use store::*;
 
 pub trait InputProvider {
     fn set_name(&mut self, name: &str);
     fn provides(&self) -> Vec<String>;
-    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder>;
+    fn describe(&self) -> Vec<UniformDescription> {
+        self.provides()
+            .iter()
+            .map(|name| UniformDescription::unknown(name))
+            .collect()
+    }
+    fn get_generation(&self, uniform_name: &str) -> InputResult<u64>;
+    fn borrow(&mut self, uniform_name: &str) -> InputResult<Option<&DataHolder>>;
+    fn get(&mut self, uniform_name: &str) -> InputResult<Option<DataHolder>> {
+        Ok(self.borrow(uniform_name)?.cloned())
+    }
 
-    fn set_property(&mut self, property: &str, value: &DataHolder);
-    fn set_beat(&mut self, _bpm: f64, _sync: bool) {}
+    fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()>;
+    fn list_properties(&self) -> Vec<PropertyDescription> {
+        Vec::new()
+    }
+    fn get_property(&self, property: &str) -> InputResult<DataHolder> {
+        Err(InputError::UnknownProperty(property.to_string()))
+    }
+    // Receives the beat position since the clock started, not the tempo: callers that used
+    // to pass a bpm here must pass `Clock::get_beat()` instead
+    fn set_beat(&mut self, _beat: f64, _sync: bool) {}
     fn set_time(&mut self, _time: f64, _sync: bool) {}
-    fn stop(&mut self) -> Result<()> {
+    fn handle_event(&mut self, _event: &InputEvent) {}
+
+    fn is_seekable(&self) -> bool {
+        false
+    }
+    fn seek_time(&mut self, _time: f64) -> InputResult<()> {
+        Err(InputError::Unsupported("seek_time".to_string()))
+    }
+    fn seek_beat(&mut self, _beat: f64) -> InputResult<()> {
+        Err(InputError::Unsupported("seek_beat".to_string()))
+    }
+    fn get_duration(&self) -> Option<f64> {
+        None
+    }
+    fn get_position(&self) -> Option<f64> {
+        None
+    }
+    fn is_looping(&self) -> bool {
+        false
+    }
+    fn set_looping(&mut self, _looping: bool) -> InputResult<()> {
+        Err(InputError::Unsupported("set_looping".to_string()))
+    }
+
+    fn stop(&mut self) -> InputResult<()> {
         Ok(())
     }
-    fn pause(&mut self) -> Result<()> {
+    fn pause(&mut self) -> InputResult<()> {
         Ok(())
     }
-    fn play(&mut self) -> Result<()> {
+    fn play(&mut self) -> InputResult<()> {
         Ok(())
     }
 }
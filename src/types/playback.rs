@@ -0,0 +1,509 @@
+use super::{
+    Automation, DataHolder, DataRange, DataType, InputError, InputResult, PropertyDescription,
+    Speed,
+};
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum PlaybackMode {
+    #[default]
+    Forward,
+    Reverse,
+    PingPong,
+}
+
+impl PlaybackMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Forward => "Forward",
+            Self::Reverse => "Reverse",
+            Self::PingPong => "PingPong",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Forward" => Some(Self::Forward),
+            "Reverse" => Some(Self::Reverse),
+            "PingPong" => Some(Self::PingPong),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LoopRegion {
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CueMarker {
+    pub name: String,
+    pub position: f64,
+    pub quantization: f64,
+}
+
+impl CueMarker {
+    pub fn next_trigger_beat(&self, beat: f64) -> f64 {
+        if self.quantization <= 0.0 {
+            return beat;
+        }
+
+        (beat / self.quantization).ceil() * self.quantization
+    }
+}
+
+// A cue jump waiting for its quantized beat
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArmedCue {
+    pub name: String,
+    pub position: f64,
+    pub beat: f64,
+}
+
+impl ArmedCue {
+    pub fn is_due(&self, beat: f64) -> bool {
+        beat >= self.beat
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PlaybackConfig {
+    pub mode: PlaybackMode,
+    pub looping: bool,
+    pub in_point: f64,
+    pub out_point: Option<f64>,
+    pub loop_region: Option<LoopRegion>,
+    pub start_offset: f64,
+    pub cues: Vec<CueMarker>,
+    pub speed_automation: Automation,
+}
+
+impl Default for PlaybackConfig {
+    fn default() -> Self {
+        Self {
+            mode: PlaybackMode::Forward,
+            looping: true,
+            in_point: 0.0,
+            out_point: None,
+            loop_region: None,
+            start_offset: 0.0,
+            cues: Vec::new(),
+            speed_automation: Automation::None,
+        }
+    }
+}
+
+impl PlaybackConfig {
+    pub fn arm_cue(&self, name: &str, beat: f64) -> InputResult<ArmedCue> {
+        match self.cues.iter().find(|cue| cue.name == name) {
+            Some(cue) => Ok(ArmedCue {
+                name: cue.name.clone(),
+                position: cue.position,
+                beat: cue.next_trigger_beat(beat),
+            }),
+            None => Err(InputError::invalid_value(
+                "cue",
+                name,
+                &self
+                    .cues
+                    .iter()
+                    .map(|cue| cue.name.as_str())
+                    .collect::<Vec<&str>>(),
+            )),
+        }
+    }
+
+    pub fn get_bounds(&self, duration: f64) -> (f64, f64) {
+        let duration = duration.max(0.0);
+        let in_point = self.in_point.max(0.0).min(duration);
+        let out_point = self
+            .out_point
+            .unwrap_or(duration)
+            .max(in_point)
+            .min(duration);
+
+        if let Some(loop_region) = self.loop_region {
+            let start = loop_region.start.max(in_point).min(out_point);
+            let end = loop_region.end.max(start).min(out_point);
+            (start, end)
+        } else {
+            (in_point, out_point)
+        }
+    }
+
+    pub fn resolve_position(&self, elapsed: f64, duration: f64) -> f64 {
+        let (start, end) = self.get_bounds(duration);
+        let span = end - start;
+        if span <= 0.0 {
+            return start;
+        }
+
+        // Negative cursors come from reverse rates and wrap like positive ones when looping
+        let cursor = elapsed + self.start_offset;
+
+        match self.mode {
+            PlaybackMode::Forward => start + self.wrap(cursor, span),
+            PlaybackMode::Reverse => end - self.wrap(cursor, span),
+            PlaybackMode::PingPong => {
+                let cursor = self.wrap(cursor, span * 2.0);
+                start + span - (cursor - span).abs()
+            }
+        }
+    }
+
+    fn wrap(&self, cursor: f64, span: f64) -> f64 {
+        if self.looping {
+            cursor.rem_euclid(span)
+        } else {
+            cursor.clamp(0.0, span)
+        }
+    }
+
+    pub fn get_speed(&self, speed: Speed, beat: f64) -> Speed {
+        let value = match speed {
+            Speed::Fps(value) | Speed::Fpb(value) => DataHolder::Float(value),
+        };
+
+        let value = match self.speed_automation.apply(&value, beat) {
+            Some(DataHolder::Float(value)) => value,
+            _ => return speed,
+        };
+
+        match speed {
+            Speed::Fps(_) => Speed::Fps(value),
+            Speed::Fpb(_) => Speed::Fpb(value),
+        }
+    }
+
+    // Rate multipliers are automated in beats whatever clock drives the playback
+    pub fn get_rate(&self, rate: f64, beat: f64) -> f64 {
+        match self
+            .speed_automation
+            .apply(&DataHolder::Float(rate as f32), beat)
+        {
+            Some(DataHolder::Float(rate)) => rate as f64,
+            _ => rate,
+        }
+    }
+
+    pub fn list_properties() -> Vec<PropertyDescription> {
+        vec![
+            PropertyDescription::new(
+                "playback_mode",
+                DataType::String,
+                DataRange::None,
+                "Forward, Reverse or PingPong",
+            ),
+            PropertyDescription::new(
+                "looping",
+                DataType::Bool,
+                DataRange::None,
+                "Loop between the in and out points",
+            ),
+            PropertyDescription::new(
+                "in_point",
+                DataType::Float,
+                DataRange::None,
+                "Playback start, in seconds",
+            ),
+            PropertyDescription::new(
+                "out_point",
+                DataType::Float,
+                DataRange::None,
+                "Playback end, in seconds, negative to unset",
+            ),
+            PropertyDescription::new(
+                "loop_region",
+                DataType::Float2,
+                DataRange::None,
+                "Loop start and end, in seconds, negative to unset",
+            ),
+            PropertyDescription::new(
+                "start_offset",
+                DataType::Float,
+                DataRange::None,
+                "Offset applied to the playback cursor, in seconds",
+            ),
+            PropertyDescription::new(
+                "cue_positions",
+                DataType::FloatArray,
+                DataRange::None,
+                "Cue marker positions, in seconds",
+            ),
+            PropertyDescription::new(
+                "cue_quantizations",
+                DataType::FloatArray,
+                DataRange::None,
+                "Cue marker jump quantization, in beats",
+            ),
+            PropertyDescription::new(
+                "cue",
+                DataType::String,
+                DataRange::None,
+                "Name of the cue to jump to on its next quantized beat",
+            ),
+        ]
+    }
+
+    // Unset optional points are reported with the negative values that clear them
+    pub fn get_properties(&self) -> Vec<(String, DataHolder)> {
+        let loop_region = self.loop_region.map_or([-1.0, -1.0], |loop_region| {
+            [loop_region.start as f32, loop_region.end as f32]
+        });
+
+        vec![
+            (
+                "playback_mode".to_string(),
+                DataHolder::String(self.mode.name().to_string()),
+            ),
+            ("looping".to_string(), DataHolder::Bool(self.looping)),
+            (
+                "in_point".to_string(),
+                DataHolder::Float(self.in_point as f32),
+            ),
+            (
+                "out_point".to_string(),
+                DataHolder::Float(self.out_point.map_or(-1.0, |out_point| out_point as f32)),
+            ),
+            ("loop_region".to_string(), DataHolder::Float2(loop_region)),
+            (
+                "start_offset".to_string(),
+                DataHolder::Float(self.start_offset as f32),
+            ),
+            (
+                "cue_positions".to_string(),
+                DataHolder::FloatArray(self.cues.iter().map(|cue| cue.position as f32).collect()),
+            ),
+            (
+                "cue_quantizations".to_string(),
+                DataHolder::FloatArray(
+                    self.cues
+                        .iter()
+                        .map(|cue| cue.quantization as f32)
+                        .collect(),
+                ),
+            ),
+        ]
+    }
+
+    // New cues are named after their index, skipping names that are already taken
+    fn get_free_cue_name(&self, index: usize) -> String {
+        (index..)
+            .map(|index| format!("cue_{}", index))
+            .find(|name| self.cues.iter().all(|cue| &cue.name != name))
+            .unwrap_or_default()
+    }
+
+    pub fn set_property(&mut self, property: &str, value: &DataHolder) -> InputResult<()> {
+        match Self::list_properties()
+            .iter()
+            .find(|description| description.name == property)
+        {
+            Some(description) => description.check(value)?,
+            None => return Err(InputError::UnknownProperty(property.to_string())),
+        }
+
+        match (property, value) {
+            ("playback_mode", DataHolder::String(mode)) => {
+                self.mode = PlaybackMode::from_name(mode).ok_or_else(|| {
+                    InputError::invalid_value(property, mode, &["Forward", "Reverse", "PingPong"])
+                })?
+            }
+            ("looping", DataHolder::Bool(looping)) => self.looping = *looping,
+            ("in_point", DataHolder::Float(in_point)) => self.in_point = *in_point as f64,
+            // Negative or empty values clear the optional points
+            ("out_point", DataHolder::Float(out_point)) if *out_point < 0.0 => {
+                self.out_point = None
+            }
+            ("out_point", DataHolder::Float(out_point)) => self.out_point = Some(*out_point as f64),
+            ("loop_region", DataHolder::Float2([start, end])) if *start < 0.0 || *end < 0.0 => {
+                self.loop_region = None
+            }
+            ("loop_region", DataHolder::Float2([start, end])) => {
+                self.loop_region = Some(LoopRegion {
+                    start: *start as f64,
+                    end: *end as f64,
+                })
+            }
+            ("start_offset", DataHolder::Float(start_offset)) => {
+                self.start_offset = *start_offset as f64
+            }
+            ("cue_positions", DataHolder::FloatArray(positions)) => {
+                self.cues.truncate(positions.len());
+                for index in self.cues.len()..positions.len() {
+                    let name = self.get_free_cue_name(index);
+                    self.cues.push(CueMarker {
+                        name,
+                        position: 0.0,
+                        quantization: 1.0,
+                    });
+                }
+                for (cue, position) in self.cues.iter_mut().zip(positions.iter()) {
+                    cue.position = *position as f64;
+                }
+            }
+            ("cue_quantizations", DataHolder::FloatArray(quantizations)) => {
+                for (cue, quantization) in self.cues.iter_mut().zip(quantizations.iter()) {
+                    cue.quantization = *quantization as f64;
+                }
+            }
+            _ => return Err(InputError::UnknownProperty(property.to_string())),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_property(config: &PlaybackConfig, property: &str) -> Option<DataHolder> {
+        config
+            .get_properties()
+            .into_iter()
+            .find(|(name, _)| name == property)
+            .map(|(_, value)| value)
+    }
+
+    #[test]
+    fn every_listed_property_is_reported() {
+        let config = PlaybackConfig::default();
+        let properties = config.get_properties();
+
+        for description in PlaybackConfig::list_properties() {
+            if description.name == "cue" {
+                continue;
+            }
+
+            let value = properties
+                .iter()
+                .find(|(name, _)| *name == description.name)
+                .map(|(_, value)| value)
+                .unwrap_or_else(|| panic!("Missing property {}", description.name));
+            assert_eq!(value.get_type(), description.data_type);
+        }
+    }
+
+    #[test]
+    fn optional_points_roundtrip_through_sentinels() {
+        let mut config = PlaybackConfig::default();
+
+        config
+            .set_property("out_point", &DataHolder::Float(2.0))
+            .unwrap();
+        config
+            .set_property("loop_region", &DataHolder::Float2([0.5, 1.5]))
+            .unwrap();
+        assert_eq!(config.out_point, Some(2.0));
+        assert_eq!(
+            get_property(&config, "loop_region"),
+            Some(DataHolder::Float2([0.5, 1.5]))
+        );
+
+        for (property, value) in config.clone().get_properties() {
+            config.set_property(&property, &value).unwrap();
+        }
+        assert_eq!(
+            config.loop_region,
+            Some(LoopRegion {
+                start: 0.5,
+                end: 1.5
+            })
+        );
+
+        config
+            .set_property("out_point", &DataHolder::Float(-1.0))
+            .unwrap();
+        config
+            .set_property("loop_region", &DataHolder::Float2([-1.0, -1.0]))
+            .unwrap();
+        assert_eq!(config.out_point, None);
+        assert_eq!(config.loop_region, None);
+        assert_eq!(
+            get_property(&config, "out_point"),
+            Some(DataHolder::Float(-1.0))
+        );
+        assert!(matches!(
+            config.set_property("loop_region", &DataHolder::FloatArray(Vec::new())),
+            Err(InputError::WrongValueType { .. })
+        ));
+    }
+
+    #[test]
+    fn cue_positions_keep_names() {
+        let mut config = PlaybackConfig {
+            cues: vec![CueMarker {
+                name: "intro".to_string(),
+                position: 0.0,
+                quantization: 4.0,
+            }],
+            ..PlaybackConfig::default()
+        };
+
+        config
+            .set_property("cue_positions", &DataHolder::FloatArray(vec![1.0, 2.0]))
+            .unwrap();
+        assert_eq!(config.cues[0].name, "intro");
+        assert_eq!(config.cues[0].position, 1.0);
+        assert_eq!(config.cues[0].quantization, 4.0);
+        assert_eq!(config.cues[1].name, "cue_1");
+
+        let armed_cue = config.arm_cue("cue_1", 2.5).unwrap();
+        assert_eq!(armed_cue.position, 2.0);
+        assert_eq!(armed_cue.beat, 3.0);
+        assert!(!armed_cue.is_due(2.9));
+        assert!(armed_cue.is_due(3.0));
+
+        assert_eq!(config.arm_cue("intro", 4.0).unwrap().beat, 4.0);
+        assert_eq!(config.arm_cue("intro", 4.1).unwrap().beat, 8.0);
+        assert!(config.arm_cue("outro", 0.0).is_err());
+    }
+
+    #[test]
+    fn new_cues_avoid_names_in_use() {
+        let mut config = PlaybackConfig {
+            cues: vec![CueMarker {
+                name: "cue_1".to_string(),
+                position: 0.0,
+                quantization: 1.0,
+            }],
+            ..PlaybackConfig::default()
+        };
+
+        config
+            .set_property(
+                "cue_positions",
+                &DataHolder::FloatArray(vec![0.0, 1.0, 2.0]),
+            )
+            .unwrap();
+        let names: Vec<&str> = config.cues.iter().map(|cue| cue.name.as_str()).collect();
+        assert_eq!(names, ["cue_1", "cue_2", "cue_3"]);
+    }
+
+    #[test]
+    fn invalid_properties_are_reported() {
+        let mut config = PlaybackConfig::default();
+
+        assert_eq!(
+            config.set_property("playback_mode", &DataHolder::String("Sideways".to_string())),
+            Err(InputError::invalid_value(
+                "playback_mode",
+                "Sideways",
+                &["Forward", "Reverse", "PingPong"]
+            ))
+        );
+        assert_eq!(config.mode, PlaybackMode::Forward);
+        assert_eq!(
+            config.set_property("speed", &DataHolder::Float(1.0)),
+            Err(InputError::UnknownProperty("speed".to_string()))
+        );
+
+        config
+            .set_property("playback_mode", &DataHolder::String("PingPong".to_string()))
+            .unwrap();
+        assert_eq!(config.mode, PlaybackMode::PingPong);
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use super::DataHolder;
+
+// Generations come from one store-wide counter, so a uniform that is removed and set again
+// never hands out a generation a consumer has already seen
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UniformStore {
+    values: HashMap<String, (u64, DataHolder)>,
+    last_generation: u64,
+}
+
+impl UniformStore {
+    pub fn set(&mut self, name: &str, value: DataHolder) -> u64 {
+        self.last_generation += 1;
+        match self.values.get_mut(name) {
+            Some(entry) => *entry = (self.last_generation, value),
+            None => {
+                self.values
+                    .insert(name.to_string(), (self.last_generation, value));
+            }
+        }
+
+        self.last_generation
+    }
+
+    pub fn set_if_changed(&mut self, name: &str, value: DataHolder) -> u64 {
+        match self.values.get(name) {
+            Some((generation, current_value)) if current_value == &value => *generation,
+            _ => self.set(name, value),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DataHolder> {
+        self.values.get(name).map(|(_, value)| value)
+    }
+
+    pub fn get_generation(&self, name: &str) -> u64 {
+        self.values
+            .get(name)
+            .map(|(generation, _)| *generation)
+            .unwrap_or_default()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<DataHolder> {
+        self.values.remove(name).map(|(_, value)| value)
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generations_only_increase() {
+        let mut store = UniformStore::default();
+        assert_eq!(store.get_generation("a"), 0);
+        assert_eq!(store.get("a"), None);
+
+        let first = store.set("a", DataHolder::Int(1));
+        let second = store.set("b", DataHolder::Int(1));
+        assert!(first > 0 && second > first);
+        assert_eq!(store.get_generation("a"), first);
+
+        // Setting the same value again still counts as a write
+        let third = store.set("a", DataHolder::Int(1));
+        assert!(third > second);
+        assert_eq!(store.get_generation("a"), third);
+        assert_eq!(store.get_generation("b"), second);
+    }
+
+    #[test]
+    fn unchanged_values_keep_their_generation() {
+        let mut store = UniformStore::default();
+        let first = store.set_if_changed("a", DataHolder::Float(0.5));
+        assert_eq!(store.set_if_changed("a", DataHolder::Float(0.5)), first);
+        assert_eq!(store.get_generation("a"), first);
+
+        let second = store.set_if_changed("a", DataHolder::Float(1.0));
+        assert!(second > first);
+        assert_eq!(store.get("a"), Some(&DataHolder::Float(1.0)));
+    }
+
+    #[test]
+    fn removed_uniforms_never_reuse_a_generation() {
+        let mut store = UniformStore::default();
+        let first = store.set("a", DataHolder::Int(1));
+
+        assert_eq!(store.remove("a"), Some(DataHolder::Int(1)));
+        assert_eq!(store.get_generation("a"), 0);
+        assert!(store.set("a", DataHolder::Int(1)) > first);
+
+        store.clear();
+        assert_eq!(store.get("a"), None);
+        assert!(store.set_if_changed("a", DataHolder::Int(1)) > first + 1);
+    }
+}